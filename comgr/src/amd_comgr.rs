@@ -156,6 +156,20 @@ pub struct amd_comgr_action_info_s {
 #[doc = " An action information object holds all the necessary information,"]
 #[doc = " excluding the input data objects, required to perform an action."]
 pub type amd_comgr_action_info_t = amd_comgr_action_info_s;
+#[doc = " @brief A handle to a metadata node."]
+#[doc = ""]
+#[doc = " A metadata node handle is used to traverse the metadata associated"]
+#[doc = " with a data node."]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct amd_comgr_metadata_node_s {
+    pub handle: u64,
+}
+#[doc = " @brief A handle to a metadata node."]
+#[doc = ""]
+#[doc = " A metadata node handle is used to traverse the metadata associated"]
+#[doc = " with a data node."]
+pub type amd_comgr_metadata_node_t = amd_comgr_metadata_node_s;
 impl amd_comgr_action_kind_s {
     #[doc = " Preprocess each source data object in @p input in order. For each"]
     #[doc = " successful preprocessor invocation, add a source data object to @p result."]
@@ -418,6 +432,36 @@ impl amd_comgr_action_kind_s {
 pub struct amd_comgr_action_kind_s(pub ::std::os::raw::c_uint);
 #[doc = " @brief The kinds of actions that can be performed."]
 pub use self::amd_comgr_action_kind_s as amd_comgr_action_kind_t;
+impl amd_comgr_metadata_kind_s {
+    #[doc = " The NULL metadata handle."]
+    pub const AMD_COMGR_METADATA_KIND_NULL: amd_comgr_metadata_kind_s =
+        amd_comgr_metadata_kind_s(0);
+}
+impl amd_comgr_metadata_kind_s {
+    #[doc = " A sting value."]
+    pub const AMD_COMGR_METADATA_KIND_STRING: amd_comgr_metadata_kind_s =
+        amd_comgr_metadata_kind_s(1);
+}
+impl amd_comgr_metadata_kind_s {
+    #[doc = " A map that consists of a set of key and value pairs."]
+    pub const AMD_COMGR_METADATA_KIND_MAP: amd_comgr_metadata_kind_s = amd_comgr_metadata_kind_s(2);
+}
+impl amd_comgr_metadata_kind_s {
+    #[doc = " A list that consists of a sequence of values."]
+    pub const AMD_COMGR_METADATA_KIND_LIST: amd_comgr_metadata_kind_s =
+        amd_comgr_metadata_kind_s(3);
+}
+impl amd_comgr_metadata_kind_s {
+    #[doc = " Marker for last valid metadata kind."]
+    pub const AMD_COMGR_METADATA_KIND_LAST: amd_comgr_metadata_kind_s =
+        amd_comgr_metadata_kind_s(3);
+}
+#[repr(transparent)]
+#[doc = " @brief The kinds of metadata nodes."]
+#[derive(Copy, Clone, Hash, PartialEq, Eq)]
+pub struct amd_comgr_metadata_kind_s(pub ::std::os::raw::c_uint);
+#[doc = " @brief The kinds of metadata nodes."]
+pub use self::amd_comgr_metadata_kind_s as amd_comgr_metadata_kind_t;
 extern crate libloading;
 pub struct LibComgr {
     __library: ::libloading::Library,
@@ -445,6 +489,12 @@ pub struct LibComgr {
         size: *mut usize,
         name: *mut ::std::os::raw::c_char,
     ) -> amd_comgr_status_t,
+    pub amd_comgr_get_data_metadata: unsafe extern "C" fn(
+        data: amd_comgr_data_t,
+        metadata: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t,
+    pub amd_comgr_destroy_metadata:
+        unsafe extern "C" fn(metadata: amd_comgr_metadata_node_t) -> amd_comgr_status_t,
     pub amd_comgr_create_data_set:
         unsafe extern "C" fn(data_set: *mut amd_comgr_data_set_t) -> amd_comgr_status_t,
     pub amd_comgr_destroy_data_set:
@@ -476,12 +526,39 @@ pub struct LibComgr {
         options: *mut *const ::std::os::raw::c_char,
         count: usize,
     ) -> amd_comgr_status_t,
+    pub amd_comgr_action_info_set_logging: unsafe extern "C" fn(
+        action_info: amd_comgr_action_info_t,
+        logging: bool,
+    ) -> amd_comgr_status_t,
     pub amd_comgr_do_action: unsafe extern "C" fn(
         kind: amd_comgr_action_kind_t,
         info: amd_comgr_action_info_t,
         input: amd_comgr_data_set_t,
         result: amd_comgr_data_set_t,
     ) -> amd_comgr_status_t,
+    pub amd_comgr_get_metadata_kind: unsafe extern "C" fn(
+        metadata: amd_comgr_metadata_node_t,
+        kind: *mut amd_comgr_metadata_kind_t,
+    ) -> amd_comgr_status_t,
+    pub amd_comgr_get_metadata_string: unsafe extern "C" fn(
+        metadata: amd_comgr_metadata_node_t,
+        size: *mut usize,
+        string: *mut ::std::os::raw::c_char,
+    ) -> amd_comgr_status_t,
+    pub amd_comgr_metadata_lookup: unsafe extern "C" fn(
+        metadata: amd_comgr_metadata_node_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t,
+    pub amd_comgr_get_metadata_list_size: unsafe extern "C" fn(
+        metadata: amd_comgr_metadata_node_t,
+        size: *mut usize,
+    ) -> amd_comgr_status_t,
+    pub amd_comgr_index_list_metadata: unsafe extern "C" fn(
+        metadata: amd_comgr_metadata_node_t,
+        index: usize,
+        value: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t,
 }
 impl LibComgr {
     pub unsafe fn new<P>(path: P) -> Result<Self, ::libloading::Error>
@@ -506,6 +583,12 @@ impl LibComgr {
         let amd_comgr_get_data_name = __library
             .get(b"amd_comgr_get_data_name\0")
             .map(|sym| *sym)?;
+        let amd_comgr_get_data_metadata = __library
+            .get(b"amd_comgr_get_data_metadata\0")
+            .map(|sym| *sym)?;
+        let amd_comgr_destroy_metadata = __library
+            .get(b"amd_comgr_destroy_metadata\0")
+            .map(|sym| *sym)?;
         let amd_comgr_create_data_set = __library
             .get(b"amd_comgr_create_data_set\0")
             .map(|sym| *sym)?;
@@ -531,7 +614,25 @@ impl LibComgr {
         let amd_comgr_action_info_set_option_list = __library
             .get(b"amd_comgr_action_info_set_option_list\0")
             .map(|sym| *sym)?;
+        let amd_comgr_action_info_set_logging = __library
+            .get(b"amd_comgr_action_info_set_logging\0")
+            .map(|sym| *sym)?;
         let amd_comgr_do_action = __library.get(b"amd_comgr_do_action\0").map(|sym| *sym)?;
+        let amd_comgr_get_metadata_kind = __library
+            .get(b"amd_comgr_get_metadata_kind\0")
+            .map(|sym| *sym)?;
+        let amd_comgr_get_metadata_string = __library
+            .get(b"amd_comgr_get_metadata_string\0")
+            .map(|sym| *sym)?;
+        let amd_comgr_metadata_lookup = __library
+            .get(b"amd_comgr_metadata_lookup\0")
+            .map(|sym| *sym)?;
+        let amd_comgr_get_metadata_list_size = __library
+            .get(b"amd_comgr_get_metadata_list_size\0")
+            .map(|sym| *sym)?;
+        let amd_comgr_index_list_metadata = __library
+            .get(b"amd_comgr_index_list_metadata\0")
+            .map(|sym| *sym)?;
         Ok(LibComgr {
             __library,
             amd_comgr_create_data,
@@ -540,6 +641,8 @@ impl LibComgr {
             amd_comgr_set_data_name,
             amd_comgr_get_data,
             amd_comgr_get_data_name,
+            amd_comgr_get_data_metadata,
+            amd_comgr_destroy_metadata,
             amd_comgr_create_data_set,
             amd_comgr_destroy_data_set,
             amd_comgr_data_set_add,
@@ -549,7 +652,13 @@ impl LibComgr {
             amd_comgr_action_info_set_isa_name,
             amd_comgr_action_info_set_language,
             amd_comgr_action_info_set_option_list,
+            amd_comgr_action_info_set_logging,
             amd_comgr_do_action,
+            amd_comgr_get_metadata_kind,
+            amd_comgr_get_metadata_string,
+            amd_comgr_metadata_lookup,
+            amd_comgr_get_metadata_list_size,
+            amd_comgr_index_list_metadata,
         })
     }
     #[must_use]
@@ -724,6 +833,51 @@ impl LibComgr {
         (self.amd_comgr_get_data_name)(data, size, name)
     }
     #[must_use]
+    #[doc = " @brief Get a handle to the metadata of a data object."]
+    #[doc = ""]
+    #[doc = " @param[in] data The data object to query."]
+    #[doc = ""]
+    #[doc = " @param[out] metadata A handle to the metadata of the data"]
+    #[doc = " object. If the data object has no metadata then the returned handle"]
+    #[doc = " has a kind of @p AMD_COMGR_METADATA_KIND_NULL. The"]
+    #[doc = " handle must be destroyed using @c amd_comgr_destroy_metadata."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " data is an invalid data object, or has kind @p"]
+    #[doc = " AMD_COMGR_DATA_KIND_UNDEF. @p metadata is NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to update the data object as out of resources."]
+    pub unsafe fn amd_comgr_get_data_metadata(
+        &self,
+        data: amd_comgr_data_t,
+        metadata: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_get_data_metadata)(data, metadata)
+    }
+    #[must_use]
+    #[doc = " @brief Destroy a metadata handle."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata A metadata handle to destroy."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has been executed"]
+    #[doc = " successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p metadata is an invalid"]
+    #[doc = " metadata handle."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES Unable to update metadata"]
+    #[doc = " handle as out of resources."]
+    pub unsafe fn amd_comgr_destroy_metadata(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_destroy_metadata)(metadata)
+    }
+    #[must_use]
     #[doc = " @brief Create a data set object."]
     #[doc = ""]
     #[doc = " @param[out] data_set A handle to the data set created. Initially it"]
@@ -961,6 +1115,29 @@ impl LibComgr {
         (self.amd_comgr_action_info_set_option_list)(action_info, options, count)
     }
     #[must_use]
+    #[doc = " @brief Set whether logging is enabled for an action info object."]
+    #[doc = ""]
+    #[doc = " @param[in] action_info A handle to the action info object to be"]
+    #[doc = " updated."]
+    #[doc = ""]
+    #[doc = " @param[in] logging Whether logging should be enabled or disable."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " action_info is an invalid action info object."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to update action info object as out of resources."]
+    pub unsafe fn amd_comgr_action_info_set_logging(
+        &self,
+        action_info: amd_comgr_action_info_t,
+        logging: bool,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_action_info_set_logging)(action_info, logging)
+    }
+    #[must_use]
     #[doc = " @brief Perform an action."]
     #[doc = ""]
     #[doc = " Each action ignores any data objects in @p input that it does not"]
@@ -1001,4 +1178,144 @@ impl LibComgr {
     ) -> amd_comgr_status_t {
         (self.amd_comgr_do_action)(kind, info, input, result)
     }
+    #[must_use]
+    #[doc = " @brief Get the kind of the metadata node."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata The metadata node to query."]
+    #[doc = ""]
+    #[doc = " @param[out] kind The kind of the metadata node."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " metadata is an invalid metadata node. @p kind is NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to create the data object as out of resources."]
+    pub unsafe fn amd_comgr_get_metadata_kind(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+        kind: *mut amd_comgr_metadata_kind_t,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_get_metadata_kind)(metadata, kind)
+    }
+    #[must_use]
+    #[doc = " @brief Get the string and/or string length from a metadata string"]
+    #[doc = " node."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata The metadata node to query."]
+    #[doc = ""]
+    #[doc = " @param[in, out] size On entry, the size of @p string. On return, if @p"]
+    #[doc = " string is NULL, set to the size of the string including the terminating null"]
+    #[doc = " character."]
+    #[doc = ""]
+    #[doc = " @param[out] string If not NULL, then the first @p size characters"]
+    #[doc = " of the string are copied. If NULL, no string is copied, and only @p"]
+    #[doc = " size is updated (useful in order to find the size of buffer required"]
+    #[doc = " to copy the string)."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " metadata is an invalid metadata node, or does not have kind @p"]
+    #[doc = " AMD_COMGR_METADATA_KIND_STRING. @p size is NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to update the data object as out of resources."]
+    pub unsafe fn amd_comgr_get_metadata_string(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+        size: *mut usize,
+        string: *mut ::std::os::raw::c_char,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_get_metadata_string)(metadata, size, string)
+    }
+    #[must_use]
+    #[doc = " @brief Use a string key to lookup an element of a metadata map"]
+    #[doc = " node and return the entry value."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata The metadata node to query."]
+    #[doc = ""]
+    #[doc = " @param[in] key A null terminated string that is the key to lookup."]
+    #[doc = ""]
+    #[doc = " @param[out] value The metadata node of the @p key element of the"]
+    #[doc = " @p metadata map metadata node. The handle must be destroyed"]
+    #[doc = " using @c amd_comgr_destroy_metadata."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR The map has no entry"]
+    #[doc = " with a string key with the value @p key."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " metadata is an invalid metadata node, or not of kind @p"]
+    #[doc = " AMD_COMGR_METADATA_KIND_MAP. @p key or @p value is"]
+    #[doc = " NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to lookup metadata as out of resources."]
+    pub unsafe fn amd_comgr_metadata_lookup(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_metadata_lookup)(metadata, key, value)
+    }
+    #[must_use]
+    #[doc = " @brief Get the list size from a metadata list node."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata The metadata node to query."]
+    #[doc = ""]
+    #[doc = " @param[out] size The number of entries in the list."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " metadata is an invalid metadata node, or does nopt have kind @p"]
+    #[doc = " AMD_COMGR_METADATA_KIND_LIST. @p size is NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to update the data object as out of resources."]
+    pub unsafe fn amd_comgr_get_metadata_list_size(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+        size: *mut usize,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_get_metadata_list_size)(metadata, size)
+    }
+    #[must_use]
+    #[doc = " @brief Return the Nth metadata node of a list metadata node."]
+    #[doc = ""]
+    #[doc = " @param[in] metadata The metadata node to query."]
+    #[doc = ""]
+    #[doc = " @param[in] index The index being requested. The first list element"]
+    #[doc = " is index 0."]
+    #[doc = ""]
+    #[doc = " @param[out] value The metadata node of the @p index element of the"]
+    #[doc = " @p metadata list metadata node. The handle must be destroyed"]
+    #[doc = " using @c amd_comgr_destroy_metadata."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_SUCCESS The function has"]
+    #[doc = " been executed successfully."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p"]
+    #[doc = " metadata is an invalid metadata node or not of kind @p"]
+    #[doc = " AMD_COMGR_METADATA_INFO_LIST. @p index is greater"]
+    #[doc = " than the number of list elements. @p value is NULL."]
+    #[doc = ""]
+    #[doc = " @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES"]
+    #[doc = " Unable to update action data object as out of resources."]
+    pub unsafe fn amd_comgr_index_list_metadata(
+        &self,
+        metadata: amd_comgr_metadata_node_t,
+        index: usize,
+        value: *mut amd_comgr_metadata_node_t,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_index_list_metadata)(metadata, index, value)
+    }
 }
@@ -0,0 +1,24 @@
+target datalayout = "e-p:64:64-p1:64:64-p2:32:32-p3:32:32-p4:64:64-p5:32:32-p6:32:32-i64:64-v16:16-v24:32-v32:32-v48:64-v96:128-v192:256-v256:256-v512:512-v1024:1024-v2048:2048-n32:64-S32-A5-G1-ni:7"
+target triple = "amdgcn-amd-amdhsa"
+
+; Grid-wide barriers (cooperative_groups grid.sync()) find their workspace
+; { u32 wsSize; u32 barrier; } through %envreg1 (low half) and %envreg2
+; (high half). Every module gets its own zero-initialized workspace, which is
+; shared by all launches of the module's kernels. The runtime orders
+; cooperative launches of a module one after another (GridWorkspace in zluda)
+@__zluda_ptx_impl__GRID_WORKSPACE = linkonce_odr hidden addrspace(1) global [2 x i32] zeroinitializer, align 8
+
+define linkonce_odr hidden i32 @__zluda_ptx_impl__sreg_envreg1() #0 {
+  %1 = ptrtoint ptr addrspace(1) @__zluda_ptx_impl__GRID_WORKSPACE to i64
+  %2 = trunc i64 %1 to i32
+  ret i32 %2
+}
+
+define linkonce_odr hidden i32 @__zluda_ptx_impl__sreg_envreg2() #0 {
+  %1 = ptrtoint ptr addrspace(1) @__zluda_ptx_impl__GRID_WORKSPACE to i64
+  %2 = lshr i64 %1, 32
+  %3 = trunc i64 %2 to i32
+  ret i32 %3
+}
+
+attributes #0 = { alwaysinline nounwind }
@@ -44,6 +44,7 @@ pub struct Comgr(LibComgr, AtomicU64);
 static WAVE32_MODULE: &'static [u8] = include_bytes!("wave32.ll");
 static WAVE32_ON_WAVE64_MODULE: &'static [u8] = include_bytes!("wave32_on_wave64.ll");
 static DOUBLE_WAVE32_ON_WAVE64_MODULE: &'static [u8] = include_bytes!("double_wave32_on_wave64.ll");
+static GRID_MODULE: &'static [u8] = include_bytes!("grid.ll");
 
 #[cfg(windows)]
 static OS_MODULE: &'static [u8] = include_bytes!("windows.ll");
@@ -80,10 +81,29 @@ impl Comgr {
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
         linker_module: &[u8],
     ) -> Result<Vec<u8>> {
-        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
-        let relocatable = self.build_relocatable_impl(compilation_mode, isa, &bitcode)?;
+        self.compile_with_log(
+            compilation_mode,
+            isa,
+            input_bitcode,
+            linker_module,
+            &mut Vec::new(),
+        )
+    }
+
+    // Same as `compile`, but diagnostics of a failed compilation step are
+    // appended to `log`
+    pub fn compile_with_log<'a>(
+        &self,
+        compilation_mode: CompilationMode,
+        isa: &'a CStr,
+        input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
+        linker_module: &[u8],
+        log: &mut Vec<String>,
+    ) -> Result<Vec<u8>> {
+        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode, log)?;
+        let relocatable = self.build_relocatable_impl(compilation_mode, isa, &bitcode, log)?;
         if !linker_module.is_empty() {
-            let source = self.assemble_source(isa, linker_module)?;
+            let source = self.assemble_source(isa, linker_module, log)?;
             self.link_relocatable_impl(
                 isa,
                 IntoIterator::into_iter([
@@ -93,6 +113,7 @@ impl Comgr {
                     )?,
                     &source,
                 ]),
+                log,
             )
         } else {
             self.link_relocatable_impl(
@@ -101,6 +122,7 @@ impl Comgr {
                     sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
                     0,
                 )?),
+                log,
             )
         }
     }
@@ -111,7 +133,8 @@ impl Comgr {
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, &'a CStr)>,
     ) -> Result<Bitcode<'this>> {
-        let data_set_bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
+        let data_set_bitcode =
+            self.link_bitcode_impl(compilation_mode, isa, input_bitcode, &mut Vec::new())?;
         Ok(Bitcode(data_set_bitcode))
     }
 
@@ -121,7 +144,8 @@ impl Comgr {
         isa: &'a CStr,
         bc: &Bitcode,
     ) -> Result<Relocatable> {
-        let data_set_relocatable = self.build_relocatable_impl(compilation_mode, isa, &bc.0)?;
+        let data_set_relocatable =
+            self.build_relocatable_impl(compilation_mode, isa, &bc.0, &mut Vec::new())?;
         Ok(Relocatable::from_data(data_set_relocatable.get_data_rc(
             self.clone(),
             sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
@@ -135,8 +159,10 @@ impl Comgr {
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, &'a CStr)>,
     ) -> Result<Relocatable> {
-        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
-        let data_set_relocatable = self.build_relocatable_impl(compilation_mode, isa, &bitcode)?;
+        let mut log = Vec::new();
+        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode, &mut log)?;
+        let data_set_relocatable =
+            self.build_relocatable_impl(compilation_mode, isa, &bitcode, &mut log)?;
         Ok(Relocatable::from_data(data_set_relocatable.get_data_rc(
             self.clone(),
             sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
@@ -149,7 +175,7 @@ impl Comgr {
         isa: &'a CStr,
         modules: impl Iterator<Item = &'a Relocatable>,
     ) -> Result<Vec<u8>> {
-        self.link_relocatable_impl(isa, modules.map(|reloc| &reloc.0))
+        self.link_relocatable_impl(isa, modules.map(|reloc| &reloc.0), &mut Vec::new())
     }
 
     pub fn version(&self) -> Result<String> {
@@ -167,6 +193,7 @@ impl Comgr {
             unsafe { CStr::from_bytes_with_nul_unchecked(b"\0") },
             iter::once(unsafe { CStr::from_bytes_with_nul_unchecked(b"-nogpuinc\0") }),
             Some(sys::amd_comgr_language_t::AMD_COMGR_LANGUAGE_HIP),
+            &mut Vec::new(),
         )?;
         let result = result.get_data(sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_SOURCE, 0)?;
         let result = result.get_data()?;
@@ -184,11 +211,68 @@ impl Comgr {
             .map_err(|_| sys::amd_comgr_status_t::AMD_COMGR_STATUS_ERROR)
     }
 
+    // AMDGPU assembly of a code object returned by `compile(...)`
+    pub fn disassemble(&self, isa: &CStr, executable: &[u8]) -> Result<String> {
+        let mut data_set = DataSet::new(self)?;
+        let data = Data::new(
+            self,
+            sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_EXECUTABLE,
+            executable,
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"executable.elf\0") },
+        )?;
+        data_set.add(&data)?;
+        let result = self.do_action(
+            sys::amd_comgr_action_kind_t::AMD_COMGR_ACTION_DISASSEMBLE_EXECUTABLE_TO_SOURCE,
+            &data_set,
+            isa,
+            iter::empty(),
+            None,
+            &mut Vec::new(),
+        )?;
+        let source = result
+            .get_data(sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_SOURCE, 0)?
+            .get_data()?;
+        Ok(String::from_utf8_lossy(&source).into_owned())
+    }
+
+    // Register, LDS and scratch usage of every kernel in a code object,
+    // as recorded by the compiler in its HSA metadata
+    pub fn kernel_resources(&self, executable: &[u8]) -> Result<Vec<KernelResources>> {
+        let data = Data::new(
+            self,
+            sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_EXECUTABLE,
+            executable,
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"executable.elf\0") },
+        )?;
+        let metadata = Metadata::of_data(self, &data)?;
+        let kernels = metadata.lookup(b"amdhsa.kernels\0")?;
+        (0..kernels.list_len()?)
+            .map(|index| {
+                let kernel = kernels.index(index)?;
+                Ok(KernelResources {
+                    name: kernel.lookup(b".name\0")?.string()?,
+                    sgpr_count: kernel.lookup_u32(b".sgpr_count\0")?,
+                    vgpr_count: kernel.lookup_u32(b".vgpr_count\0")?,
+                    // Only present on GPUs with accumulation registers (CDNA)
+                    agpr_count: kernel.lookup_u32(b".agpr_count\0").ok(),
+                    sgpr_spill_count: kernel.lookup_u32(b".sgpr_spill_count\0")?,
+                    vgpr_spill_count: kernel.lookup_u32(b".vgpr_spill_count\0")?,
+                    group_segment_fixed_size: kernel.lookup_u32(b".group_segment_fixed_size\0")?,
+                    private_segment_fixed_size: kernel
+                        .lookup_u32(b".private_segment_fixed_size\0")?,
+                    wavefront_size: kernel.lookup_u32(b".wavefront_size\0")?,
+                    max_flat_workgroup_size: kernel.lookup_u32(b".max_flat_workgroup_size\0")?,
+                })
+            })
+            .collect()
+    }
+
     fn link_bitcode_impl<'this, 'a>(
         &'this self,
         compilation_mode: CompilationMode,
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
+        log: &mut Vec<String>,
     ) -> Result<DataSet<'this>> {
         let mut bitcode_modules = DataSet::new(self)?;
         for (bc, name) in input_bitcode {
@@ -218,6 +302,13 @@ impl Comgr {
             unsafe { CStr::from_bytes_with_nul_unchecked(b"os.ll\0") },
         )?;
         bitcode_modules.add(&os_module)?;
+        let grid_module = Data::new(
+            self,
+            sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_BC,
+            GRID_MODULE,
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"grid.ll\0") },
+        )?;
+        bitcode_modules.add(&grid_module)?;
         let lib_options = unsafe {
             match compilation_mode {
                 CompilationMode::Wave32 => Either::Left([CStr::from_bytes_with_nul_unchecked(
@@ -237,6 +328,7 @@ impl Comgr {
             isa,
             lib_options.into_iter(),
             Some(sys::amd_comgr_language_t::AMD_COMGR_LANGUAGE_OPENCL_2_0),
+            log,
         )?;
         self.do_action(
             sys::amd_comgr_action_kind_t::AMD_COMGR_ACTION_LINK_BC_TO_BC,
@@ -250,6 +342,7 @@ impl Comgr {
                 .copied()
             },
             None,
+            log,
         )
     }
 
@@ -258,6 +351,7 @@ impl Comgr {
         compilation_mode: CompilationMode,
         isa: &'a CStr,
         bc_linking_output: &DataSet<'this>,
+        log: &mut Vec<String>,
     ) -> Result<DataSet<'this>> {
         let debug_level = if cfg!(debug_assertions) {
             unsafe {
@@ -314,6 +408,7 @@ impl Comgr {
             .copied()
             .chain(debug_level.iter().copied()),
             None,
+            log,
         )?;
         Ok(relocatable)
     }
@@ -322,6 +417,7 @@ impl Comgr {
         &'this self,
         isa: &'a CStr,
         modules: impl Iterator<Item = &'a Data<C>>,
+        log: &mut Vec<String>,
     ) -> Result<Vec<u8>> {
         let mut input = DataSet::new(self)?;
         for module in modules {
@@ -340,6 +436,7 @@ impl Comgr {
             .iter()
             .copied(),
             None,
+            log,
         )?;
         let executable_data = executable_set.get_data(
             sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_EXECUTABLE,
@@ -348,7 +445,12 @@ impl Comgr {
         executable_data.get_data()
     }
 
-    fn assemble_source(&self, isa: &CStr, src: &[u8]) -> Result<Data<&Self>> {
+    fn assemble_source(
+        &self,
+        isa: &CStr,
+        src: &[u8],
+        log: &mut Vec<String>,
+    ) -> Result<Data<&Self>> {
         let data = Data::new(
             self,
             sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_SOURCE,
@@ -365,6 +467,7 @@ impl Comgr {
                 CStr::from_bytes_with_nul_unchecked(CODE_OBJECT_VERSION_FLAG_CLANG)
             }),
             None,
+            log,
         )?;
         assembled.get_data(
             sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
@@ -379,6 +482,7 @@ impl Comgr {
         isa: &CStr,
         options: impl Iterator<Item = &'cstr CStr>,
         language: Option<sys::amd_comgr_language_t>,
+        log: &mut Vec<String>,
     ) -> Result<DataSet<'a>> {
         let output = DataSet::new(self)?;
         let action = ActionInfo::new(self, isa)?;
@@ -388,11 +492,36 @@ impl Comgr {
         if let Some(lang) = language {
             action.set_language(lang)?;
         }
-        action.execute(kind, &input, &output)?;
+        action.set_logging(true)?;
+        if let Err(err) = action.execute(kind, &input, &output) {
+            // Log is placed in the output set even if the action failed
+            if let Ok(text) = output.get_log() {
+                if !text.is_empty() {
+                    log.push(text);
+                }
+            }
+            return Err(err);
+        }
         Ok(output)
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct KernelResources {
+    pub name: String,
+    pub sgpr_count: u32,
+    pub vgpr_count: u32,
+    pub agpr_count: Option<u32>,
+    pub sgpr_spill_count: u32,
+    pub vgpr_spill_count: u32,
+    // Statically allocated LDS in bytes
+    pub group_segment_fixed_size: u32,
+    // Scratch memory per work-item in bytes
+    pub private_segment_fixed_size: u32,
+    pub wavefront_size: u32,
+    pub max_flat_workgroup_size: u32,
+}
+
 pub struct Bitcode<'a>(DataSet<'a>);
 
 impl<'a> Bitcode<'a> {
@@ -461,6 +590,14 @@ impl<'a> ActionInfo<'a> {
         Ok(())
     }
 
+    fn set_logging(&self, logging: bool) -> Result<()> {
+        call!(self
+            .1
+            .get()
+            .amd_comgr_action_info_set_logging(self.get(), logging));
+        Ok(())
+    }
+
     fn set_language(&self, lang: sys::amd_comgr_language_t) -> Result<()> {
         call!(self
             .1
@@ -540,6 +677,13 @@ impl<'a> DataSet<'a> {
         Ok(Data(output, comgr))
     }
 
+    fn get_log(&self) -> Result<String> {
+        let log = self
+            .get_data(sys::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_LOG, 0)?
+            .get_data()?;
+        Ok(String::from_utf8_lossy(&log).trim_end().to_string())
+    }
+
     fn get(&self) -> sys::amd_comgr_data_set_t {
         self.base
     }
@@ -598,6 +742,78 @@ impl<C: Borrow<Comgr>> Data<C> {
     }
 }
 
+struct Metadata<'a>(sys::amd_comgr_metadata_node_t, &'a Comgr);
+
+impl<'a> Metadata<'a> {
+    fn of_data<C: Borrow<Comgr>>(comgr: &'a Comgr, data: &Data<C>) -> Result<Self> {
+        let mut node = unsafe { mem::zeroed() };
+        call!(comgr
+            .get()
+            .amd_comgr_get_data_metadata(data.get(), &mut node));
+        Ok(Self(node, comgr))
+    }
+
+    // Keys are null-terminated
+    fn lookup(&self, key: &[u8]) -> Result<Metadata<'a>> {
+        let mut node = unsafe { mem::zeroed() };
+        call!(self
+            .1
+            .get()
+            .amd_comgr_metadata_lookup(self.0, key.as_ptr() as _, &mut node));
+        Ok(Self(node, self.1))
+    }
+
+    // Metadata keeps numbers as strings
+    fn lookup_u32(&self, key: &[u8]) -> Result<u32> {
+        self.lookup(key)?
+            .string()?
+            .parse::<u32>()
+            .map_err(|_| sys::amd_comgr_status_t::AMD_COMGR_STATUS_ERROR)
+    }
+
+    fn list_len(&self) -> Result<usize> {
+        let mut size = 0;
+        call!(self
+            .1
+            .get()
+            .amd_comgr_get_metadata_list_size(self.0, &mut size));
+        Ok(size)
+    }
+
+    fn index(&self, index: usize) -> Result<Metadata<'a>> {
+        let mut node = unsafe { mem::zeroed() };
+        call!(self
+            .1
+            .get()
+            .amd_comgr_index_list_metadata(self.0, index, &mut node));
+        Ok(Self(node, self.1))
+    }
+
+    fn string(&self) -> Result<String> {
+        let mut size = 0;
+        call!(self
+            .1
+            .get()
+            .amd_comgr_get_metadata_string(self.0, &mut size, ptr::null_mut()));
+        let mut output = vec![0u8; size];
+        call!(self.1.get().amd_comgr_get_metadata_string(
+            self.0,
+            &mut size,
+            output.as_mut_ptr() as _
+        ));
+        // Size includes terminating null
+        output.truncate(size.saturating_sub(1));
+        String::from_utf8(output).map_err(|_| sys::amd_comgr_status_t::AMD_COMGR_STATUS_ERROR)
+    }
+}
+
+#[allow(unused_must_use)]
+impl<'a> Drop for Metadata<'a> {
+    fn drop(&mut self) {
+        unsafe { self.1.get().amd_comgr_destroy_metadata(self.0) };
+    }
+}
+
 impl<C: Borrow<Comgr> + Clone> Clone for Data<C> {
     fn clone(&self) -> Self {
         Self(self.0.clone(), self.1.clone())
@@ -1,5 +1,452 @@
-// // include!("cache_db.rs");
-// include!("cache_file.rs");
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+mod cache_db;
 mod cache_file;
+pub mod compute;
+pub use cache_db::*;
 pub use cache_file::*;
+
+pub const DEFAULT_MAX_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+// After eviction the cache takes at most this fraction of the limit, so
+// that we don't scan the whole cache on every save
+const EVICTION_TARGET: f64 = 0.9;
+
+// Storage of compiled programs. A program is identified by the hash of its
+// source, compiler version, ZLUDA git hash, target device and parameters
+// specific to the user of the cache (ZLUDA or ZLUDA OptiX)
+pub trait KernelRepository: Send + Sync {
+    fn save_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        binary: &[u8],
+        additional_parameters: &[u8],
+    ) -> std::io::Result<()>;
+
+    fn try_load_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        additional_parameters: &[u8],
+    ) -> std::io::Result<Option<Vec<u8>>>;
+
+    // Read-only repositories (e.g. a system-wide cache shipped with an
+    // application) are never modified: no saves, statistics or use times
+    fn is_read_only(&self) -> bool;
+
+    fn stats(&self) -> CacheStats;
+
+    fn reset_stats(&self) -> std::io::Result<()>;
+
+    // Calls `f` with every valid program, used to migrate between backends
+    fn for_each_entry(&self, f: &mut dyn FnMut(&EntryMetadata, &[u8])) -> std::io::Result<()>;
+
+    // Every stored program, including ones that can't be loaded, used by
+    // zluda-cache to inspect and prune the cache
+    fn list_programs(&self) -> std::io::Result<Vec<StoredProgram>>;
+
+    // Full integrity check of a listed program
+    fn verify_program(&self, program: &StoredProgram) -> std::io::Result<bool>;
+
+    fn remove_program(&self, program: &StoredProgram) -> std::io::Result<()>;
+}
+
+pub struct StoredProgram {
+    pub location: ProgramLocation,
+    // Size counted against the cache size limit
+    pub size: u64,
+    pub last_used: SystemTime,
+    // Save that is in progress or was interrupted
+    pub temporary: bool,
+    // None if the program is corrupt
+    pub metadata: Option<EntryMetadata>,
+}
+
+pub enum ProgramLocation {
+    File(PathBuf),
+    Row(i64),
+}
+
+impl Display for ProgramLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramLocation::File(path) => write!(f, "{}", path.display()),
+            ProgramLocation::Row(id) => write!(f, "row {}", id),
+        }
+    }
+}
+
+// ZLUDA_CACHE_MAX_SIZE is a size in bytes with an optional K, M or G
+// suffix, 0 disables the limit
+pub fn max_size_from_env() -> Option<u64> {
+    match std::env::var("ZLUDA_CACHE_MAX_SIZE") {
+        Ok(value) => match parse_size(&value) {
+            Some(0) => None,
+            Some(size) => Some(size),
+            None => Some(DEFAULT_MAX_SIZE),
+        },
+        Err(_) => Some(DEFAULT_MAX_SIZE),
+    }
+}
+
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last()? {
+        (index, 'k') | (index, 'K') => (&value[..index], 1024),
+        (index, 'm') | (index, 'M') => (&value[..index], 1024 * 1024),
+        (index, 'g') | (index, 'G') => (&value[..index], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    // A file per program, see FileRepository
+    File,
+    // Single SQLite database, see SqliteRepository
+    Sqlite,
+}
+
+impl Backend {
+    // Backend of the writable cache, set with ZLUDA_CACHE_BACKEND
+    pub fn from_env() -> Self {
+        std::env::var("ZLUDA_CACHE_BACKEND")
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or(Backend::File)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match &*value.trim().to_ascii_lowercase() {
+            "file" => Some(Backend::File),
+            "sqlite" => Some(Backend::Sqlite),
+            _ => None,
+        }
+    }
+
+    // Backend of an existing cache directory
+    pub fn detect(cache_path: &Path) -> Self {
+        if SqliteRepository::database_path(cache_path).is_file() {
+            Backend::Sqlite
+        } else {
+            Backend::File
+        }
+    }
+
+    pub fn open(
+        self,
+        cache_path: PathBuf,
+        max_size: Option<u64>,
+    ) -> std::io::Result<Box<dyn KernelRepository>> {
+        Ok(match self {
+            Backend::File => Box::new(FileRepository::new(cache_path, max_size)),
+            Backend::Sqlite => Box::new(
+                SqliteRepository::new(Some(SqliteRepository::database_path(&cache_path)), max_size)
+                    .map_err(cache_db::to_io_error)?,
+            ),
+        })
+    }
+
+    pub fn open_read_only(self, cache_path: PathBuf) -> std::io::Result<Box<dyn KernelRepository>> {
+        Ok(match self {
+            Backend::File => Box::new(FileRepository::read_only(cache_path)),
+            Backend::Sqlite => Box::new(
+                SqliteRepository::read_only(SqliteRepository::database_path(&cache_path))
+                    .map_err(cache_db::to_io_error)?,
+            ),
+        })
+    }
+}
+
+// Copies every program of `from` into `to`, returns the number of copied programs
+pub fn migrate(from: &dyn KernelRepository, to: &dyn KernelRepository) -> std::io::Result<usize> {
+    let mut copied = 0;
+    let mut result = Ok(());
+    from.for_each_entry(&mut |metadata, binary| {
+        if result.is_err() {
+            return;
+        }
+        result = to.save_program(
+            &metadata.hash,
+            &metadata.compiler_version,
+            &metadata.git_hash,
+            &metadata.device,
+            binary,
+            &metadata.additional_parameters,
+        );
+        if result.is_ok() {
+            copied += 1;
+        }
+    })?;
+    result.map(|()| copied)
+}
+
+// Ordered list of repositories, e.g. read-only system-wide caches followed by
+// the writable per-user cache. Programs are loaded from the first repository
+// that has them and saved to every writable one
+pub struct CacheLayers {
+    layers: Vec<Box<dyn KernelRepository>>,
+}
+
+impl CacheLayers {
+    pub fn new(layers: Vec<Box<dyn KernelRepository>>) -> Option<Self> {
+        if layers.is_empty() {
+            None
+        } else {
+            Some(Self { layers })
+        }
+    }
+
+    // Existing `system_dirs` are opened read-only with whatever backend they
+    // use, `user_dir` is created if needed and opened with the backend set
+    // in ZLUDA_CACHE_BACKEND
+    pub fn open(system_dirs: Vec<PathBuf>, user_dir: Option<PathBuf>) -> Option<Self> {
+        let mut layers = system_dirs
+            .into_iter()
+            .filter(|dir| dir.is_dir())
+            .filter_map(|dir| Backend::detect(&dir).open_read_only(dir).ok())
+            .collect::<Vec<_>>();
+        if let Some(user_dir) = user_dir {
+            if std::fs::create_dir_all(&user_dir).is_ok() {
+                if let Ok(user_layer) = Backend::from_env().open(user_dir, max_size_from_env()) {
+                    layers.push(user_layer);
+                }
+            }
+        }
+        Self::new(layers)
+    }
+
+    pub fn save_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        binary: &[u8],
+        additional_parameters: &[u8],
+    ) -> std::io::Result<()> {
+        for layer in self.layers.iter().filter(|layer| !layer.is_read_only()) {
+            layer.save_program(
+                hash,
+                compiler_version,
+                git_hash,
+                device,
+                binary,
+                additional_parameters,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn try_load_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        additional_parameters: &[u8],
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let mut result = Ok(None);
+        for layer in self.layers.iter() {
+            match layer.try_load_program(
+                hash,
+                compiler_version,
+                git_hash,
+                device,
+                additional_parameters,
+            ) {
+                Ok(Some(binary)) => return Ok(Some(binary)),
+                Ok(None) => {}
+                // Broken layer should not hide the ones after it
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_program, migrate, parse_size, CacheLayers, FileRepository, KernelRepository,
+        SqliteRepository,
+    };
+    use std::ffi::CString;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("zluda_layers_test_{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    #[test]
+    fn parse_size_suffixes() {
+        assert_eq!(parse_size("1234"), Some(1234));
+        assert_eq!(parse_size("2K"), Some(2048));
+        assert_eq!(parse_size(" 3 m"), Some(3 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), None);
+    }
+
+    #[test]
+    fn sqlite_insert_select() {
+        let device = CString::new("gfx1030").unwrap();
+        let repo = SqliteRepository::new(None, None).unwrap();
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[3])
+            .unwrap();
+        repo.save_program("a", "clang", "git", &device, &[2; 100], &[3])
+            .unwrap();
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[3])
+                .unwrap(),
+            Some(vec![2; 100])
+        );
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[4])
+                .unwrap(),
+            None
+        );
+        let stats = repo.stats();
+        assert_eq!((stats.hits, stats.misses, stats.saves), (1, 1, 2));
+    }
+
+    #[test]
+    fn sqlite_evicts_other_versions_then_least_recently_used() {
+        let device = CString::new("gfx1030").unwrap();
+        let repo = SqliteRepository::new(None, Some(250)).unwrap();
+        repo.save_program("old", "clang", "old_git", &device, &[0; 100], &[])
+            .unwrap();
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        repo.save_program("b", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        assert_eq!(
+            repo.try_load_program("old", "clang", "old_git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert!(repo
+            .try_load_program("a", "clang", "git", &device, &[])
+            .unwrap()
+            .is_some());
+        assert_eq!(repo.stats().evicted, 1);
+    }
+
+    #[test]
+    fn migrate_between_backends() {
+        let dir = temp_dir("migrate");
+        let device = CString::new("gfx1030").unwrap();
+        let file = FileRepository::new(dir.clone(), None);
+        file.save_program("a", "clang", "git", &device, &[1; 100], &[3])
+            .unwrap();
+        file.save_program("b", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        let sqlite = SqliteRepository::new(None, None).unwrap();
+        assert_eq!(migrate(&file, &sqlite).unwrap(), 2);
+        assert_eq!(
+            sqlite
+                .try_load_program("a", "clang", "git", &device, &[3])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        assert_eq!(
+            sqlite
+                .try_load_program("b", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![2; 100])
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn list_verify_and_remove_programs_in_both_backends() {
+        let dir = temp_dir("list");
+        let device = CString::new("gfx1030").unwrap();
+        let repos: Vec<Box<dyn KernelRepository>> = vec![
+            Box::new(FileRepository::new(dir.clone(), None)),
+            Box::new(SqliteRepository::new(None, None).unwrap()),
+        ];
+        for repo in repos {
+            repo.save_program("a", "clang", "git", &device, &[1; 100], &[3])
+                .unwrap();
+            repo.save_program("b", "clang", "git", &device, &[2; 50], &[])
+                .unwrap();
+            let mut programs = repo.list_programs().unwrap();
+            programs.sort_unstable_by_key(|program| program.metadata.clone().unwrap().hash);
+            assert_eq!(programs.len(), 2);
+            assert!(programs.iter().all(|program| !program.temporary));
+            assert!(programs
+                .iter()
+                .all(|program| repo.verify_program(program).unwrap()));
+            assert_eq!(
+                programs[0].metadata.as_ref().unwrap().additional_parameters,
+                vec![3]
+            );
+            repo.remove_program(&programs[0]).unwrap();
+            assert_eq!(
+                repo.try_load_program("a", "clang", "git", &device, &[3])
+                    .unwrap(),
+                None
+            );
+            assert_eq!(repo.list_programs().unwrap().len(), 1);
+            let mut exported = Vec::new();
+            repo.for_each_entry(&mut |metadata, binary| {
+                exported.push(encode_program(metadata, binary))
+            })
+            .unwrap();
+            assert_eq!(exported.len(), 1);
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn layers_load_in_order_and_save_to_writable() {
+        let system_dir = temp_dir("system");
+        let user_dir = temp_dir("user");
+        let device = CString::new("gfx1030").unwrap();
+        let system = FileRepository::new(system_dir.clone(), None);
+        system
+            .save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        let user = FileRepository::new(user_dir.clone(), None);
+        user.save_program("a", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        let layers = CacheLayers::new(vec![
+            Box::new(FileRepository::read_only(system_dir.clone())),
+            Box::new(FileRepository::new(user_dir.clone(), None)),
+        ])
+        .unwrap();
+        assert_eq!(
+            layers
+                .try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        layers
+            .save_program("b", "clang", "git", &device, &[3; 100], &[])
+            .unwrap();
+        assert_eq!(
+            system
+                .try_load_program("b", "clang", "git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            user.try_load_program("b", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![3; 100])
+        );
+        std::fs::remove_dir_all(&system_dir).ok();
+        std::fs::remove_dir_all(&user_dir).ok();
+    }
+}
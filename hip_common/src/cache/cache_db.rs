@@ -1,240 +1,478 @@
-use itertools::Itertools;
-use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
-use rusqlite::Connection;
+use super::{CacheStats, EntryMetadata, KernelRepository, ProgramLocation, StoredProgram};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
 use std::ffi::{CStr, CString};
-use std::fmt::Display;
-use std::marker::PhantomData;
-use std::path::PathBuf;
-use std::time::{self, SystemTime};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{self, Duration, SystemTime};
 
-pub trait KernelExtendedData {
-    const INPUT_COLUMNS: &'static [[&'static str; 2]];
+// All programs are stored in a single SQLite database, <cache_path>/zluda.db.
+// Total size of the binaries is kept up to date by triggers, once it grows
+// over the limit programs of other versions (compiler version, git hash,
+// build type) are evicted first, then the least recently used ones
+pub struct SqliteRepository {
+    connection: Mutex<Connection>,
+    max_size: Option<u64>,
+    read_only: bool,
 }
 
-pub struct KernelRepository<T: KernelExtendedData> {
-    cache_file: Option<PathBuf>,
-    insert_kernel: String,
-    update_last_used: String,
-    select_kernel: String,
-    _marker: PhantomData<T>,
-}
+impl SqliteRepository {
+    pub const DATABASE_FILE: &'static str = "zluda.db";
+    // Databases with an older schema (e.g. written by older ZLUDA builds) are
+    // recreated, it's just a cache
+    const SCHEMA_VERSION: i32 = 1;
+    const CREATE_TABLES: &'static str = "
+        DROP TABLE IF EXISTS kernels;
+        DROP TABLE IF EXISTS globals;
+        CREATE TABLE kernels (
+            id INTEGER PRIMARY KEY NOT NULL,
+            hash TEXT NOT NULL,
+            compiler_version TEXT NOT NULL,
+            git_hash TEXT NOT NULL,
+            device TEXT NOT NULL,
+            is_debug INTEGER NOT NULL,
+            is_windows BOOLEAN NOT NULL,
+            additional_parameters BLOB NOT NULL,
+            binary BLOB NOT NULL,
+            last_used INTEGER NOT NULL
+        );
+        CREATE UNIQUE INDEX kernels_index ON kernels (hash, compiler_version, git_hash, device, is_windows, is_debug, additional_parameters);
+        CREATE TABLE globals (
+            key TEXT PRIMARY KEY,
+            value INTEGER NOT NULL
+        ) WITHOUT ROWID;
+        CREATE TRIGGER update_size_on_delete
+        AFTER
+            DELETE ON kernels FOR EACH ROW BEGIN
+        UPDATE
+            globals
+        SET
+            value = value - length(OLD.binary)
+        WHERE
+            key = 'total_binary_size';
+        END;
+        CREATE TRIGGER update_size_on_insert
+        AFTER
+            INSERT ON kernels FOR EACH ROW BEGIN
+        UPDATE
+            globals
+        SET
+            value = value + length(NEW.binary)
+        WHERE
+            key = 'total_binary_size';
+        END;
+        CREATE TRIGGER update_size_on_update
+        AFTER
+            UPDATE OF binary ON kernels FOR EACH ROW BEGIN
+        UPDATE
+            globals
+        SET
+            value = value - length(OLD.binary) + length(NEW.binary)
+        WHERE
+            key = 'total_binary_size';
+        END;
+        INSERT INTO globals (key, value) VALUES
+            ('total_binary_size', 0),
+            ('hits', 0),
+            ('misses', 0),
+            ('saves', 0),
+            ('evicted', 0),
+            ('corrupt', 0);";
+    const INSERT_KERNEL: &'static str = "
+        INSERT INTO
+            kernels (last_used, hash, compiler_version, git_hash, device, is_windows, is_debug, additional_parameters, binary)
+        VALUES
+            (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) ON CONFLICT DO
+        UPDATE
+        SET
+            last_used = ?1,
+            binary = excluded.binary;";
+    const SELECT_KERNEL: &'static str = "
+        SELECT
+            id, binary
+        FROM
+            kernels
+        WHERE
+            hash = ?1 AND compiler_version = ?2 AND git_hash = ?3 AND device = ?4 AND is_windows = ?5 AND is_debug = ?6 AND additional_parameters = ?7;";
 
-// We do all that string concatenation during run time, because there is no
-// good alternative. const_format crate implements const-time format!(),
-// but it does not work with generic types. Maybe it works with &'static str
-// const generics, but &'static str const generics are currently illegal
-impl<T: KernelExtendedData> KernelRepository<T> {
-    pub fn new(cache_file: Option<PathBuf>) -> rusqlite::Result<Self> {
-        let create_tables = {
-            let input_columns = Self::columns_comma(T::INPUT_COLUMNS);
-            let input_columns_with_type = Self::columns_comma_type(T::INPUT_COLUMNS);
-            format!("
-            CREATE TABLE IF NOT EXISTS kernels (
-                id INTEGER PRIMARY KEY NOT NULL,
-                hash TEXT NOT NULL,
-                compiler_version TEXT NOT NULL,
-                git_hash TEXT NOT NULL,
-                device TEXT NOT NULL,
-                is_debug INTEGER NOT NULL,
-                is_windows BOOLEAN NOT NULL,
-                binary BLOB NOT NULL,
-                last_used INTEGER NOT NULL
-                {input_columns_with_type}
-            );
-            CREATE UNIQUE INDEX IF NOT EXISTS kernels_index ON kernels (hash, compiler_version, git_hash, device, is_windows, is_debug {input_columns});
-            CREATE TABLE IF NOT EXISTS globals (
-                key TEXT PRIMARY KEY,
-                value INTEGER NOT NULL
-            ) WITHOUT ROWID;
-            CREATE TRIGGER IF NOT EXISTS update_size_on_delete 
-            AFTER 
-                DELETE ON kernels FOR EACH ROW BEGIN 
-            UPDATE 
-                globals 
-            SET 
-                value = value - length(OLD.binary) 
-            WHERE 
-                key = 'total_binary_size';
-            END;
-            CREATE TRIGGER IF NOT EXISTS update_size_on_insert
-            AFTER 
-                INSERT ON kernels FOR EACH ROW BEGIN 
-            UPDATE 
-                globals 
-            SET 
-                value = value + length(NEW.binary) 
-            WHERE 
-                key = 'total_binary_size';
-            END;
-            INSERT OR IGNORE INTO globals (key, value) VALUES ('total_binary_size', 0);")
-        };
-        let insert_kernel = {
-            let input_columns = Self::columns_comma(T::INPUT_COLUMNS);
-            let arg_markers = (0..T::INPUT_COLUMNS.len())
-                .into_iter()
-                .format_with("", |_, f| f(&", ?"));
-            format!("
-            INSERT INTO
-                kernels (last_used, hash, compiler_version, git_hash, device, is_windows, is_debug, binary {input_columns})
-            VALUES
-                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8 {arg_markers}) ON CONFLICT DO
-            UPDATE
-            SET
-                last_used = ?1;")
-        };
-        let update_last_used = {
-            let input_columns = Self::columns_and(T::INPUT_COLUMNS);
-            format!("
-            UPDATE
-                kernels
-            SET
-                last_used = ?1
-            WHERE
-                hash = ?2 AND compiler_version = ?3 AND git_hash = ?4 AND device = ?5 AND is_windows = ?6 AND is_debug = ?7 {input_columns};")
-        };
-        let select_kernel = {
-            let input_columns = Self::columns_and(T::INPUT_COLUMNS);
-            format!("
-            SELECT
-                id, binary
-            FROM 
-                kernels
-            WHERE
-                hash = ?1 AND compiler_version = ?2 AND git_hash = ?3 AND device = ?4 AND is_windows = ?5 AND is_debug = ?6 {input_columns};")
-        };
-        let result = Self {
-            cache_file,
-            insert_kernel,
-            update_last_used,
-            select_kernel,
-            _marker: PhantomData,
+    // In-memory database if `database` is None
+    pub fn new(database: Option<PathBuf>, max_size: Option<u64>) -> rusqlite::Result<Self> {
+        let mut connection = match database {
+            Some(ref database) => Connection::open(database)?,
+            None => Connection::open_in_memory()?,
         };
-        let mut connection = result.connect()?;
+        connection.busy_timeout(Duration::from_secs(10))?;
         connection.pragma_update(None, "journal_mode", "WAL")?;
         connection.pragma_update(None, "synchronous", "normal")?;
         // Deferred transaction here can lead to SQLITE_BUSY errors
         let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
         {
-            tx.set_db_config(
-                rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY,
-                true,
-            )?;
             tx.set_db_config(
                 rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER,
                 true,
             )?;
-            tx.execute_batch(&create_tables)?;
+            let schema_version: i32 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            if schema_version != Self::SCHEMA_VERSION {
+                tx.execute_batch(Self::CREATE_TABLES)?;
+                tx.pragma_update(None, "user_version", Self::SCHEMA_VERSION)?;
+            }
         }
         tx.commit()?;
-        Ok(result)
+        Ok(Self {
+            connection: Mutex::new(connection),
+            max_size,
+            read_only: false,
+        })
     }
 
-    pub fn connect(&self) -> rusqlite::Result<Connection> {
-        match self.cache_file {
-            Some(ref file) => rusqlite::Connection::open(file),
-            None => rusqlite::Connection::open_in_memory(),
+    pub fn read_only(database: PathBuf) -> rusqlite::Result<Self> {
+        let connection = Connection::open_with_flags(
+            database,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        connection.busy_timeout(Duration::from_secs(10))?;
+        let schema_version: i32 =
+            connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if schema_version != Self::SCHEMA_VERSION {
+            return Err(rusqlite::Error::InvalidQuery);
         }
+        Ok(Self {
+            connection: Mutex::new(connection),
+            max_size: None,
+            read_only: true,
+        })
     }
 
-    pub fn save_program(
-        &self,
-        now: i64,
-        hash: &str,
+    pub fn database_path(cache_path: &Path) -> PathBuf {
+        let mut path = cache_path.to_path_buf();
+        path.push(Self::DATABASE_FILE);
+        path
+    }
+
+    pub fn now() -> Result<i64, time::SystemTimeError> {
+        let now = SystemTime::now().duration_since(time::UNIX_EPOCH)?;
+        Ok(now.as_millis() as i64)
+    }
+
+    fn lock(&self) -> std::io::Result<std::sync::MutexGuard<'_, Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "poisoned lock"))
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.read_only {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "read-only cache",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn program_row(program: &StoredProgram) -> std::io::Result<i64> {
+        match program.location {
+            ProgramLocation::Row(id) => Ok(id),
+            ProgramLocation::File(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "not a database cache entry",
+            )),
+        }
+    }
+
+    fn record(tx: &Transaction, key: &str, count: u64) -> rusqlite::Result<()> {
+        tx.execute(
+            "UPDATE globals SET value = value + ?1 WHERE key = ?2",
+            params![count as i64, key],
+        )?;
+        Ok(())
+    }
+
+    // Once the database grows over `max_size`, removes programs until it fits
+    // in a fraction of it, so that we don't evict on every save
+    fn evict(
+        tx: &Transaction,
         compiler_version: &str,
         git_hash: &str,
-        device: &CStr,
-        binary: &[u8],
-        input_values: &[&dyn rusqlite::ToSql],
+        max_size: u64,
     ) -> rusqlite::Result<()> {
-        let connection = self.connect()?;
-        let tx = connection.unchecked_transaction()?;
+        let total_size: i64 = tx.query_row(
+            "SELECT value FROM globals WHERE key = 'total_binary_size'",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut size = total_size.max(0) as u64;
+        if size <= max_size {
+            return Ok(());
+        }
+        let target = (max_size as f64 * super::EVICTION_TARGET) as u64;
+        let mut evicted = Vec::new();
         {
-            let mut insert_kernel = tx.prepare(&self.insert_kernel)?;
-            let common_values = rusqlite::params![
-                now,
-                hash,
+            let mut select = tx.prepare(
+                "
+                SELECT
+                    id, length(binary)
+                FROM
+                    kernels
+                ORDER BY
+                    (compiler_version = ?1 AND git_hash = ?2 AND is_windows = ?3 AND is_debug = ?4), last_used;",
+            )?;
+            let mut rows = select.query(params![
                 compiler_version,
                 git_hash,
-                SqlCStrRef(device),
                 cfg!(windows),
-                cfg!(debug_assertions),
-                binary
-            ];
-            insert_kernel.execute(rusqlite::params_from_iter(
-                IntoIterator::into_iter([common_values, input_values]).flatten(),
-            ))?;
+                cfg!(debug_assertions)
+            ])?;
+            while size > target {
+                let row = match rows.next()? {
+                    Some(row) => row,
+                    None => break,
+                };
+                evicted.push(row.get::<_, i64>(0)?);
+                size = size.saturating_sub(row.get::<_, i64>(1)? as u64);
+            }
+        }
+        let mut delete = tx.prepare("DELETE FROM kernels WHERE id = ?1")?;
+        for id in evicted.iter() {
+            delete.execute([id])?;
         }
-        tx.commit()
+        Self::record(tx, "evicted", evicted.len() as u64)
     }
+}
 
-    pub fn try_load_program(
+impl KernelRepository for SqliteRepository {
+    fn save_program(
         &self,
-        now: i64,
         hash: &str,
         compiler_version: &str,
         git_hash: &str,
         device: &CStr,
-        input_values: &[&dyn rusqlite::ToSql],
-    ) -> rusqlite::Result<Option<Vec<u8>>> {
-        let connection = self.connect()?;
-        let tx = connection.unchecked_transaction()?;
-        let result;
-        {
-            let common_params = rusqlite::params![
-                now,
-                hash,
-                compiler_version,
-                git_hash,
-                SqlCStrRef(device),
-                cfg!(windows),
-                cfg!(debug_assertions)
-            ];
-            let mut statement = tx.prepare(&self.update_last_used)?;
-            let rows_affected = statement.execute(rusqlite::params_from_iter(
-                IntoIterator::into_iter([common_params, input_values]).flatten(),
-            ))?;
-            if rows_affected == 0 {
-                return Ok(None);
+        binary: &[u8],
+        additional_parameters: &[u8],
+    ) -> std::io::Result<()> {
+        self.check_writable()?;
+        let now = Self::now().map_err(to_io_error)?;
+        let mut connection = self.lock()?;
+        (|| {
+            let tx =
+                connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+            tx.execute(
+                Self::INSERT_KERNEL,
+                params![
+                    now,
+                    hash,
+                    compiler_version,
+                    git_hash,
+                    SqlCStrRef(device),
+                    cfg!(windows),
+                    cfg!(debug_assertions),
+                    additional_parameters,
+                    binary
+                ],
+            )?;
+            Self::record(&tx, "saves", 1)?;
+            if let Some(max_size) = self.max_size {
+                Self::evict(&tx, compiler_version, git_hash, max_size)?;
             }
-            let mut select_kernel = tx.prepare(&self.select_kernel)?;
-            let mut kernel_rows = select_kernel.query(rusqlite::params_from_iter(
-                IntoIterator::into_iter([&common_params[1..], input_values]).flatten(),
-            ))?;
-            if let Some(row) = kernel_rows.next()? {
-                let binary = row.get::<_, Vec<u8>>(1)?;
-                result = Some(binary);
-            } else {
-                result = None;
+            tx.commit()
+        })()
+        .map_err(to_io_error)
+    }
+
+    fn try_load_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        additional_parameters: &[u8],
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let now = Self::now().map_err(to_io_error)?;
+        let mut connection = self.lock()?;
+        (|| -> rusqlite::Result<_> {
+            let tx = connection.transaction()?;
+            let kernel = tx
+                .query_row(
+                    Self::SELECT_KERNEL,
+                    params![
+                        hash,
+                        compiler_version,
+                        git_hash,
+                        SqlCStrRef(device),
+                        cfg!(windows),
+                        cfg!(debug_assertions),
+                        additional_parameters
+                    ],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+                )
+                .optional()?;
+            if self.read_only {
+                return Ok(kernel.map(|(_, binary)| binary));
+            }
+            let result = match kernel {
+                Some((id, binary)) => {
+                    tx.execute(
+                        "UPDATE kernels SET last_used = ?1 WHERE id = ?2",
+                        params![now, id],
+                    )?;
+                    Self::record(&tx, "hits", 1)?;
+                    Some(binary)
+                }
+                None => {
+                    Self::record(&tx, "misses", 1)?;
+                    None
+                }
+            };
+            tx.commit()?;
+            Ok(result)
+        })()
+        .map_err(to_io_error)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn stats(&self) -> CacheStats {
+        let mut result = CacheStats::default();
+        let connection = match self.lock() {
+            Ok(connection) => connection,
+            Err(_) => return result,
+        };
+        let mut statement = match connection.prepare("SELECT key, value FROM globals") {
+            Ok(statement) => statement,
+            Err(_) => return result,
+        };
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        });
+        for (key, value) in rows.into_iter().flatten().filter_map(Result::ok) {
+            let value = value.max(0) as u64;
+            match &*key {
+                "hits" => result.hits = value,
+                "misses" => result.misses = value,
+                "saves" => result.saves = value,
+                "evicted" => result.evicted = value,
+                "corrupt" => result.corrupt = value,
+                _ => {}
             }
         }
-        tx.commit()?;
-        Ok(result)
+        result
     }
 
-    fn columns_comma(columns: &'static [[&'static str; 2]]) -> impl Display {
-        columns
-            .iter()
-            .format_with("", |[name, _], f| f(&format_args!(", {name}")))
+    fn reset_stats(&self) -> std::io::Result<()> {
+        self.check_writable()?;
+        self.lock()?
+            .execute(
+                "UPDATE globals SET value = 0 WHERE key != 'total_binary_size'",
+                [],
+            )
+            .map_err(to_io_error)?;
+        Ok(())
     }
 
-    fn columns_comma_type(columns: &'static [[&'static str; 2]]) -> impl Display {
-        columns
-            .iter()
-            .format_with("", |[name, type_], f| f(&format_args!(", {name} {type_}")))
+    // Only programs of the current build type and OS
+    fn for_each_entry(&self, f: &mut dyn FnMut(&EntryMetadata, &[u8])) -> std::io::Result<()> {
+        let connection = self.lock()?;
+        (|| -> rusqlite::Result<()> {
+            let mut statement = connection.prepare(
+                "
+                SELECT
+                    hash, compiler_version, git_hash, device, additional_parameters, binary
+                FROM
+                    kernels
+                WHERE
+                    is_windows = ?1 AND is_debug = ?2;",
+            )?;
+            let mut rows = statement.query(params![cfg!(windows), cfg!(debug_assertions)])?;
+            while let Some(row) = rows.next()? {
+                let device = match CString::new(row.get::<_, String>(3)?) {
+                    Ok(device) => device,
+                    Err(_) => continue,
+                };
+                let metadata = EntryMetadata {
+                    hash: row.get(0)?,
+                    compiler_version: row.get(1)?,
+                    git_hash: row.get(2)?,
+                    device,
+                    additional_parameters: row.get(4)?,
+                };
+                let binary = row.get::<_, Vec<u8>>(5)?;
+                f(&metadata, &binary);
+            }
+            Ok(())
+        })()
+        .map_err(to_io_error)
     }
 
-    fn columns_and(columns: &'static [[&'static str; 2]]) -> impl Display {
-        columns
-            .iter()
-            .format_with("", |[name, _], f| f(&format_args!(" AND {name} = ?")))
+    // Programs of every build type and OS
+    fn list_programs(&self) -> std::io::Result<Vec<StoredProgram>> {
+        let connection = self.lock()?;
+        (|| -> rusqlite::Result<_> {
+            let mut statement = connection.prepare(
+                "
+                SELECT
+                    id, hash, compiler_version, git_hash, device, additional_parameters, length(binary), last_used
+                FROM
+                    kernels;",
+            )?;
+            let mut rows = statement.query([])?;
+            let mut result = Vec::new();
+            while let Some(row) = rows.next()? {
+                let metadata = CString::new(row.get::<_, String>(4)?)
+                    .ok()
+                    .map(|device| -> rusqlite::Result<_> {
+                        Ok(EntryMetadata {
+                            hash: row.get(1)?,
+                            compiler_version: row.get(2)?,
+                            git_hash: row.get(3)?,
+                            device,
+                            additional_parameters: row.get(5)?,
+                        })
+                    })
+                    .transpose()?;
+                let last_used = Duration::from_millis(row.get::<_, i64>(7)?.max(0) as u64);
+                result.push(StoredProgram {
+                    location: ProgramLocation::Row(row.get(0)?),
+                    size: row.get::<_, i64>(6)?.max(0) as u64,
+                    last_used: time::UNIX_EPOCH + last_used,
+                    temporary: false,
+                    metadata,
+                });
+            }
+            Ok(result)
+        })()
+        .map_err(to_io_error)
     }
 
-    pub fn now() -> Result<i64, time::SystemTimeError> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH)?;
-        Ok(now.as_millis() as i64)
+    // SQLite checks consistency of the database itself, a row is valid as
+    // long as it can still be read
+    fn verify_program(&self, program: &StoredProgram) -> std::io::Result<bool> {
+        let id = Self::program_row(program)?;
+        let binary = self
+            .lock()?
+            .query_row("SELECT binary FROM kernels WHERE id = ?1", [id], |row| {
+                row.get::<_, Vec<u8>>(0)
+            })
+            .optional()
+            .map_err(to_io_error)?;
+        Ok(program.metadata.is_some() && binary.is_some())
+    }
+
+    fn remove_program(&self, program: &StoredProgram) -> std::io::Result<()> {
+        self.check_writable()?;
+        let id = Self::program_row(program)?;
+        self.lock()?
+            .execute("DELETE FROM kernels WHERE id = ?1", [id])
+            .map_err(to_io_error)?;
+        Ok(())
     }
 }
 
+pub(super) fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
 pub struct SqlCStrRef<'a>(pub &'a CStr);
 
 impl<'a> rusqlite::ToSql for SqlCStrRef<'a> {
@@ -244,14 +482,3 @@ impl<'a> rusqlite::ToSql for SqlCStrRef<'a> {
         ))
     }
 }
-
-pub struct SqlCString(pub CString);
-
-impl FromSql for SqlCString {
-    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        let str = value.as_str()?;
-        Ok(SqlCString(
-            CString::new(str.to_string()).map_err(|err| FromSqlError::Other(Box::new(err)))?,
-        ))
-    }
-}
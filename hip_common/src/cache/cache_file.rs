@@ -1,36 +1,207 @@
+use super::{KernelRepository, ProgramLocation, StoredProgram};
 use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
 use std::ffi::{CStr, CString};
 use std::fmt::Display;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::{self, SystemTime};
-use sha2::{Digest, Sha256};
 
-pub struct KernelRepository {
+// Programs are stored as <cache_path>/<version>/<hash prefix>/<hash>, where
+// <version> is derived from ZLUDA git hash and compiler version. Everything
+// outside of the current <version> directory (including the flat layout used
+// by older ZLUDA builds) is evicted first once the cache grows over its limit.
+// Last modification time of a file is its last use time.
+// Read-only repositories (e.g. a system-wide cache shipped with an application)
+// are never modified: no saves, statistics, use times or removals
+pub struct FileRepository {
     cache_path: PathBuf,
+    max_size: Option<u64>,
+    read_only: bool,
+    // Size of all the files in the cache, computed on the first save
+    total_size: Mutex<Option<u64>>,
+    // Statistics not yet added to the statistics file and the number of
+    // events they hold
+    pending_stats: Mutex<(CacheStats, u32)>,
 }
 
-// We do all that string concatenation during run time, because there is no
-// good alternative. const_format crate implements const-time format!(),
-// but it does not work with generic types. Maybe it works with &'static str
-// const generics, but &'static str const generics are currently illegal
-impl KernelRepository {
-    pub fn new(cache_path: PathBuf) -> Self {
+impl FileRepository {
+    const VERSION_DIR_PREFIX: &'static str = "v1-";
+    const STATS_FILE: &'static str = "stats.txt";
+    const STATS_FLUSH_INTERVAL: u32 = 64;
+
+    pub fn new(cache_path: PathBuf, max_size: Option<u64>) -> Self {
+        Self {
+            cache_path,
+            max_size,
+            read_only: false,
+            total_size: Mutex::new(None),
+            pending_stats: Mutex::new((CacheStats::default(), 0)),
+        }
+    }
+
+    pub fn read_only(cache_path: PathBuf) -> Self {
         Self {
             cache_path,
+            max_size: None,
+            read_only: true,
+            total_size: Mutex::new(None),
+            pending_stats: Mutex::new((CacheStats::default(), 0)),
         }
     }
 
-    pub fn save_program(
+    fn write_entry(
+        &self,
+        path: &Path,
+        compiler_version: &str,
+        git_hash: &str,
+        entry: &[u8],
+    ) -> std::io::Result<()> {
+        self.check_writable()?;
+        let previous_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        write_atomic(&path, entry, true)?;
+        self.track_size(
+            compiler_version,
+            git_hash,
+            entry.len() as u64,
+            previous_size,
+        );
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    // All the entries in the cache, including incomplete and corrupt ones.
+    // Cache directory might be shared with other files, so only files laid
+    // out as entries (<version>/<hash prefix>/<hash> or the flat
+    // <hash prefix>/<hash>) are ever listed and thus evicted
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let mut result = Vec::new();
+        for dir_entry in Self::read_dir(&self.cache_path) {
+            let name = dir_entry.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if is_hex(name, 2) {
+                Self::collect_entries(&dir_entry.path(), name, &mut result);
+            } else if name
+                .strip_prefix(Self::VERSION_DIR_PREFIX)
+                .map_or(false, |version| is_hex(version, 16))
+            {
+                for prefix_entry in Self::read_dir(&dir_entry.path()) {
+                    let prefix = prefix_entry.file_name();
+                    match prefix.to_str() {
+                        Some(prefix) if is_hex(prefix, 2) => {
+                            Self::collect_entries(&prefix_entry.path(), prefix, &mut result)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn remove_entry(&self, entry: &CacheEntry) -> std::io::Result<()> {
+        self.check_writable()?;
+        std::fs::remove_file(&entry.path)?;
+        self.track_removal(entry.size);
+        if let Some(parent) = entry.path.parent() {
+            Self::remove_empty_dirs(&self.cache_path, parent);
+        }
+        Ok(())
+    }
+
+    // Stores an entry read from another cache (e.g. exported with
+    // zluda-cache). Returns false if the entry is not valid
+    pub fn import_entry(&self, entry: &[u8]) -> std::io::Result<bool> {
+        let metadata = match decode_entry(entry) {
+            Some((metadata, _)) => metadata,
+            None => return Ok(false),
+        };
+        let path = self.program_path(
+            &metadata.hash,
+            &metadata.compiler_version,
+            &metadata.git_hash,
+            &metadata.device,
+            &metadata.additional_parameters,
+        );
+        self.write_entry(&path, &metadata.compiler_version, &metadata.git_hash, entry)?;
+        Ok(true)
+    }
+
+    // Statistics are shared by all the processes using the cache. They are
+    // counted in memory and added to the statistics file every
+    // STATS_FLUSH_INTERVAL events and on drop. Concurrent updates might get
+    // lost, which is fine for what they are used for
+    fn record(&self, update: impl FnOnce(&mut CacheStats)) {
+        if self.read_only {
+            return;
+        }
+        let mut pending_stats = match self.pending_stats.lock() {
+            Ok(pending_stats) => pending_stats,
+            Err(_) => return,
+        };
+        let (ref mut stats, ref mut events) = *pending_stats;
+        update(stats);
+        *events += 1;
+        if *events >= Self::STATS_FLUSH_INTERVAL {
+            self.flush_stats(stats);
+            *events = 0;
+        }
+    }
+
+    // Best-effort, statistics are not worth an fsync
+    fn flush_stats(&self, pending: &mut CacheStats) {
+        if *pending == CacheStats::default() {
+            return;
+        }
+        let mut stats = self.file_stats();
+        stats.add(pending);
+        *pending = CacheStats::default();
+        write_atomic(&self.stats_path(), stats.to_string().as_bytes(), false).ok();
+    }
+
+    fn file_stats(&self) -> CacheStats {
+        std::fs::read_to_string(self.stats_path())
+            .map(|text| CacheStats::parse(&text))
+            .unwrap_or_default()
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.read_only {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is a read-only cache", self.cache_path.display()),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn stats_path(&self) -> PathBuf {
+        let mut path = self.cache_path.clone();
+        path.push(Self::STATS_FILE);
+        path
+    }
+
+    fn program_path(
         &self,
         hash: &str,
         compiler_version: &str,
         git_hash: &str,
         device: &CStr,
-        binary: &[u8],
         additional_parameters: &[u8],
-    ) -> std::io::Result<()> {
-        let mut path = self.cache_path.clone();
+    ) -> PathBuf {
+        let mut path = self.version_dir(compiler_version, git_hash);
         let mut hasher = Sha256::new();
         hasher.update(hash);
         hasher.update(b":");
@@ -43,17 +214,173 @@ impl KernelRepository {
         hasher.update(additional_parameters);
         let hash = hex::encode(hasher.finalize());
         let hash_prefix = &hash[..2];
-        
+
         path.push(hash_prefix);
         path.push(hash);
+        path
+    }
 
-        std::fs::create_dir_all(path.parent().unwrap())?;
-        std::fs::write(&path, binary)?;
+    fn version_dir(&self, compiler_version: &str, git_hash: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(compiler_version);
+        hasher.update(b":");
+        hasher.update(git_hash);
+        let version = hex::encode(&hasher.finalize()[..8]);
+        let mut path = self.cache_path.clone();
+        path.push(format!("{}{}", Self::VERSION_DIR_PREFIX, version));
+        path
+    }
+
+    fn track_size(&self, compiler_version: &str, git_hash: &str, added: u64, removed: u64) {
+        let max_size = match self.max_size {
+            Some(max_size) => max_size,
+            None => return,
+        };
+        let mut total_size = match self.total_size.lock() {
+            Ok(total_size) => total_size,
+            Err(_) => return,
+        };
+        let size = match *total_size {
+            Some(size) => (size + added).saturating_sub(removed),
+            None => self.entries().iter().map(|entry| entry.size).sum(),
+        };
+        *total_size = Some(if size > max_size {
+            let target = (max_size as f64 * super::EVICTION_TARGET) as u64;
+            self.evict((compiler_version, git_hash), target)
+        } else {
+            size
+        });
+    }
+
+    fn track_removal(&self, removed: u64) {
+        if let Ok(mut total_size) = self.total_size.lock() {
+            if let Some(ref mut size) = *total_size {
+                *size = size.saturating_sub(removed);
+            }
+        }
+    }
+
+    // Removes entries of versions other than the current one (compiler
+    // version and git hash), then least recently used entries of the current
+    // version until the cache fits in `target`. Returns the size of the cache
+    // after eviction
+    fn evict(&self, current_version: (&str, &str), target: u64) -> u64 {
+        let current_version = self.version_dir(current_version.0, current_version.1);
+        let mut entries = self.entries();
+        entries.sort_unstable_by_key(|entry| {
+            (entry.path.starts_with(&current_version), entry.last_used)
+        });
+        let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+        let mut evicted = 0;
+        for entry in entries {
+            if size <= target {
+                break;
+            }
+            if std::fs::remove_file(&entry.path).is_ok() {
+                size -= entry.size;
+                evicted += 1;
+                if let Some(parent) = entry.path.parent() {
+                    Self::remove_empty_dirs(&self.cache_path, parent);
+                }
+            }
+        }
+        if evicted > 0 {
+            self.record(|stats| stats.evicted += evicted);
+        }
+        size
+    }
+
+    fn read_dir(dir: &Path) -> impl Iterator<Item = std::fs::DirEntry> {
+        std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+    }
+
+    // Entries in a <hash prefix> directory, temporary files included
+    fn collect_entries(dir: &Path, prefix: &str, result: &mut Vec<CacheEntry>) {
+        for dir_entry in Self::read_dir(dir) {
+            let name = dir_entry.file_name();
+            let is_entry = match name.to_str().map(|name| (name.get(..64), name.get(64..))) {
+                Some((Some(hash), Some(rest))) => {
+                    is_hex(hash, 64)
+                        && hash.starts_with(prefix)
+                        && (rest.is_empty() || rest.starts_with(TEMP_SUFFIX))
+                }
+                _ => false,
+            };
+            if !is_entry {
+                continue;
+            }
+            let metadata = match dir_entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_file() {
+                result.push(CacheEntry {
+                    path: dir_entry.path(),
+                    size: metadata.len(),
+                    last_used: metadata.modified().unwrap_or(time::UNIX_EPOCH),
+                });
+            }
+        }
+    }
+
+    fn program_file(program: &StoredProgram) -> std::io::Result<&Path> {
+        match program.location {
+            ProgramLocation::File(ref path) => Ok(path),
+            ProgramLocation::Row(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "not a file cache entry",
+            )),
+        }
+    }
 
+    // Fails silently on the first non-empty directory
+    fn remove_empty_dirs(root: &Path, mut dir: &Path) {
+        while dir.starts_with(root) && dir != root {
+            if std::fs::remove_dir(dir).is_err() {
+                return;
+            }
+            dir = match dir.parent() {
+                Some(parent) => parent,
+                None => return,
+            };
+        }
+    }
+}
+
+impl KernelRepository for FileRepository {
+    fn save_program(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &CStr,
+        binary: &[u8],
+        additional_parameters: &[u8],
+    ) -> std::io::Result<()> {
+        let path = self.program_path(
+            hash,
+            compiler_version,
+            git_hash,
+            device,
+            additional_parameters,
+        );
+        let metadata = EntryMetadata::encode(
+            hash,
+            compiler_version,
+            git_hash,
+            device.to_bytes(),
+            additional_parameters,
+        );
+        let entry = encode_entry(&metadata, binary);
+        self.write_entry(&path, compiler_version, git_hash, &entry)?;
+        self.record(|stats| stats.saves += 1);
         Ok(())
     }
 
-    pub fn try_load_program(
+    fn try_load_program(
         &self,
         hash: &str,
         compiler_version: &str,
@@ -61,28 +388,591 @@ impl KernelRepository {
         device: &CStr,
         additional_parameters: &[u8],
     ) -> std::io::Result<Option<Vec<u8>>> {
-        let mut path = self.cache_path.clone();
-        let mut hasher = Sha256::new();
-        hasher.update(hash);
-        hasher.update(b":");
-        hasher.update(compiler_version);
-        hasher.update(b":");
-        hasher.update(git_hash);
-        hasher.update(b":");
-        hasher.update(device.to_bytes());
-        hasher.update(b":");
-        hasher.update(additional_parameters);
-        let hash = hex::encode(hasher.finalize());
-        let hash_prefix = &hash[..2];
+        let path = self.program_path(
+            hash,
+            compiler_version,
+            git_hash,
+            device,
+            additional_parameters,
+        );
+        let mut entry = match std::fs::read(&path) {
+            Ok(entry) => entry,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.record(|stats| stats.misses += 1);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        let payload_offset = match decode_entry(&entry) {
+            Some((_, payload)) => entry.len() - payload.len(),
+            None if self.read_only => {
+                return Ok(None);
+            }
+            None => {
+                // Left by a crashed process or a different ZLUDA build, either
+                // way it's never going to be valid
+                if std::fs::remove_file(&path).is_ok() {
+                    self.track_removal(entry.len() as u64);
+                }
+                self.record(|stats| {
+                    stats.misses += 1;
+                    stats.corrupt += 1;
+                });
+                return Ok(None);
+            }
+        };
+        // Failing to record the use only makes the entry look older
+        if !self.read_only {
+            if let Ok(file) = std::fs::OpenOptions::new().write(true).open(&path) {
+                file.set_modified(SystemTime::now()).ok();
+            }
+        }
+        self.record(|stats| stats.hits += 1);
+        entry.drain(..payload_offset);
+        Ok(Some(entry))
+    }
 
-        path.push(hash_prefix);
-        path.push(hash);
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn stats(&self) -> CacheStats {
+        let mut stats = self.file_stats();
+        if let Ok(pending_stats) = self.pending_stats.lock() {
+            stats.add(&pending_stats.0);
+        }
+        stats
+    }
+
+    fn reset_stats(&self) -> std::io::Result<()> {
+        self.check_writable()?;
+        if let Ok(mut pending_stats) = self.pending_stats.lock() {
+            *pending_stats = (CacheStats::default(), 0);
+        }
+        match std::fs::remove_file(self.stats_path()) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn for_each_entry(&self, f: &mut dyn FnMut(&EntryMetadata, &[u8])) -> std::io::Result<()> {
+        for entry in self.entries() {
+            if entry.is_temporary() {
+                continue;
+            }
+            let content = match std::fs::read(&entry.path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            if let Some((metadata, payload)) = decode_entry(&content) {
+                f(&metadata, payload);
+            }
+        }
+        Ok(())
+    }
+
+    fn list_programs(&self) -> std::io::Result<Vec<StoredProgram>> {
+        let mut result = Vec::new();
+        for entry in self.entries() {
+            let temporary = entry.is_temporary();
+            let metadata = if temporary {
+                None
+            } else {
+                match EntryMetadata::read(&entry.path) {
+                    Ok(metadata) => metadata,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                }
+            };
+            result.push(StoredProgram {
+                location: ProgramLocation::File(entry.path),
+                size: entry.size,
+                last_used: entry.last_used,
+                temporary,
+                metadata,
+            });
+        }
+        Ok(result)
+    }
+
+    fn verify_program(&self, program: &StoredProgram) -> std::io::Result<bool> {
+        let path = Self::program_file(program)?;
+        Ok(!program.temporary && decode_entry(&std::fs::read(path)?).is_some())
+    }
+
+    fn remove_program(&self, program: &StoredProgram) -> std::io::Result<()> {
+        let path = Self::program_file(program)?;
+        self.remove_entry(&CacheEntry {
+            path: path.to_path_buf(),
+            size: program.size,
+            last_used: program.last_used,
+        })
+    }
+}
+
+impl Drop for FileRepository {
+    fn drop(&mut self) {
+        let mut stats = match self.pending_stats.get_mut() {
+            Ok(pending_stats) => pending_stats.0,
+            Err(_) => return,
+        };
+        self.flush_stats(&mut stats);
+    }
+}
+
+// Lowercase, as written by hex::encode(...)
+fn is_hex(text: &str, length: usize) -> bool {
+    text.len() == length && text.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+// Entries and statistics are written to a temporary file and renamed into
+// place, so other processes never observe a partially written file. Without
+// `sync_data` a crash might still leave an empty or truncated file behind
+fn write_atomic(path: &Path, content: &[u8], sync_data: bool) -> std::io::Result<()> {
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(
+        "{}{}-{}",
+        TEMP_SUFFIX,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = PathBuf::from(temp_path);
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(content)?;
+        if sync_data {
+            file.sync_data()?;
+        }
+        drop(file);
+        std::fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        std::fs::remove_file(&temp_path).ok();
+    }
+    result
+}
+
+const TEMP_SUFFIX: &'static str = ".tmp-";
+const ENTRY_MAGIC: [u8; 4] = *b"ZLCO";
+const ENTRY_FORMAT_VERSION: u32 = 2;
+// magic, format version, payload length, SHA-256 of metadata and payload,
+// metadata length
+const ENTRY_HEADER_SIZE: usize = 4 + 4 + 8 + 32 + 4;
+
+fn encode_entry(metadata: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(metadata);
+    hasher.update(payload);
+    let mut result = Vec::with_capacity(ENTRY_HEADER_SIZE + metadata.len() + payload.len());
+    result.extend_from_slice(&ENTRY_MAGIC);
+    result.extend_from_slice(&ENTRY_FORMAT_VERSION.to_le_bytes());
+    result.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    result.extend_from_slice(&hasher.finalize());
+    result.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    result.extend_from_slice(metadata);
+    result.extend_from_slice(payload);
+    result
+}
+
+// Entry of a program as written by FileRepository, e.g. for an archive
+pub fn encode_program(metadata: &EntryMetadata, binary: &[u8]) -> Vec<u8> {
+    let metadata = EntryMetadata::encode(
+        &metadata.hash,
+        &metadata.compiler_version,
+        &metadata.git_hash,
+        metadata.device.to_bytes(),
+        &metadata.additional_parameters,
+    );
+    encode_entry(&metadata, binary)
+}
+
+// Returns metadata and payload of a valid entry
+pub fn decode_entry(entry: &[u8]) -> Option<(EntryMetadata, &[u8])> {
+    let metadata_length = decode_header(entry)?;
+    let header = &entry[..ENTRY_HEADER_SIZE];
+    let rest = &entry[ENTRY_HEADER_SIZE..];
+    let payload_length = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    if Some(rest.len() as u64) != (metadata_length as u64).checked_add(payload_length) {
+        return None;
+    }
+    if Sha256::digest(rest)[..] != header[16..48] {
+        return None;
+    }
+    let (metadata, payload) = rest.split_at(metadata_length);
+    Some((EntryMetadata::decode(metadata)?, payload))
+}
+
+// Returns length of the metadata
+fn decode_header(entry: &[u8]) -> Option<usize> {
+    let header = entry.get(..ENTRY_HEADER_SIZE)?;
+    if header[0..4] != ENTRY_MAGIC
+        || u32::from_le_bytes(header[4..8].try_into().unwrap()) != ENTRY_FORMAT_VERSION
+    {
+        return None;
+    }
+    Some(u32::from_le_bytes(header[48..52].try_into().unwrap()) as usize)
+}
 
-        match std::fs::read(&path) {
-            Ok(binary) => Ok(Some(binary)),
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
-            Err(err) => Err(err),
+// Everything that identifies a program, stored in the entry so that the
+// cache can be inspected and entries moved between machines
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub hash: String,
+    pub compiler_version: String,
+    pub git_hash: String,
+    pub device: CString,
+    pub additional_parameters: Vec<u8>,
+}
+
+impl EntryMetadata {
+    // Reads only the metadata, without verifying the payload
+    pub fn read(path: &Path) -> std::io::Result<Option<Self>> {
+        let mut file = std::fs::File::open(path)?;
+        let mut header = [0u8; ENTRY_HEADER_SIZE];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+        let metadata_length = match decode_header(&header) {
+            Some(metadata_length) => metadata_length,
+            None => return Ok(None),
+        };
+        let mut metadata = vec![0u8; metadata_length];
+        if file.read_exact(&mut metadata).is_err() {
+            return Ok(None);
+        }
+        Ok(Self::decode(&metadata))
+    }
+
+    fn encode(
+        hash: &str,
+        compiler_version: &str,
+        git_hash: &str,
+        device: &[u8],
+        additional_parameters: &[u8],
+    ) -> Vec<u8> {
+        let mut result = Vec::new();
+        for field in [
+            hash.as_bytes(),
+            compiler_version.as_bytes(),
+            git_hash.as_bytes(),
+            device,
+            additional_parameters,
+        ] {
+            result.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            result.extend_from_slice(field);
         }
+        result
+    }
+
+    fn decode(mut metadata: &[u8]) -> Option<Self> {
+        let mut next_field = || {
+            let current: &[u8] = metadata;
+            let length = u32::from_le_bytes(current.get(..4)?.try_into().unwrap()) as usize;
+            let field = current.get(4..4 + length)?;
+            metadata = &current[4 + length..];
+            Some(field)
+        };
+        let hash = String::from_utf8(next_field()?.to_vec()).ok()?;
+        let compiler_version = String::from_utf8(next_field()?.to_vec()).ok()?;
+        let git_hash = String::from_utf8(next_field()?.to_vec()).ok()?;
+        let device = CString::new(next_field()?).ok()?;
+        let additional_parameters = next_field()?.to_vec();
+        Some(Self {
+            hash,
+            compiler_version,
+            git_hash,
+            device,
+            additional_parameters,
+        })
     }
+}
+
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+impl CacheEntry {
+    // Temporary file of a save that is in progress or was interrupted
+    pub fn is_temporary(&self) -> bool {
+        self.path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.contains(TEMP_SUFFIX))
+    }
+}
 
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub saves: u64,
+    pub evicted: u64,
+    pub corrupt: u64,
+}
+
+impl CacheStats {
+    fn add(&mut self, other: &CacheStats) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.saves += other.saves;
+        self.evicted += other.evicted;
+        self.corrupt += other.corrupt;
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut result = Self::default();
+        for line in text.lines() {
+            let (key, value) = match line.split_once(' ') {
+                Some((key, value)) => (key, value.trim().parse().unwrap_or(0)),
+                None => continue,
+            };
+            match key {
+                "hits" => result.hits = value,
+                "misses" => result.misses = value,
+                "saves" => result.saves = value,
+                "evicted" => result.evicted = value,
+                "corrupt" => result.corrupt = value,
+                _ => {}
+            }
+        }
+        result
+    }
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "hits {}", self.hits)?;
+        writeln!(f, "misses {}", self.misses)?;
+        writeln!(f, "saves {}", self.saves)?;
+        writeln!(f, "evicted {}", self.evicted)?;
+        writeln!(f, "corrupt {}", self.corrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntryMetadata, FileRepository, KernelRepository};
+    use std::ffi::CString;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("zluda_cache_test_{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    #[test]
+    fn evicts_other_versions_then_least_recently_used() {
+        let dir = temp_dir("evict");
+        let device = CString::new("gfx1030").unwrap();
+        let repo = FileRepository::new(dir.clone(), Some(400));
+        let old_repo = FileRepository::new(dir.clone(), None);
+        old_repo
+            .save_program("old", "clang", "old_git", &device, &[0; 100], &[])
+            .unwrap();
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        let a_path = repo.program_path("a", "clang", "git", &device, &[]);
+        let a_file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&a_path)
+            .unwrap();
+        a_file
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+        // Over the limit: entry of the old version goes first, then "a"
+        repo.save_program("b", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        assert_eq!(
+            old_repo
+                .try_load_program("old", "clang", "old_git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            repo.try_load_program("b", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![2; 100])
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn corrupt_entry_is_a_miss_and_gets_deleted() {
+        let dir = temp_dir("corrupt");
+        let device = CString::new("gfx1030").unwrap();
+        let repo = FileRepository::new(dir.clone(), None);
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        let path = repo.program_path("a", "clang", "git", &device, &[]);
+        let entry = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &entry[..entry.len() - 1]).unwrap();
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert!(!path.exists());
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn read_only_repository_is_never_modified() {
+        let dir = temp_dir("read_only");
+        let device = CString::new("gfx1030").unwrap();
+        let repo = FileRepository::new(dir.clone(), None);
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        repo.save_program("b", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        let a_path = repo.program_path("a", "clang", "git", &device, &[]);
+        let last_used = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&a_path)
+            .unwrap()
+            .set_modified(last_used)
+            .unwrap();
+        let b_path = repo.program_path("b", "clang", "git", &device, &[]);
+        let entry = std::fs::read(&b_path).unwrap();
+        std::fs::write(&b_path, &entry[..entry.len() - 1]).unwrap();
+        let stats = repo.stats();
+        drop(repo);
+        let read_only = FileRepository::read_only(dir.clone());
+        assert_eq!(
+            read_only
+                .try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        assert_eq!(
+            read_only
+                .try_load_program("b", "clang", "git", &device, &[])
+                .unwrap(),
+            None
+        );
+        assert!(read_only
+            .save_program("c", "clang", "git", &device, &[3; 100], &[])
+            .is_err());
+        assert!(b_path.exists());
+        assert_eq!(
+            std::fs::metadata(&a_path).unwrap().modified().unwrap(),
+            last_used
+        );
+        assert_eq!(read_only.stats(), stats);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn unrelated_files_are_never_evicted() {
+        let dir = temp_dir("unrelated");
+        let device = CString::new("gfx1030").unwrap();
+        let unrelated = [
+            dir.join("notes.txt"),
+            dir.join("ab").join("notes.txt"),
+            dir.join("documents").join("a".repeat(64)),
+            dir.join("v1-0123456789abcdef")
+                .join("ab")
+                .join("c".repeat(64)),
+        ];
+        for path in unrelated.iter() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &[0; 1000]).unwrap();
+        }
+        let legacy = dir.join("ab").join(format!("ab{}", "0".repeat(62)));
+        std::fs::write(&legacy, &[0; 100]).unwrap();
+        let repo = FileRepository::new(dir.clone(), Some(300));
+        assert_eq!(repo.entries().len(), 1);
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        repo.save_program("b", "clang", "git", &device, &[2; 100], &[])
+            .unwrap();
+        assert!(!legacy.exists());
+        for path in unrelated.iter() {
+            assert!(path.exists());
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn overflowing_payload_length_is_corrupt() {
+        let mut entry = super::encode_entry(b"metadata", &[1; 100]);
+        entry[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(super::decode_entry(&entry).is_none());
+    }
+
+    #[test]
+    fn stats_are_flushed_on_drop() {
+        let dir = temp_dir("stats");
+        let device = CString::new("gfx1030").unwrap();
+        let repo = FileRepository::new(dir.clone(), None);
+        repo.save_program("a", "clang", "git", &device, &[1; 100], &[])
+            .unwrap();
+        assert_eq!(
+            repo.try_load_program("a", "clang", "git", &device, &[])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        assert_eq!(repo.stats().hits, 1);
+        assert!(!repo.stats_path().exists());
+        drop(repo);
+        let stats = FileRepository::new(dir.clone(), None).stats();
+        assert_eq!((stats.saves, stats.hits), (1, 1));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let source_dir = temp_dir("export");
+        let target_dir = temp_dir("import");
+        let device = CString::new("gfx1030").unwrap();
+        let source = FileRepository::new(source_dir.clone(), None);
+        source
+            .save_program("a", "clang", "git", &device, &[1; 100], &[3])
+            .unwrap();
+        let entries = source.entries();
+        assert_eq!(entries.len(), 1);
+        let metadata = EntryMetadata::read(&entries[0].path).unwrap().unwrap();
+        assert_eq!(
+            metadata,
+            EntryMetadata {
+                hash: "a".to_string(),
+                compiler_version: "clang".to_string(),
+                git_hash: "git".to_string(),
+                device: device.clone(),
+                additional_parameters: vec![3],
+            }
+        );
+        let entry = std::fs::read(&entries[0].path).unwrap();
+        let target = FileRepository::new(target_dir.clone(), None);
+        assert!(target.import_entry(&entry).unwrap());
+        assert!(!target.import_entry(&entry[1..]).unwrap());
+        assert_eq!(
+            target
+                .try_load_program("a", "clang", "git", &device, &[3])
+                .unwrap(),
+            Some(vec![1; 100])
+        );
+        assert_eq!(target.stats().hits, 1);
+        std::fs::remove_dir_all(&source_dir).ok();
+        std::fs::remove_dir_all(&target_dir).ok();
+    }
 }
@@ -0,0 +1,59 @@
+// Keys of ZLUDA compute cache. Shared by the runtime and the offline compiler,
+// which precompiles modules into the same cache
+use crate::CompilationMode;
+use std::path::PathBuf;
+
+// Marks an entry that records which PTX of a multi-arch fatbin compiled
+pub const PTX_SELECTION_RECORD: u8 = b's';
+
+pub fn default_dir() -> Option<PathBuf> {
+    if let Ok(cache_dir) = std::env::var("ZLUDA_CO_CACHE_DIR") {
+        Some(PathBuf::from(cache_dir))
+    } else if let Ok(cache_dir) = std::env::var("ZLUDA_CACHE_DIR") {
+        let mut cache_dir = PathBuf::from(cache_dir);
+        cache_dir.push("ComputeCache");
+        Some(cache_dir)
+    } else {
+        let mut cache_dir = dirs::cache_dir()?;
+        cache_dir.push("ZLUDA");
+        cache_dir.push("ComputeCache");
+        Some(cache_dir)
+    }
+}
+
+// Read-only cache layers consulted before the per-user cache, e.g. shipped
+// with an application or baked into a container image. ZLUDA_CO_CACHE_SYSTEM_DIRS
+// is a list of directories separated like PATH, earlier directories take precedence
+pub fn system_dirs() -> Vec<PathBuf> {
+    match std::env::var_os("ZLUDA_CO_CACHE_SYSTEM_DIRS") {
+        Some(dirs) => std::env::split_paths(&dirs)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn module_hash<'a>(ptx_modules: impl IntoIterator<Item = &'a str>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for module in ptx_modules {
+        hasher.update(module.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+pub fn module_parameters(compilation_mode: CompilationMode) -> [u8; 1] {
+    [compilation_mode as u8]
+}
+
+pub fn ptx_selection_hash<'a>(candidates: impl IntoIterator<Item = (&'a str, u32)>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for (module, sm_version) in candidates {
+        hasher.update(&sm_version.to_le_bytes());
+        hasher.update(module.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+pub fn ptx_selection_parameters(compilation_mode: CompilationMode) -> [u8; 2] {
+    [compilation_mode as u8, PTX_SELECTION_RECORD]
+}
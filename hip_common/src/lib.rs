@@ -66,7 +66,7 @@ macro_rules! unwrap_or_continue {
 }
 
 #[repr(u8)]
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum CompilationMode {
     Wave32 = 1,
     Wave32OnWave64 = 2,
@@ -84,6 +84,18 @@ impl CompilationMode {
         }
     }
 
+    // Mode used on GPUs with 64 lanes wide wavefronts. Shared by the runtime
+    // and the offline compiler, because the mode is a part of the cache key
+    pub fn wave64() -> Self {
+        match std::env::var("ZLUDA_WAVE64_SLOW_MODE")
+            .ok()
+            .and_then(|value| str::parse::<u32>(&value).ok())
+        {
+            Some(value) if value != 0 => CompilationMode::Wave32OnWave64,
+            _ => CompilationMode::DoubleWave32OnWave64,
+        }
+    }
+
     pub fn from_u8(value: u8) -> Option<Self> {
         Some(match value {
             1 => CompilationMode::Wave32,
@@ -0,0 +1,347 @@
+// Compilation of PTX files for one or more GPUs. Every input gets a JSON
+// report next to its outputs, so failures in large batches can be inspected
+// after the fact
+use comgr::{Comgr, KernelResources};
+use hip_common::CompilationMode;
+use ptx::{ModuleParserExt, PassInstrumentation};
+use serde::Serialize;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::{fs, iter};
+
+#[derive(Serialize)]
+pub(crate) struct Report {
+    pub(crate) inputs: Vec<String>,
+    pub(crate) mode: String,
+    pub(crate) success: bool,
+    // Errors common to all targets: reading, parsing and translation to LLVM
+    pub(crate) errors: Vec<String>,
+    pub(crate) targets: Vec<TargetReport>,
+    // Only with --time-passes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) passes: Vec<PassReport>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PassReport {
+    pub(crate) pass: &'static str,
+    pub(crate) milliseconds: f64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TargetReport {
+    pub(crate) isa: String,
+    pub(crate) success: bool,
+    // Failure followed by compiler log, if any
+    pub(crate) errors: Vec<String>,
+    pub(crate) kernels: Vec<KernelReport>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct KernelReport {
+    name: String,
+    sgpr_count: u32,
+    vgpr_count: u32,
+    agpr_count: Option<u32>,
+    sgpr_spill_count: u32,
+    vgpr_spill_count: u32,
+    lds_size: u32,
+    scratch_size: u32,
+    wavefront_size: u32,
+    max_flat_workgroup_size: u32,
+}
+
+impl From<KernelResources> for KernelReport {
+    fn from(kernel: KernelResources) -> Self {
+        Self {
+            name: kernel.name,
+            sgpr_count: kernel.sgpr_count,
+            vgpr_count: kernel.vgpr_count,
+            agpr_count: kernel.agpr_count,
+            sgpr_spill_count: kernel.sgpr_spill_count,
+            vgpr_spill_count: kernel.vgpr_spill_count,
+            lds_size: kernel.group_segment_fixed_size,
+            scratch_size: kernel.private_segment_fixed_size,
+            wavefront_size: kernel.wavefront_size,
+            max_flat_workgroup_size: kernel.max_flat_workgroup_size,
+        }
+    }
+}
+
+// Single unit of compilation: one PTX file or, with --link, all of them.
+// Outputs are named after the first input and written to `output_dir`
+pub(crate) struct Job {
+    pub(crate) inputs: Vec<PathBuf>,
+    pub(crate) output_dir: PathBuf,
+}
+
+// Expands directories into the PTX files they contain (recursively). Every
+// file keeps its position relative to the directory under `output`
+pub(crate) fn collect_jobs(inputs: &[PathBuf], output: Option<&Path>) -> Result<Vec<Job>, String> {
+    let mut jobs = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut files = Vec::new();
+            find_ptx_files(input, &mut files)?;
+            files.sort();
+            for file in files {
+                let output_dir = match output {
+                    Some(output) => {
+                        let relative = file
+                            .parent()
+                            .and_then(|parent| parent.strip_prefix(input).ok())
+                            .unwrap_or_else(|| Path::new(""));
+                        output.join(relative)
+                    }
+                    None => parent_dir(&file),
+                };
+                jobs.push(Job {
+                    inputs: vec![file],
+                    output_dir,
+                });
+            }
+        } else {
+            let output_dir = match output {
+                Some(output) => output.to_path_buf(),
+                None => parent_dir(input),
+            };
+            jobs.push(Job {
+                inputs: vec![input.clone()],
+                output_dir,
+            });
+        }
+    }
+    Ok(jobs)
+}
+
+fn find_ptx_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("{}: {}", dir.display(), err))?
+            .path();
+        if path.is_dir() {
+            find_ptx_files(&path, files)?;
+        } else if path.extension().map_or(false, |ext| ext == "ptx") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// Writes `<name>.ll`, `<name>.metadata.s` (ZLUDA module metadata) and for
+// every ISA `<name>.<isa>.elf` with its disassembly in `<name>.<isa>.s`.
+// Module dumps requested by `instrumentation` go to `<name>.<pass>.txt`
+pub(crate) fn compile(
+    comgr: &Comgr,
+    job: &Job,
+    mode: CompilationMode,
+    isas: &[String],
+    mut instrumentation: PassInstrumentation,
+) -> Report {
+    let mut report = Report {
+        inputs: job
+            .inputs
+            .iter()
+            .map(|input| input.to_string_lossy().into_owned())
+            .collect(),
+        mode: format!("{:?}", mode),
+        success: false,
+        errors: Vec::new(),
+        targets: Vec::new(),
+        passes: Vec::new(),
+    };
+    let mut output_path = job.output_dir.clone();
+    output_path.push(job.inputs[0].file_stem().unwrap_or_default());
+    if let Err(err) = compile_impl(
+        comgr,
+        job,
+        mode,
+        isas,
+        &output_path,
+        &mut instrumentation,
+        &mut report,
+    ) {
+        report.errors.push(err);
+    }
+    report.success = report.errors.is_empty() && report.targets.iter().all(|target| target.success);
+    let report_path = with_suffix(&output_path, "json");
+    let json = serde_json::to_string_pretty(&report).unwrap();
+    if let Err(err) = fs::write(&report_path, json) {
+        report
+            .errors
+            .push(format!("{}: {}", report_path.display(), err));
+        report.success = false;
+    }
+    report
+}
+
+fn compile_impl(
+    comgr: &Comgr,
+    job: &Job,
+    mode: CompilationMode,
+    isas: &[String],
+    output_path: &Path,
+    instrumentation: &mut PassInstrumentation,
+    report: &mut Report,
+) -> Result<(), String> {
+    fs::create_dir_all(&job.output_dir)
+        .map_err(|err| format!("{}: {}", job.output_dir.display(), err))?;
+    let texts = job
+        .inputs
+        .iter()
+        .map(|input| {
+            fs::read_to_string(input).map_err(|err| format!("{}: {}", input.display(), err))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut asts = Vec::new();
+    for (input, text) in job.inputs.iter().zip(texts.iter()) {
+        match ptx::ModuleParser::parse_checked(text) {
+            Ok(ast) => asts.push(ast),
+            Err(errors) => {
+                for error in errors.iter() {
+                    let error = unsafe { ptx::DisplayParseError::new(error, text) };
+                    report
+                        .errors
+                        .push(format!("{}: {}", input.display(), error));
+                }
+            }
+        }
+    }
+    if !report.errors.is_empty() {
+        return Ok(());
+    }
+    let module = ptx::to_llvm_module_instrumented(mode, asts, instrumentation);
+    report.passes = instrumentation
+        .timings
+        .iter()
+        .map(|timing| PassReport {
+            pass: timing.pass,
+            milliseconds: timing.duration.as_secs_f64() * 1000.0,
+        })
+        .collect();
+    // Dumps are most useful when translation fails, write them first
+    for dump in instrumentation.dumps.iter() {
+        write(
+            &with_suffix(output_path, &format!("{}.txt", dump.pass)),
+            dump.text.as_bytes(),
+        )?;
+    }
+    let module = module.map_err(|err| format!("PTX translation failed: {}", err))?;
+    write(
+        &with_suffix(output_path, "ll"),
+        module.get_llvm_text().to_string().as_bytes(),
+    )?;
+    let metadata = module.metadata.to_elf_section();
+    write(&with_suffix(output_path, "metadata.s"), &metadata)?;
+    for isa in isas {
+        let mut target = TargetReport {
+            isa: isa.clone(),
+            success: false,
+            errors: Vec::new(),
+            kernels: Vec::new(),
+        };
+        if let Err(err) = compile_target(comgr, &module, mode, &metadata, output_path, &mut target)
+        {
+            target.errors.insert(0, err);
+        }
+        target.success = target.errors.is_empty();
+        report.targets.push(target);
+    }
+    Ok(())
+}
+
+fn compile_target(
+    comgr: &Comgr,
+    module: &ptx::Module,
+    mode: CompilationMode,
+    metadata: &[u8],
+    output_path: &Path,
+    target: &mut TargetReport,
+) -> Result<(), String> {
+    let full_isa = CString::new(format!("amdgcn-amd-amdhsa--{}", target.isa))
+        .map_err(|_| format!("invalid ISA: {:?}", target.isa))?;
+    let binary = comgr
+        .compile_with_log(
+            mode,
+            &full_isa,
+            ptx::Module::get_bitcode_multi(iter::once(module)).into_iter(),
+            metadata,
+            &mut target.errors,
+        )
+        .map_err(|err| format!("AMD GPU code compilation failed: {:?}", err))?;
+    // Target features (e.g. gfx90a:sramecc+:xnack-) contain characters
+    // that are not allowed in Windows file names
+    let file_isa = target.isa.replace(':', "_");
+    write(
+        &with_suffix(output_path, &format!("{}.elf", file_isa)),
+        &binary,
+    )?;
+    let assembly = comgr
+        .disassemble(&full_isa, &binary)
+        .map_err(|err| format!("Disassembly failed: {:?}", err))?;
+    write(
+        &with_suffix(output_path, &format!("{}.s", file_isa)),
+        assembly.as_bytes(),
+    )?;
+    target.kernels = comgr
+        .kernel_resources(&binary)
+        .map_err(|err| format!("Reading kernel metadata failed: {:?}", err))?
+        .into_iter()
+        .map(KernelReport::from)
+        .collect();
+    Ok(())
+}
+
+// Unlike Path::with_extension(...) keeps dots already present in the name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn write(path: &Path, content: &[u8]) -> Result<(), String> {
+    fs::write(path, content).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collect_jobs;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn directories_keep_relative_layout() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("zoc_collect_jobs_{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.ptx"), "").unwrap();
+        fs::write(dir.join("nested").join("b.ptx"), "").unwrap();
+        fs::write(dir.join("nested").join("c.txt"), "").unwrap();
+        let jobs = collect_jobs(&[dir.clone()], Some(Path::new("out"))).unwrap();
+        let jobs = jobs
+            .iter()
+            .map(|job| (job.inputs.clone(), job.output_dir.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            jobs,
+            vec![
+                (vec![dir.join("a.ptx")], PathBuf::from("out")),
+                (
+                    vec![dir.join("nested").join("b.ptx")],
+                    Path::new("out").join("nested")
+                ),
+            ]
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,34 +1,63 @@
 use argh::FromArgs;
 use comgr::Comgr;
+use hip_common::cache::{self, compute, Backend};
 use hip_common::CompilationMode;
 use hip_runtime_sys::*;
 use hiprt_sys::*;
-use ptx::ModuleParserExt;
-use std::borrow::Cow;
+use ptx::{ModuleParserExt, PassInstrumentation};
 use std::ffi::{c_void, CStr};
 use std::path::Path;
+use std::process;
 use std::rc::Rc;
 use std::{ffi::CString, fs, path::PathBuf};
 use std::{iter, ptr};
 
+mod batch;
+mod precompile;
+
+// Some inputs failed to compile
+const EXIT_FAILURE: i32 = 1;
+// Invalid arguments or missing dependencies, nothing was compiled
+const EXIT_USAGE: i32 = 2;
+
 #[derive(FromArgs)]
 /// ZLUDA offline compiler
 struct CompilerArguments {
-    /// LLVM AMDGPU ISA, defaults to "gfx1030"
-    #[argh(option, default = "default_isa()")]
-    isa: String,
-    /// paths to PTX files
+    /// LLVM AMDGPU ISA, defaults to "gfx1030". Can be repeated to compile for several GPUs,
+    /// except with --rt-program
+    #[argh(option)]
+    isa: Vec<String>,
+    /// paths to PTX files or directories with them, or to executables and shared libraries with
+    /// --precompile
     #[argh(positional)]
     inputs: Vec<PathBuf>,
-    /// directory with output, will be created if does not exist
+    /// directory with output, will be created if does not exist. Defaults to the directory of
+    /// every input
     #[argh(option, short = 'o')]
     output: Option<PathBuf>,
+    /// compile all PTX inputs together into a single module named after the first input
+    #[argh(switch)]
+    link: bool,
     /// name of an OptiX program, if provided PTX will be compiled in raytracing mode
     #[argh(option)]
     rt_program: Option<String>,
-    /// compilation mode: 1 - Wave32, 2 - Wave32OnWave64, 3 - DoubleWave32OnWave64, defaults to Wave32
+    /// compilation mode: 1 - Wave32, 2 - Wave32OnWave64, 3 - DoubleWave32OnWave64, defaults to Wave32.
+    /// Can be repeated with --precompile, where it defaults to the mode of the ISA
     #[argh(option, short = 'm')]
-    mode: Option<u8>,
+    mode: Vec<u8>,
+    /// compile every kernel of the given executables and shared libraries into ZLUDA cache
+    #[argh(switch)]
+    precompile: bool,
+    /// cache directory used with --precompile, defaults to the one used by ZLUDA
+    #[argh(option)]
+    cache_dir: Option<PathBuf>,
+    /// print wall time of every PTX translation pass and add it to the JSON report
+    #[argh(switch)]
+    time_passes: bool,
+    /// write the module after the given PTX translation pass to `<name>.<pass>.txt`. Can be
+    /// repeated, `all` dumps after every pass
+    #[argh(option)]
+    dump_after: Vec<String>,
     /// print LLVM version
     #[argh(switch, short = 'V')]
     version: bool,
@@ -40,106 +69,212 @@ fn default_isa() -> String {
 
 fn main() {
     let args: CompilerArguments = argh::from_env();
-    let comgr = comgr::Comgr::find_and_load().unwrap();
+    let comgr = match comgr::Comgr::find_and_load() {
+        Ok(comgr) => comgr,
+        Err(_) => exit_usage("Could not load AMD comgr library"),
+    };
     if args.version {
-        println!("{}", comgr.version().unwrap());
+        match comgr.version() {
+            Ok(version) => println!("{}", version),
+            Err(err) => exit_usage(&format!("Could not read comgr version: {:?}", err)),
+        }
         return;
     }
-    let mut full_isa = "amdgcn-amd-amdhsa--".to_string();
-    full_isa.push_str(&args.isa);
-    let main_path = &args.inputs[0];
-    let inputs = args
-        .inputs
-        .iter()
-        .map(|input| fs::read_to_string(&input).unwrap())
-        .collect::<Vec<_>>();
-    let asts = inputs
+    if args.inputs.is_empty() {
+        exit_usage("No inputs");
+    }
+    let modes = args
+        .mode
         .iter()
-        .map(|input| ptx::ModuleParser::parse_checked(input).unwrap())
+        .map(|mode| {
+            CompilationMode::from_u8(*mode)
+                .unwrap_or_else(|| exit_usage(&format!("Invalid compilation mode: {}", mode)))
+        })
         .collect::<Vec<_>>();
-    let output_dir = if let Some(output) = args.output {
-        fs::create_dir_all(&output).unwrap();
-        Cow::Owned(output)
+    let instrumented = args.time_passes || !args.dump_after.is_empty();
+    if instrumented && (args.precompile || args.rt_program.is_some()) {
+        exit_usage(
+            "--time-passes and --dump-after can't be used with --precompile or --rt-program",
+        );
+    }
+    if let Err(err) = PassInstrumentation::new(args.time_passes, args.dump_after.clone()) {
+        exit_usage(&err);
+    }
+    if args.precompile {
+        return precompile(args, modes, &comgr);
+    }
+    if modes.len() > 1 {
+        exit_usage("-m can be repeated only with --precompile");
+    }
+    if args.rt_program.is_some() && args.isa.len() > 1 {
+        exit_usage("--isa can't be repeated with --rt-program");
+    }
+    if let Some(ref rt_program) = args.rt_program {
+        let isa = args.isa.first().cloned().unwrap_or_else(default_isa);
+        if let Err(err) = compile_raytracing(&args, &isa, rt_program, Rc::new(comgr)) {
+            eprintln!("{}", err);
+            process::exit(EXIT_FAILURE);
+        }
+        return;
+    }
+    compile(
+        args,
+        modes.first().copied().unwrap_or(CompilationMode::Wave32),
+        &comgr,
+    )
+}
+
+fn exit_usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(EXIT_USAGE)
+}
+
+fn compile(args: CompilerArguments, mode: CompilationMode, comgr: &Comgr) {
+    let isas = if args.isa.is_empty() {
+        vec![default_isa()]
     } else {
-        Cow::Borrowed(args.inputs[0].parent().unwrap())
+        args.isa
     };
-    match args.rt_program {
-        Some(rt_program) => {
-            let comgr = Rc::new(comgr);
-            if asts.len() != 1 {
-                panic!("Raytracing compiler expects single .ptx input file")
+    let mut jobs = batch::collect_jobs(&args.inputs, args.output.as_deref())
+        .unwrap_or_else(|err| exit_usage(&err));
+    if args.link && !jobs.is_empty() {
+        let output_dir = jobs[0].output_dir.clone();
+        let inputs = jobs.into_iter().flat_map(|job| job.inputs).collect();
+        jobs = vec![batch::Job { inputs, output_dir }];
+    }
+    if jobs.is_empty() {
+        exit_usage("No PTX files found");
+    }
+    let mut failed = 0;
+    for job in jobs.iter() {
+        // Pass names were validated in main(...)
+        let instrumentation =
+            PassInstrumentation::new(args.time_passes, args.dump_after.clone()).unwrap();
+        let report = batch::compile(comgr, job, mode, &isas, instrumentation);
+        let name = report.inputs.join(", ");
+        for error in report.errors.iter() {
+            eprintln!("{}", error);
+        }
+        if !report.passes.is_empty() {
+            println!("{}: PTX translation passes", name);
+            for pass in report.passes.iter() {
+                println!("  {:<40} {:>10.3} ms", pass.pass, pass.milliseconds);
+            }
+        }
+        for target in report.targets.iter() {
+            if target.success {
+                println!(
+                    "{}: compiled for {}, {} kernels",
+                    name,
+                    target.isa,
+                    target.kernels.len()
+                );
+            } else {
+                eprintln!("{}: compilation for {} failed", name, target.isa);
+                for error in target.errors.iter() {
+                    eprintln!("  {}", error);
+                }
             }
-            compile_and_dump_raytracing(
-                full_isa,
-                output_dir,
-                &comgr,
-                rt_program,
-                main_path,
-                asts.into_iter().next().unwrap(),
-            )
         }
-        None => {
-            let mode = args
-                .mode
-                .map(CompilationMode::from_u8)
-                .flatten()
-                .unwrap_or(CompilationMode::Wave32);
-            compile_and_dump(full_isa, output_dir, mode, comgr, main_path, asts)
+        if !report.success {
+            failed += 1;
         }
     }
+    println!("{} inputs compiled, {} failed", jobs.len() - failed, failed);
+    if failed > 0 {
+        process::exit(EXIT_FAILURE);
+    }
 }
 
-fn compile_and_dump<'input>(
-    full_isa: String,
-    output_dir: Cow<Path>,
-    compilation_mode: CompilationMode,
-    comgr: Comgr,
-    main_path: &PathBuf,
-    asts: Vec<ptx::ast::Module<'input>>,
-) {
-    let compiled_module = ptx::to_llvm_module(compilation_mode, asts).unwrap();
-    let mut output_path = output_dir.to_path_buf();
-    output_path.push(main_path.file_name().unwrap());
-    output_path.set_extension("ll");
-    fs::write(&output_path, compiled_module.get_llvm_text().to_string()).unwrap();
-    output_path.set_extension("s");
-    let metadata = compiled_module.metadata.to_elf_section();
-    fs::write(&output_path, &metadata).unwrap();
-    let binary = comgr
-        .compile(
-            compilation_mode,
-            CString::new(&*full_isa).unwrap().as_c_str(),
-            ptx::Module::get_bitcode_multi(iter::once(&compiled_module)).into_iter(),
-            &metadata,
-        )
-        .unwrap();
-    output_path.set_extension("elf");
-    fs::write(&output_path, &binary).unwrap();
+fn precompile(args: CompilerArguments, modes: Vec<CompilationMode>, comgr: &Comgr) {
+    let isas = if args.isa.is_empty() {
+        vec![default_isa()]
+    } else {
+        args.isa
+    };
+    let targets = isas
+        .iter()
+        .map(|isa| precompile::Target::new(isa, &modes))
+        .collect::<Vec<_>>();
+    let cache_dir = args
+        .cache_dir
+        .or_else(compute::default_dir)
+        .unwrap_or_else(|| exit_usage("Could not determine ZLUDA cache directory"));
+    let cache = fs::create_dir_all(&cache_dir)
+        .and_then(|()| Backend::from_env().open(cache_dir.clone(), cache::max_size_from_env()))
+        .unwrap_or_else(|err| exit_usage(&format!("{}: {}", cache_dir.display(), err)));
+    let mut precompiler =
+        precompile::Precompiler::new(comgr, &*cache, &targets).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(EXIT_FAILURE)
+        });
+    for input in args.inputs.iter() {
+        precompiler.add_host_object(input);
+    }
+    let summary = precompiler.finish();
+    println!(
+        "{} modules compiled, {} failed",
+        summary.compiled, summary.failed
+    );
+    if summary.failed > 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+fn compile_raytracing(
+    args: &CompilerArguments,
+    isa: &str,
+    rt_program: &str,
+    comgr: Rc<Comgr>,
+) -> Result<(), String> {
+    let main_path = match &*args.inputs {
+        [main_path] => main_path,
+        _ => return Err("Raytracing compiler expects single .ptx input file".to_string()),
+    };
+    let input =
+        fs::read_to_string(main_path).map_err(|err| format!("{}: {}", main_path.display(), err))?;
+    let ast = ptx::ModuleParser::parse_checked(&input).map_err(|errors| {
+        errors
+            .iter()
+            .map(|error| {
+                let error = unsafe { ptx::DisplayParseError::new(error, &input) };
+                format!("{}: {}", main_path.display(), error)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+    let output_dir = match args.output {
+        Some(ref output) => output.clone(),
+        None => main_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+    fs::create_dir_all(&output_dir).map_err(|err| format!("{}: {}", output_dir.display(), err))?;
+    let full_isa = format!("amdgcn-amd-amdhsa--{}", isa);
+    compile_and_dump_raytracing(full_isa, output_dir, &comgr, rt_program, main_path, ast)
 }
 
 fn compile_and_dump_raytracing(
     full_isa: String,
-    output_dir: Cow<Path>,
+    mut output_path: PathBuf,
     comgr: &Rc<Comgr>,
-    rt_program: String,
-    filename: &PathBuf,
+    rt_program: &str,
+    filename: &Path,
     ast: ptx::ast::Module,
-) {
+) -> Result<(), String> {
     let mut empty_variable_block = hip_common::raytracing::VariablesBlock::empty();
     let raytracing_module =
-        ptx::to_llvm_module_for_raytracing(ast, &rt_program, &mut empty_variable_block).unwrap();
-    let mut output_path = output_dir.into_owned();
-    output_path.push(filename.file_name().unwrap());
+        ptx::to_llvm_module_for_raytracing(ast, rt_program, &mut empty_variable_block)
+            .map_err(|err| format!("PTX translation failed: {}", err))?;
+    output_path.push(filename.file_name().unwrap_or_default());
     output_path.set_extension("ll");
-    fs::write(
+    write(
         &output_path,
         raytracing_module
             .compilation_module
             .get_llvm_text()
-            .to_string(),
-    )
-    .unwrap();
-    dump_headers(&output_path, &raytracing_module);
+            .to_string()
+            .as_bytes(),
+    )?;
+    dump_headers(&output_path, &raytracing_module)?;
     unsafe { compile_and_dump_relocatable(output_path, raytracing_module, comgr, full_isa) }
 }
 
@@ -148,9 +283,9 @@ unsafe fn compile_and_dump_relocatable(
     raytracing_module: ptx::raytracing::Module,
     comgr: &Rc<Comgr>,
     full_isa: String,
-) {
-    let hiprt_bitcode = build_and_dump_hiprt(&mut output_path, &raytracing_module);
-    let full_isa = CString::from_vec_unchecked(full_isa.into_bytes());
+) -> Result<(), String> {
+    let hiprt_bitcode = build_and_dump_hiprt(&mut output_path, &raytracing_module)?;
+    let full_isa = CString::new(full_isa).map_err(|_| "Invalid ISA".to_string())?;
     let binary = ptx::llvm::MemoryBuffer::create_no_copy(&hiprt_bitcode, false);
     let main_name = CStr::from_bytes_with_nul_unchecked(b"raytracing_main\0");
     let bitcode = comgr
@@ -160,58 +295,64 @@ unsafe fn compile_and_dump_relocatable(
             iter::once((binary, main_name))
                 .chain(raytracing_module.compilation_module.get_bitcode_all()),
         )
-        .unwrap();
-    dump_bitcode(&output_path, &bitcode);
+        .map_err(|err| format!("Linking bitcode failed: {:?}", err))?;
+    dump_bitcode(&output_path, &bitcode)?;
     let relocatable_object = comgr
         .bitcode_to_relocatable(
             raytracing_module.compilation_module.compilation_mode,
             &full_isa,
             &bitcode,
         )
-        .unwrap();
+        .map_err(|err| format!("AMD GPU code compilation failed: {:?}", err))?;
     let relocatable = comgr
         .link_relocatable(&full_isa, iter::once(&relocatable_object))
-        .unwrap();
-    //let relocatable = relocatable_object.get_data().unwrap();
+        .map_err(|err| format!("Linking AMD GPU code failed: {:?}", err))?;
     output_path.set_extension("elf");
-    fs::write(&output_path, relocatable).unwrap();
+    write(&output_path, &relocatable)
 }
 
-fn dump_bitcode(output_path: &PathBuf, bitcode: &comgr::Bitcode) {
+fn dump_bitcode(output_path: &PathBuf, bitcode: &comgr::Bitcode) -> Result<(), String> {
     let mut output_path = output_path.clone();
     let mut file_name = output_path
         .file_stem()
-        .unwrap()
+        .unwrap_or_default()
         .to_string_lossy()
         .into_owned();
     file_name.push_str("_linked.bc");
     output_path.set_file_name(file_name);
-    let linked_bitcode = bitcode.get_data().unwrap();
-    fs::write(output_path, linked_bitcode).unwrap();
+    let linked_bitcode = bitcode
+        .get_data()
+        .map_err(|err| format!("Reading linked bitcode failed: {:?}", err))?;
+    write(&output_path, &linked_bitcode)
 }
 
 unsafe fn build_and_dump_hiprt(
     output_path: &mut PathBuf,
     raytracing_module: &ptx::raytracing::Module,
-) -> Vec<u8> {
+) -> Result<Vec<u8>, String> {
     let kernel_source = raytracing_module.kernel_source;
-    assert_eq!(hipInit(0), hipError_t(0));
+    if hipInit(0) != hipError_t(0) {
+        return Err("HIP initialization failed".to_string());
+    }
     let mut hip_context = ptr::null_mut();
-    assert_eq!(hipCtxCreate(&mut hip_context, 0, 0), hipError_t(0));
+    if hipCtxCreate(&mut hip_context, 0, 0) != hipError_t(0) {
+        return Err("HIP context creation failed".to_string());
+    }
     let mut context_input = hiprtContextCreationInput {
         ctxt: hip_context as _,
         device: 0,
         deviceType: hiprtDeviceType::hiprtDeviceAMD,
     };
-    let hiprt = hiprt_sys::HipRt::load().unwrap();
+    let hiprt = hiprt_sys::HipRt::load().map_err(|_| "Could not load HIP RT".to_string())?;
     let mut context = ptr::null_mut();
-    assert!(
-        hiprt.hiprtCreateContext(
-            hiprt_sys::HIPRT_API_VERSION,
-            &mut context_input,
-            &mut context,
-        ) == hiprtError(0)
-    );
+    if hiprt.hiprtCreateContext(
+        hiprt_sys::HIPRT_API_VERSION,
+        &mut context_input,
+        &mut context,
+    ) != hiprtError(0)
+    {
+        return Err("HIP RT context creation failed".to_string());
+    }
     let debug_level = if cfg!(debug_assertions) {
         b"-g\0".as_ptr()
     } else {
@@ -242,31 +383,32 @@ unsafe fn build_and_dump_hiprt(
         .iter()
         .map(|s| s.as_ptr())
         .collect::<Vec<_>>();
-    assert!(
-        hiprt.hiprtBuildTraceProgram(
-            context,
-            ptx::raytracing::Module::KERNEL_NAME.as_ptr(),
-            kernel_source.as_ptr() as _,
-            "zluda_rt_kernel\0".as_ptr() as _,
-            headers.len() as i32,
-            headers.as_ptr() as _,
-            header_names.as_ptr() as _,
-            options.as_ptr() as _,
-            options.len() as i32,
-            (&mut rt_program) as *mut _ as _,
-        ) == hiprtError(0)
-    );
-    let hiprt_bitcode = get_bitcode(rt_program);
+    if hiprt.hiprtBuildTraceProgram(
+        context,
+        ptx::raytracing::Module::KERNEL_NAME.as_ptr(),
+        kernel_source.as_ptr() as _,
+        "zluda_rt_kernel\0".as_ptr() as _,
+        headers.len() as i32,
+        headers.as_ptr() as _,
+        header_names.as_ptr() as _,
+        options.as_ptr() as _,
+        options.len() as i32,
+        (&mut rt_program) as *mut _ as _,
+    ) != hiprtError(0)
+    {
+        return Err("HIP RT trace program compilation failed".to_string());
+    }
+    let hiprt_bitcode = get_bitcode(rt_program)?;
     let mut output_path = output_path.clone();
     let mut file_name = output_path
         .file_stem()
-        .unwrap()
+        .unwrap_or_default()
         .to_string_lossy()
         .into_owned();
     file_name.push_str("_hiprt.bc");
     output_path.set_file_name(file_name);
-    fs::write(&output_path, &hiprt_bitcode).unwrap();
-    hiprt_bitcode
+    write(&output_path, &hiprt_bitcode)?;
+    Ok(hiprt_bitcode)
 }
 
 #[cfg(windows)]
@@ -275,31 +417,39 @@ const HIPRTC: &'static str = "hiprtc\0";
 #[cfg(not(windows))]
 const HIPRTC: &'static str = "libhiprtc.so\0";
 
-unsafe fn get_bitcode(rt_program: *mut c_void) -> Vec<u8> {
+unsafe fn get_bitcode(rt_program: *mut c_void) -> Result<Vec<u8>, String> {
     use libloading::{Library, Symbol};
-    let hiprtc = Library::new(HIPRTC).unwrap();
+    let hiprtc = Library::new(HIPRTC).map_err(|err| format!("Could not load hiprtc: {}", err))?;
     let hiprtc_get_bitcode_size: Symbol<
         unsafe fn(prog: *mut c_void, bitcode_size: *mut usize) -> u32,
-    > = hiprtc.get(b"hiprtcGetBitcodeSize\0").unwrap();
-    let hiprtc_get_bitcode: Symbol<unsafe fn(prog: *mut c_void, bitcode: *mut u8) -> u32> =
-        hiprtc.get(b"hiprtcGetBitcode\0").unwrap();
+    > = hiprtc
+        .get(b"hiprtcGetBitcodeSize\0")
+        .map_err(|err| err.to_string())?;
+    let hiprtc_get_bitcode: Symbol<unsafe fn(prog: *mut c_void, bitcode: *mut u8) -> u32> = hiprtc
+        .get(b"hiprtcGetBitcode\0")
+        .map_err(|err| err.to_string())?;
     let mut program_size = 0;
     let error = hiprtc_get_bitcode_size(rt_program, &mut program_size);
     if error != 0 {
-        panic!("{}", error);
+        return Err(format!("hiprtcGetBitcodeSize failed: {}", error));
     }
     let mut main_bitcode = vec![0u8; program_size];
     let error = hiprtc_get_bitcode(rt_program, main_bitcode.as_mut_ptr());
     if error != 0 {
-        panic!("{}", error);
+        return Err(format!("hiprtcGetBitcode failed: {}", error));
     }
-    main_bitcode
+    Ok(main_bitcode)
 }
 
-fn dump_headers(output_path: &PathBuf, rt_module: &ptx::raytracing::Module) {
+fn dump_headers(output_path: &PathBuf, rt_module: &ptx::raytracing::Module) -> Result<(), String> {
     let mut header_path = output_path.clone();
     for (header_name, header) in rt_module.header_names.iter().zip(rt_module.headers.iter()) {
-        header_path.set_file_name(header_name.to_str().unwrap());
-        fs::write(&header_path, header.as_bytes()).unwrap();
+        header_path.set_file_name(&*header_name.to_string_lossy());
+        write(&header_path, header.as_bytes())?;
     }
+    Ok(())
+}
+
+fn write(path: &Path, content: &[u8]) -> Result<(), String> {
+    fs::write(path, content).map_err(|err| format!("{}: {}", path.display(), err))
 }
@@ -0,0 +1,300 @@
+// Ahead-of-time compilation of application kernels into ZLUDA compute cache.
+// Every module is stored under the key ZLUDA computes when the application
+// loads it (see KernelCache in zluda), so the first run finds it in the cache
+use comgr::Comgr;
+use hip_common::cache::{compute, KernelRepository};
+use hip_common::CompilationMode;
+use ptx::ModuleParserExt;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::{fs, iter};
+use zluda_dark_api::{
+    extract_ptx, CudaFatbin, FatbinModule, FatbinModuleHandle, HostObjectFatbins, PtxSelection,
+};
+
+pub(crate) struct Target {
+    // Full comgr ISA, e.g. "amdgcn-amd-amdhsa--gfx1030"
+    pub(crate) isa: CString,
+    pub(crate) modes: Vec<CompilationMode>,
+}
+
+impl Target {
+    // `gcn_arch` must be written exactly like HIP reports gcnArchName of the
+    // device, including target features, e.g. "gfx90a:sramecc+:xnack-"
+    pub(crate) fn new(gcn_arch: &str, modes: &[CompilationMode]) -> Self {
+        let isa = CString::new(format!("amdgcn-amd-amdhsa--{}", gcn_arch)).unwrap();
+        let modes = if modes.is_empty() {
+            vec![Self::default_mode(gcn_arch)]
+        } else {
+            modes.to_vec()
+        };
+        Self { isa, modes }
+    }
+
+    // Same as the runtime: only GCN and CDNA GPUs (gfx9 and older) run
+    // 64 lanes wide
+    fn default_mode(gcn_arch: &str) -> CompilationMode {
+        match gcn_arch
+            .strip_prefix("gfx")
+            .and_then(|arch| arch.chars().next())
+        {
+            Some('6'..='9') => CompilationMode::wave64(),
+            _ => CompilationMode::Wave32,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Summary {
+    pub(crate) compiled: usize,
+    pub(crate) failed: usize,
+}
+
+pub(crate) struct Precompiler<'a> {
+    comgr: &'a Comgr,
+    comgr_version: String,
+    cache: &'a dyn KernelRepository,
+    targets: &'a [Target],
+    selection: PtxSelection,
+    summary: Summary,
+}
+
+impl<'a> Precompiler<'a> {
+    pub(crate) fn new(
+        comgr: &'a Comgr,
+        cache: &'a dyn KernelRepository,
+        targets: &'a [Target],
+    ) -> Result<Self, String> {
+        let comgr_version = comgr
+            .version()
+            .map_err(|err| format!("Could not read comgr version: {:?}", err))?;
+        Ok(Self {
+            comgr,
+            comgr_version,
+            cache,
+            targets,
+            selection: PtxSelection::from_env(),
+            summary: Summary::default(),
+        })
+    }
+
+    pub(crate) fn finish(self) -> Summary {
+        self.summary
+    }
+
+    // Executable or a shared library with CUDA device code
+    pub(crate) fn add_host_object(&mut self, path: &Path) {
+        let display = path.display();
+        let elf = match fs::read(path) {
+            Ok(elf) => elf,
+            Err(err) => {
+                eprintln!("{}: {}", display, err);
+                self.summary.failed += 1;
+                return;
+            }
+        };
+        let fatbins = match HostObjectFatbins::from_host_object(&elf) {
+            Ok(fatbins) => fatbins,
+            Err(_) => {
+                eprintln!("{}: not a valid ELF file", display);
+                self.summary.failed += 1;
+                return;
+            }
+        };
+        if fatbins.is_empty() {
+            eprintln!("{}: no CUDA fatbins found", display);
+            return;
+        }
+        for (index, fatbin) in fatbins.loadable_fatbins().enumerate() {
+            if let CudaFatbin::Version1(module) = fatbin {
+                let ptx_files = module_ptx(&module);
+                if ptx_files.is_empty() {
+                    continue;
+                }
+                self.add_ptx_candidates(&format!("{} fatbin #{}", display, index), ptx_files);
+            }
+        }
+        // Programs built with -rdc=true register a device-linked fatbin that
+        // usually has no PTX. ZLUDA then links PTX of all pre-link modules,
+        // which are the relocatable fatbins of the program
+        let relocatable = fatbins
+            .relocatable_modules()
+            .map(|module| {
+                self.selection
+                    .select(module_ptx(&module))
+                    .into_iter()
+                    .next()
+                    .map(|(ptx, _)| ptx)
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(ptx_modules) = relocatable.filter(|modules| !modules.is_empty()) {
+            self.add_linked_ptx(&format!("{} relocatable fatbins", display), &ptx_modules);
+        }
+    }
+
+    // Mirrors link_build_ptx_candidates(...) in zluda
+    fn add_ptx_candidates(&mut self, name: &str, ptx_files: Vec<(Cow<'static, str>, u32)>) {
+        let candidates = self
+            .selection
+            .select(ptx_files.iter().map(|(ptx, sm)| (&**ptx, *sm)).collect());
+        for target in self.targets {
+            for &mode in target.modes.iter() {
+                let mut errors = Vec::new();
+                let compiled = candidates.iter().find_map(|(ptx, sm_version)| {
+                    match compile(self.comgr, &target.isa, mode, &[*ptx]) {
+                        Ok(binary) => Some((*ptx, *sm_version, binary)),
+                        Err(err) => {
+                            errors.push(format!("sm_{}: {}", sm_version, err));
+                            None
+                        }
+                    }
+                });
+                let (ptx, sm_version, binary) = match compiled {
+                    Some(compiled) => compiled,
+                    None => {
+                        self.report_failure(name, &target.isa, mode, &errors);
+                        continue;
+                    }
+                };
+                self.save(&target.isa, mode, &[ptx], &binary);
+                if candidates.len() > 1 {
+                    self.save_ptx_selection(&target.isa, mode, &ptx_files, sm_version);
+                }
+                println!(
+                    "{}: sm_{} compiled for {} ({:?})",
+                    name,
+                    sm_version,
+                    target.isa.to_string_lossy(),
+                    mode
+                );
+                self.summary.compiled += 1;
+            }
+        }
+    }
+
+    fn add_linked_ptx(&mut self, name: &str, ptx_modules: &[Cow<'static, str>]) {
+        let ptx_modules = ptx_modules.iter().map(|ptx| &**ptx).collect::<Vec<_>>();
+        for target in self.targets {
+            for &mode in target.modes.iter() {
+                match compile(self.comgr, &target.isa, mode, &ptx_modules) {
+                    Ok(binary) => {
+                        self.save(&target.isa, mode, &ptx_modules, &binary);
+                        println!(
+                            "{}: compiled for {} ({:?})",
+                            name,
+                            target.isa.to_string_lossy(),
+                            mode
+                        );
+                        self.summary.compiled += 1;
+                    }
+                    Err(err) => self.report_failure(name, &target.isa, mode, &[err]),
+                }
+            }
+        }
+    }
+
+    fn report_failure(&mut self, name: &str, isa: &CStr, mode: CompilationMode, errors: &[String]) {
+        eprintln!(
+            "{}: compilation for {} ({:?}) failed",
+            name,
+            isa.to_string_lossy(),
+            mode
+        );
+        for error in errors {
+            eprintln!("  {}", error);
+        }
+        self.summary.failed += 1;
+    }
+
+    fn save(&self, isa: &CStr, mode: CompilationMode, ptx_modules: &[&str], binary: &[u8]) {
+        let hash = compute::module_hash(ptx_modules.iter().copied());
+        if let Err(err) = self.cache.save_program(
+            &hash,
+            &self.comgr_version,
+            env!("VERGEN_GIT_SHA"),
+            isa,
+            binary,
+            &compute::module_parameters(mode),
+        ) {
+            eprintln!("Could not write {} to the cache: {}", hash, err);
+        }
+    }
+
+    fn save_ptx_selection(
+        &self,
+        isa: &CStr,
+        mode: CompilationMode,
+        ptx_files: &[(Cow<'static, str>, u32)],
+        sm_version: u32,
+    ) {
+        let hash = compute::ptx_selection_hash(ptx_files.iter().map(|(ptx, sm)| (&**ptx, *sm)));
+        if let Err(err) = self.cache.save_program(
+            &hash,
+            &self.comgr_version,
+            env!("VERGEN_GIT_SHA"),
+            isa,
+            &sm_version.to_le_bytes(),
+            &compute::ptx_selection_parameters(mode),
+        ) {
+            eprintln!("Could not write {} to the cache: {}", hash, err);
+        }
+    }
+}
+
+fn module_ptx(module: &FatbinModuleHandle) -> Vec<(Cow<'static, str>, u32)> {
+    match unsafe { module.get() } {
+        Ok(FatbinModule::Files(files)) => extract_ptx(files),
+        Ok(FatbinModule::Elf(_)) | Err(_) => Vec::new(),
+    }
+}
+
+// Unlike ZLUDA, never falls back to an empty module: a module that does not
+// compile is left for ZLUDA to handle at runtime
+fn compile(
+    comgr: &Comgr,
+    isa: &CStr,
+    mode: CompilationMode,
+    ptx_modules: &[&str],
+) -> Result<Vec<u8>, String> {
+    let asts = ptx_modules
+        .iter()
+        .map(|ptx| {
+            ptx::ModuleParser::parse_checked(ptx)
+                .map_err(|errors| format!("PTX parsing failed with {} errors", errors.len()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let module = ptx::to_llvm_module(mode, asts)
+        .map_err(|err| format!("PTX translation failed: {}", err))?;
+    comgr
+        .compile(
+            mode,
+            isa,
+            ptx::Module::get_bitcode_multi(iter::once(&module)).into_iter(),
+            &module.metadata.to_elf_section(),
+        )
+        .map_err(|err| format!("AMD GPU code compilation failed: {:?}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Target;
+    use hip_common::CompilationMode;
+
+    #[test]
+    fn default_mode_follows_wavefront_size() {
+        assert_eq!(Target::default_mode("gfx1030"), CompilationMode::Wave32);
+        assert_eq!(Target::default_mode("gfx1100"), CompilationMode::Wave32);
+        assert_eq!(Target::default_mode("gfx906"), CompilationMode::wave64());
+        assert_eq!(
+            Target::default_mode("gfx90a:sramecc+:xnack-"),
+            CompilationMode::wave64()
+        );
+        if std::env::var_os("ZLUDA_WAVE64_SLOW_MODE").is_none() {
+            assert_eq!(
+                Target::default_mode("gfx906"),
+                CompilationMode::DoubleWave32OnWave64
+            );
+        }
+    }
+}
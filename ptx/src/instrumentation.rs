@@ -0,0 +1,539 @@
+// Optional instrumentation of PTX translation: wall time of every pass and
+// readable dumps of the module after selected passes
+use crate::ast;
+use crate::translate::{
+    ConversionKind, Id, Statement, TranslationDirective, TranslationMethod, TranslationModule,
+};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// Passes that transform the statement list, in the order they run. Any of
+// them can be named in `dump_after`
+pub const PASSES: &[&str] = &[
+    "link_and_normalize_modules",
+    "extract_builtin_functions",
+    "resolve_instruction_types",
+    "restructure_function_return_types",
+    "deparamize_function_declarations",
+    "insert_hardware_registers",
+    "fix_special_registers",
+    "insert_mem_ssa_statements",
+    "expand_arguments",
+    "deparamize_variable_declarations",
+    "insert_implicit_conversions",
+    "insert_compilation_mode_prologue",
+    "normalize_labels",
+    "hoist_globals",
+    "move_variables_to_start",
+    "replace_instructions_with_builtins",
+    "convert_dynamic_shared_memory_usage",
+];
+
+// Dumps the module after every pass
+pub const ALL_PASSES: &str = "all";
+
+const TIME_PASSES_ENV: &str = "ZLUDA_PTX_TIME_PASSES";
+const DUMP_AFTER_ENV: &str = "ZLUDA_PTX_DUMP_AFTER";
+
+pub struct PassTiming {
+    pub pass: &'static str,
+    pub duration: Duration,
+}
+
+pub struct PassDump {
+    pub pass: &'static str,
+    pub text: String,
+}
+
+pub struct PassInstrumentation {
+    time_passes: bool,
+    dump_after: Vec<String>,
+    pub timings: Vec<PassTiming>,
+    pub dumps: Vec<PassDump>,
+}
+
+impl PassInstrumentation {
+    pub fn new(time_passes: bool, dump_after: Vec<String>) -> Result<Self, String> {
+        for pass in dump_after.iter() {
+            if pass != ALL_PASSES && !PASSES.contains(&&**pass) {
+                return Err(format!(
+                    "Unknown pass: {}, expected one of: {}, {}",
+                    pass,
+                    ALL_PASSES,
+                    PASSES.join(", ")
+                ));
+            }
+        }
+        Ok(Self {
+            time_passes,
+            dump_after,
+            timings: Vec::new(),
+            dumps: Vec::new(),
+        })
+    }
+
+    // ZLUDA_PTX_TIME_PASSES=1 records pass timings, ZLUDA_PTX_DUMP_AFTER is a
+    // comma-separated list of passes (or `all`). Returns None if neither is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let time_passes = match std::env::var(TIME_PASSES_ENV) {
+            Ok(value) => str::parse::<u32>(&value).map_or(false, |value| value != 0),
+            Err(_) => false,
+        };
+        let dump_after = match std::env::var(DUMP_AFTER_ENV) {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|pass| !pass.is_empty())
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+        if !time_passes && dump_after.is_empty() {
+            return Ok(None);
+        }
+        Self::new(time_passes, dump_after)
+            .map(Some)
+            .map_err(|err| format!("{}: {}", DUMP_AFTER_ENV, err))
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.timings.iter().map(|timing| timing.duration).sum()
+    }
+
+    // One line per pass followed by the total, durations in milliseconds
+    pub fn timing_report(&self) -> String {
+        let width = self
+            .timings
+            .iter()
+            .map(|timing| timing.pass.len())
+            .max()
+            .unwrap_or(0);
+        let mut result = String::new();
+        for timing in self.timings.iter() {
+            writeln!(
+                result,
+                "{:<width$} {:>10.3} ms",
+                timing.pass,
+                timing.duration.as_secs_f64() * 1000.0,
+                width = width
+            )
+            .unwrap();
+        }
+        write!(
+            result,
+            "{:<width$} {:>10.3} ms",
+            "total",
+            self.total_time().as_secs_f64() * 1000.0,
+            width = width
+        )
+        .unwrap();
+        result
+    }
+
+    pub(crate) fn time<T>(&mut self, pass: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.time_passes {
+            return f();
+        }
+        let start = Instant::now();
+        let result = f();
+        self.timings.push(PassTiming {
+            pass,
+            duration: start.elapsed(),
+        });
+        result
+    }
+
+    pub(crate) fn dump<P: ast::ArgParams<Id = Id>>(
+        &mut self,
+        pass: &'static str,
+        module: &TranslationModule<P>,
+    ) {
+        if self
+            .dump_after
+            .iter()
+            .any(|name| name == ALL_PASSES || name == pass)
+        {
+            self.dumps.push(PassDump {
+                pass,
+                text: print_module(module),
+            });
+        }
+    }
+}
+
+// PTX-like listing of the module. Instructions are printed without operands,
+// every other statement with the ids it reads and writes
+fn print_module<P: ast::ArgParams<Id = Id>>(module: &TranslationModule<P>) -> String {
+    let mut result = String::new();
+    for directive in module.directives.iter() {
+        match directive {
+            TranslationDirective::Variable(_, name, var) => {
+                write!(
+                    result,
+                    "{}{} {}",
+                    space_name(var.state_space),
+                    type_name(&var.type_),
+                    id(var.name)
+                )
+                .unwrap();
+                if let Some(name) = name {
+                    write!(result, " // {}", name).unwrap();
+                }
+                result.push('\n');
+            }
+            TranslationDirective::Method(method) => print_method(&mut result, method),
+        }
+    }
+    result
+}
+
+fn print_method<P: ast::ArgParams<Id = Id>>(result: &mut String, method: &TranslationMethod<P>) {
+    let kind = if method.is_kernel { ".entry" } else { ".func" };
+    write!(result, "{}", kind).unwrap();
+    if !method.return_arguments.is_empty() {
+        write!(result, " ({})", arguments(&method.return_arguments)).unwrap();
+    }
+    write!(
+        result,
+        " {}({})",
+        id(method.name),
+        arguments(&method.input_arguments)
+    )
+    .unwrap();
+    if let Some(ref name) = method.source_name {
+        write!(result, " // {}", name).unwrap();
+    }
+    result.push('\n');
+    let body = match method.body {
+        Some(ref body) => body,
+        None => return,
+    };
+    result.push_str("{\n");
+    for statement in body.iter() {
+        print_statement(result, statement);
+        result.push('\n');
+    }
+    result.push_str("}\n");
+}
+
+fn print_statement<P: ast::ArgParams<Id = Id>>(
+    result: &mut String,
+    statement: &Statement<ast::Instruction<P>, P>,
+) {
+    // Writing to a String can't fail
+    let written = match statement {
+        Statement::Label(label) => write!(result, "{}:", id(*label)),
+        Statement::Variable(var) => write!(
+            result,
+            "    {}{} {}",
+            space_name(var.state_space),
+            type_name(&var.type_),
+            id(var.name)
+        ),
+        Statement::Instruction(inst) => write!(result, "    {}", instruction_name(inst)),
+        Statement::Conditional(cond) => write!(
+            result,
+            "    @{} bra {}, {}",
+            id(cond.predicate),
+            id(cond.if_true),
+            id(cond.if_false)
+        ),
+        Statement::Call(call) => {
+            let returns = call
+                .return_arguments
+                .iter()
+                .map(|(ret, _, _)| id(*ret))
+                .collect::<Vec<_>>();
+            write!(
+                result,
+                "    call ({}), {}, {} arguments",
+                returns.join(", "),
+                id(call.name),
+                call.input_arguments.len()
+            )
+        }
+        Statement::LoadVar(details) => write!(
+            result,
+            "    {} = load_var{} {}",
+            id(details.arg.dst),
+            type_name(&details.typ),
+            id(details.arg.src)
+        ),
+        Statement::StoreVar(details) => write!(
+            result,
+            "    store_var{} {}, {}",
+            type_name(&details.type_),
+            id(details.arg.src1),
+            id(details.arg.src2)
+        ),
+        Statement::Conversion(conv) => write!(
+            result,
+            "    {} = {}{}{} {} // from {}{}",
+            id(conv.dst),
+            conversion_name(&conv.kind),
+            space_name(conv.to_space),
+            type_name(&conv.to_type),
+            id(conv.src),
+            space_name(conv.from_space),
+            type_name(&conv.from_type)
+        ),
+        Statement::Constant(constant) => {
+            let value = match constant.value {
+                ast::ImmediateValue::U64(value) => value.to_string(),
+                ast::ImmediateValue::S64(value) => value.to_string(),
+                ast::ImmediateValue::F32(value) => value.to_string(),
+                ast::ImmediateValue::F64(value) => value.to_string(),
+            };
+            write!(
+                result,
+                "    {} = const.{} {}",
+                id(constant.dst),
+                constant.typ.to_ptx_name(),
+                value
+            )
+        }
+        Statement::RetValue(_, values) => {
+            let values = values
+                .iter()
+                .map(|(value, _)| id(*value))
+                .collect::<Vec<_>>();
+            write!(result, "    ret {}", values.join(", "))
+        }
+        Statement::PtrAccess(access) => write!(
+            result,
+            "    {} = ptr_access{}{} {}",
+            id(access.dst),
+            space_name(access.state_space),
+            type_name(&access.underlying_type),
+            id(access.ptr_src)
+        ),
+        Statement::RepackVector(repack) => {
+            let unpacked = repack
+                .unpacked
+                .iter()
+                .map(|unpacked| id(*unpacked))
+                .collect::<Vec<_>>();
+            if repack.is_extract {
+                write!(
+                    result,
+                    "    {{{}}} = unpack.{} {}",
+                    unpacked.join(", "),
+                    repack.typ.to_ptx_name(),
+                    id(repack.packed)
+                )
+            } else {
+                write!(
+                    result,
+                    "    {} = pack.{} {{{}}}",
+                    id(repack.packed),
+                    repack.typ.to_ptx_name(),
+                    unpacked.join(", ")
+                )
+            }
+        }
+        Statement::FunctionPointer(details) => write!(
+            result,
+            "    {} = function_pointer {}",
+            id(details.dst),
+            id(details.src)
+        ),
+        Statement::MadC(details) => write!(result, "    madc.{}", details.type_.to_ptx_name()),
+        Statement::MadCC(details) => write!(result, "    mad.cc.{}", details.type_.to_ptx_name()),
+        Statement::AddC(type_, _) => write!(result, "    addc.{}", type_.to_ptx_name()),
+        Statement::AddCC(type_, _) => write!(result, "    add.cc.{}", type_.to_ptx_name()),
+        Statement::SubC(type_, _) => write!(result, "    subc.{}", type_.to_ptx_name()),
+        Statement::SubCC(type_, _) => write!(result, "    sub.cc.{}", type_.to_ptx_name()),
+        Statement::AsmVolatile { asm, .. } => write!(result, "    asm volatile \"{}\"", asm),
+    };
+    written.unwrap()
+}
+
+fn arguments(arguments: &[ast::VariableDeclaration<Id>]) -> String {
+    arguments
+        .iter()
+        .map(|arg| {
+            format!(
+                "{}{} {}",
+                space_name(arg.state_space),
+                type_name(&arg.type_),
+                id(arg.name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn id(id: Id) -> String {
+    format!("%{}", id.get())
+}
+
+fn type_name(type_: &ast::Type) -> String {
+    match type_ {
+        ast::Type::Scalar(scalar) => format!(".{}", scalar.to_ptx_name()),
+        ast::Type::Vector(scalar, len) => format!(".v{}.{}", len, scalar.to_ptx_name()),
+        ast::Type::Array(scalar, dimensions) => {
+            let mut result = format!(".{}", scalar.to_ptx_name());
+            for dimension in dimensions.iter() {
+                write!(result, "[{}]", dimension).unwrap();
+            }
+            result
+        }
+        ast::Type::Pointer(scalar, space) => {
+            format!(".ptr<.{}{}>", scalar.to_ptx_name(), space_name(*space))
+        }
+        ast::Type::Texref => ".texref".to_string(),
+        ast::Type::Surfref => ".surfref".to_string(),
+        ast::Type::Struct(fields) => {
+            let fields = fields
+                .iter()
+                .map(|field| match field {
+                    ast::StructField::Scalar(scalar) => format!(".{}", scalar.to_ptx_name()),
+                    ast::StructField::Vector(scalar, len) => {
+                        format!(".v{}.{}", len, scalar.to_ptx_name())
+                    }
+                })
+                .collect::<Vec<_>>();
+            format!(".struct{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+fn space_name(space: ast::StateSpace) -> &'static str {
+    match space {
+        ast::StateSpace::Reg => ".reg",
+        ast::StateSpace::Const => ".const",
+        ast::StateSpace::Global => ".global",
+        ast::StateSpace::Local => ".local",
+        ast::StateSpace::Shared => ".shared",
+        ast::StateSpace::Param => ".param",
+        ast::StateSpace::Generic => "",
+        ast::StateSpace::Sreg => ".sreg",
+    }
+}
+
+fn conversion_name(kind: &ConversionKind) -> &'static str {
+    match kind {
+        ConversionKind::Default => "convert",
+        ConversionKind::SignExtend => "sext",
+        ConversionKind::BitToPtr => "bit_to_ptr",
+        ConversionKind::PtrToPtr => "ptr_to_ptr",
+        ConversionKind::AddressOf => "address_of",
+    }
+}
+
+fn instruction_name<P: ast::ArgParams>(inst: &ast::Instruction<P>) -> &'static str {
+    match inst {
+        ast::Instruction::Ld(..) => "ld",
+        ast::Instruction::Mov(..) => "mov",
+        ast::Instruction::Mul(..) => "mul",
+        ast::Instruction::Add(..) => "add",
+        ast::Instruction::AddC(..) => "addc",
+        ast::Instruction::AddCC(..) => "add.cc",
+        ast::Instruction::Setp(..) => "setp",
+        ast::Instruction::SetpBool(..) => "setp.bool",
+        ast::Instruction::Not(..) => "not",
+        ast::Instruction::Bra(..) => "bra",
+        ast::Instruction::Cvt(..) => "cvt",
+        ast::Instruction::Cvta(..) => "cvta",
+        ast::Instruction::Shl(..) => "shl",
+        ast::Instruction::Shr(..) => "shr",
+        ast::Instruction::St(..) => "st",
+        ast::Instruction::Ret(..) => "ret",
+        ast::Instruction::Call(..) => "call",
+        ast::Instruction::Abs(..) => "abs",
+        ast::Instruction::Mad(..) => "mad",
+        ast::Instruction::MadC { .. } => "madc",
+        ast::Instruction::MadCC { .. } => "mad.cc",
+        ast::Instruction::Fma(..) => "fma",
+        ast::Instruction::Or(..) => "or",
+        ast::Instruction::Sub(..) => "sub",
+        ast::Instruction::SubC(..) => "subc",
+        ast::Instruction::SubCC(..) => "sub.cc",
+        ast::Instruction::Min(..) => "min",
+        ast::Instruction::Max(..) => "max",
+        ast::Instruction::Rcp(..) => "rcp",
+        ast::Instruction::Sqrt(..) => "sqrt",
+        ast::Instruction::And(..) => "and",
+        ast::Instruction::Selp(..) => "selp",
+        ast::Instruction::Bar(..) => "bar",
+        ast::Instruction::BarWarp(..) => "bar.warp",
+        ast::Instruction::BarRed(..) => "bar.red",
+        ast::Instruction::Atom(..) => "atom",
+        ast::Instruction::AtomCas(..) => "atom.cas",
+        ast::Instruction::Div(..) => "div",
+        ast::Instruction::Rsqrt(..) => "rsqrt",
+        ast::Instruction::Neg(..) => "neg",
+        ast::Instruction::Sin { .. } => "sin",
+        ast::Instruction::Cos { .. } => "cos",
+        ast::Instruction::Lg2 { .. } => "lg2",
+        ast::Instruction::Ex2 { .. } => "ex2",
+        ast::Instruction::Clz { .. } => "clz",
+        ast::Instruction::Brev { .. } => "brev",
+        ast::Instruction::Popc { .. } => "popc",
+        ast::Instruction::Xor { .. } => "xor",
+        ast::Instruction::Bfe { .. } => "bfe",
+        ast::Instruction::Bfi { .. } => "bfi",
+        ast::Instruction::Rem { .. } => "rem",
+        ast::Instruction::Prmt { .. } => "prmt",
+        ast::Instruction::PrmtSlow { .. } => "prmt",
+        ast::Instruction::Activemask { .. } => "activemask",
+        ast::Instruction::Membar { .. } => "membar",
+        ast::Instruction::Tex(..) => "tex",
+        ast::Instruction::Suld(..) => "suld",
+        ast::Instruction::Sust(..) => "sust",
+        ast::Instruction::Shfl(..) => "shfl",
+        ast::Instruction::Shf(..) => "shf",
+        ast::Instruction::Vote(..) => "vote",
+        ast::Instruction::Exit => "exit",
+        ast::Instruction::Trap => "trap",
+        ast::Instruction::Brkpt => "brkpt",
+        ast::Instruction::Vshr(..) => "vshr",
+        ast::Instruction::Bfind(..) => "bfind",
+        ast::Instruction::Set(..) => "set",
+        ast::Instruction::Dp4a(..) => "dp4a",
+        ast::Instruction::MatchAny(..) => "match.any",
+        ast::Instruction::Red(..) => "red",
+        ast::Instruction::Nanosleep(..) => "nanosleep",
+        ast::Instruction::Sad(..) => "sad",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PassInstrumentation, PASSES};
+    use crate::ModuleParserExt;
+    use hip_common::CompilationMode;
+
+    const ADD: &str = include_str!("test/spirv_run/add.ptx");
+
+    #[test]
+    fn records_every_pass_and_requested_dumps() {
+        let ast = crate::ModuleParser::parse_checked(ADD).unwrap();
+        let mut instrumentation =
+            PassInstrumentation::new(true, vec!["expand_arguments".to_string()]).unwrap();
+        crate::to_llvm_module_instrumented(
+            CompilationMode::Wave32,
+            vec![ast],
+            &mut instrumentation,
+        )
+        .unwrap();
+        let timed = instrumentation
+            .timings
+            .iter()
+            .map(|timing| timing.pass)
+            .filter(|pass| PASSES.contains(pass))
+            .collect::<Vec<_>>();
+        assert_eq!(timed, PASSES);
+        assert_eq!(instrumentation.dumps.len(), 1);
+        let dump = &instrumentation.dumps[0];
+        assert_eq!(dump.pass, "expand_arguments");
+        assert!(dump.text.contains(".entry"));
+        assert!(dump.text.contains("// add"));
+    }
+
+    #[test]
+    fn rejects_unknown_pass() {
+        assert!(PassInstrumentation::new(false, vec!["all".to_string()]).is_ok());
+        assert!(PassInstrumentation::new(false, vec!["not_a_pass".to_string()]).is_err());
+    }
+}
@@ -11,6 +11,7 @@ lalrpop_mod!(ptx);
 
 pub mod ast;
 mod emit;
+pub mod instrumentation;
 pub mod llvm;
 pub mod raytracing;
 #[cfg(test)]
@@ -23,7 +24,9 @@ pub use lalrpop_util::lexer::Token;
 pub use lalrpop_util::ParseError;
 use std::fmt;
 pub use translate::to_llvm_module;
+pub use instrumentation::PassInstrumentation;
 pub use translate::to_llvm_module_for_raytracing;
+pub use translate::to_llvm_module_instrumented;
 pub use translate::Module;
 pub use translate::TranslateError;
 
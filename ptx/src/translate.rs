@@ -1,3 +1,4 @@
+use crate::instrumentation::PassInstrumentation;
 use crate::llvm::Message;
 use crate::{ast, emit, llvm, raytracing};
 use bit_vec::BitVec;
@@ -3302,7 +3303,17 @@ pub fn to_llvm_module<'input>(
     compilation_mode: CompilationMode,
     ast: Vec<ast::Module<'input>>,
 ) -> Result<Module, TranslateError> {
-    to_llvm_module_impl2(compilation_mode, ast, None)
+    to_llvm_module_impl2(compilation_mode, ast, None, None)
+}
+
+// Same as to_llvm_module(...), but records pass timings and module dumps
+// requested by `instrumentation`
+pub fn to_llvm_module_instrumented<'input>(
+    compilation_mode: CompilationMode,
+    ast: Vec<ast::Module<'input>>,
+    instrumentation: &mut PassInstrumentation,
+) -> Result<Module<'input>, TranslateError> {
+    to_llvm_module_impl2(compilation_mode, ast, None, Some(instrumentation))
 }
 
 pub fn to_llvm_module_for_raytracing<'input>(
@@ -3316,6 +3327,7 @@ pub fn to_llvm_module_for_raytracing<'input>(
         CompilationMode::Wave32,
         vec![ast],
         Some(&mut raytracing_state),
+        None,
     )?;
     let entry_point_kind: RaytracingEntryPointKind = raytracing_state.entry_point_kind.unwrap();
     let rt_section = hip_common::kernel_metadata::zluda_rt6::write(
@@ -3380,45 +3392,135 @@ fn to_llvm_module_impl2<'a, 'input>(
     compilation_mode: CompilationMode,
     asts: Vec<ast::Module<'input>>,
     mut raytracing: Option<&mut RaytracingTranslationState<'a, 'input>>,
+    mut instrumentation: Option<&mut PassInstrumentation>,
 ) -> Result<Module<'input>, TranslateError> {
     let empty_module = if raytracing.is_some() {
         raytracing::create_module_with_builtins()
     } else {
         TranslationModule::new(compilation_mode)
     };
-    let linking = resolve_linking(&*asts, raytracing.is_some())?;
+    let linking = time_pass(&mut instrumentation, "resolve_linking", || {
+        resolve_linking(&*asts, raytracing.is_some())
+    })?;
     let (mut translation_module, functions) =
-        link_and_normalize_modules(asts, empty_module, linking)?;
+        time_pass(&mut instrumentation, "link_and_normalize_modules", || {
+            link_and_normalize_modules(asts, empty_module, linking)
+        })?;
+    if let Some(ref mut instrumentation) = instrumentation {
+        instrumentation.dump("link_and_normalize_modules", &translation_module);
+    }
     if let Some(ref mut raytracing_state) = raytracing {
         translation_module = raytracing::run_on_normalized(translation_module, raytracing_state)?;
     }
-    let translation_module = extract_builtin_functions(translation_module);
-    let translation_module = resolve_instruction_types(translation_module, functions)?;
-    let mut translation_module = restructure_function_return_types(translation_module)?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "extract_builtin_functions",
+        translation_module,
+        |module| Ok(extract_builtin_functions(module)),
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "resolve_instruction_types",
+        translation_module,
+        |module| resolve_instruction_types(module, functions),
+    )?;
+    let mut translation_module = run_pass(
+        &mut instrumentation,
+        "restructure_function_return_types",
+        translation_module,
+        restructure_function_return_types,
+    )?;
     if let Some(ref mut raytracing_state) = raytracing {
         translation_module = raytracing::run_on_typed(translation_module, raytracing_state)?;
     }
-    let translation_module = deparamize_function_declarations(translation_module)?;
-    let translation_module = insert_hardware_registers(translation_module)?;
-    let translation_module = fix_special_registers(translation_module)?;
-    let translation_module = insert_mem_ssa_statements(translation_module)?;
-    let translation_module = expand_arguments(translation_module)?;
-    let mut translation_module = deparamize_variable_declarations(translation_module)?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "deparamize_function_declarations",
+        translation_module,
+        deparamize_function_declarations,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "insert_hardware_registers",
+        translation_module,
+        insert_hardware_registers,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "fix_special_registers",
+        translation_module,
+        fix_special_registers,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "insert_mem_ssa_statements",
+        translation_module,
+        insert_mem_ssa_statements,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "expand_arguments",
+        translation_module,
+        expand_arguments,
+    )?;
+    let mut translation_module = run_pass(
+        &mut instrumentation,
+        "deparamize_variable_declarations",
+        translation_module,
+        deparamize_variable_declarations,
+    )?;
     if let Some(ref mut raytracing_state) = raytracing {
         // raytracing passes rely heavily on particular PTX patterns, they must run before implicit conversions
         translation_module = raytracing::postprocess(translation_module, raytracing_state)?;
     }
-    let translation_module = insert_implicit_conversions(translation_module)?;
-    let translation_module = insert_compilation_mode_prologue(translation_module);
-    let translation_module = normalize_labels(translation_module)?;
-    let translation_module = hoist_globals(translation_module);
-    let translation_module = move_variables_to_start(translation_module)?;
-    let mut translation_module = replace_instructions_with_builtins(translation_module)?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "insert_implicit_conversions",
+        translation_module,
+        insert_implicit_conversions,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "insert_compilation_mode_prologue",
+        translation_module,
+        |module| Ok(insert_compilation_mode_prologue(module)),
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "normalize_labels",
+        translation_module,
+        normalize_labels,
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "hoist_globals",
+        translation_module,
+        |module| Ok(hoist_globals(module)),
+    )?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "move_variables_to_start",
+        translation_module,
+        move_variables_to_start,
+    )?;
+    let mut translation_module = run_pass(
+        &mut instrumentation,
+        "replace_instructions_with_builtins",
+        translation_module,
+        replace_instructions_with_builtins,
+    )?;
     if raytracing.is_some() {
         translation_module = raytracing::replace_tex_builtins_hack(translation_module)?;
     }
-    let call_graph = CallGraph::new(&translation_module.directives);
-    let translation_module = convert_dynamic_shared_memory_usage(translation_module, &call_graph)?;
+    let translation_module = run_pass(
+        &mut instrumentation,
+        "convert_dynamic_shared_memory_usage",
+        translation_module,
+        |module| {
+            let call_graph = CallGraph::new(&module.directives);
+            convert_dynamic_shared_memory_usage(module, &call_graph)
+        },
+    )?;
     let denorm_statistics = compute_denorm_statistics(&translation_module);
     let kernel_arguments = get_kernel_arguments(&translation_module.directives)?;
     let mut bitcode_modules = vec![ZLUDA_PTX_IMPL_AMD];
@@ -3426,9 +3528,9 @@ fn to_llvm_module_impl2<'a, 'input>(
         bitcode_modules.push(raytracing::bitcode());
     }
     let metadata = create_metadata(&translation_module);
-    let (llvm_context, llvm_module) = unsafe {
-        emit::emit_llvm_bitcode_and_linker_module(translation_module, denorm_statistics)?
-    };
+    let (llvm_context, llvm_module) = time_pass(&mut instrumentation, "emit_llvm", || unsafe {
+        emit::emit_llvm_bitcode_and_linker_module(translation_module, denorm_statistics)
+    })?;
     Ok(Module {
         metadata,
         compilation_mode,
@@ -3439,6 +3541,34 @@ fn to_llvm_module_impl2<'a, 'input>(
     })
 }
 
+fn time_pass<T>(
+    instrumentation: &mut Option<&mut PassInstrumentation>,
+    name: &'static str,
+    pass: impl FnOnce() -> T,
+) -> T {
+    match instrumentation {
+        Some(instrumentation) => instrumentation.time(name, pass),
+        None => pass(),
+    }
+}
+
+fn run_pass<'input, P: ast::ArgParams<Id = Id>, Q: ast::ArgParams<Id = Id>>(
+    instrumentation: &mut Option<&mut PassInstrumentation>,
+    name: &'static str,
+    module: TranslationModule<'input, P>,
+    pass: impl FnOnce(
+        TranslationModule<'input, P>,
+    ) -> Result<TranslationModule<'input, Q>, TranslateError>,
+) -> Result<TranslationModule<'input, Q>, TranslateError> {
+    let instrumentation = match instrumentation {
+        Some(instrumentation) => instrumentation,
+        None => return pass(module),
+    };
+    let module = instrumentation.time(name, || pass(module))?;
+    instrumentation.dump(name, &module);
+    Ok(module)
+}
+
 // From "Performance Tips for Frontend Authors" (https://llvm.org/docs/Frontend/PerformanceTips.html):
 // "The SROA (Scalar Replacement Of Aggregates) and Mem2Reg passes only attempt to eliminate alloca
 // instructions that are in the entry basic block. Given SSA is the canonical form expected by much
@@ -4993,6 +5123,8 @@ enum PtxSpecialRegister {
     LanemaskGe,
     Laneid,
     Clock64,
+    Envreg1,
+    Envreg2,
 }
 
 impl PtxSpecialRegister {
@@ -5008,6 +5140,8 @@ impl PtxSpecialRegister {
             "%lanemask_ge" => Some(Self::LanemaskGe),
             "%laneid" => Some(Self::Laneid),
             "%clock64" => Some(Self::Clock64),
+            "%envreg1" => Some(Self::Envreg1),
+            "%envreg2" => Some(Self::Envreg2),
             _ => None,
         }
     }
@@ -5032,7 +5166,9 @@ impl PtxSpecialRegister {
             | PtxSpecialRegister::LanemaskLt
             | PtxSpecialRegister::LanemaskLe
             | PtxSpecialRegister::LanemaskGe
-            | PtxSpecialRegister::Laneid => ast::ScalarType::U32,
+            | PtxSpecialRegister::Laneid
+            | PtxSpecialRegister::Envreg1
+            | PtxSpecialRegister::Envreg2 => ast::ScalarType::U32,
             PtxSpecialRegister::Clock64 => ast::ScalarType::U64,
         }
     }
@@ -5048,7 +5184,9 @@ impl PtxSpecialRegister {
             | PtxSpecialRegister::LanemaskLt
             | PtxSpecialRegister::LanemaskLe
             | PtxSpecialRegister::LanemaskGe
-            | PtxSpecialRegister::Laneid => None,
+            | PtxSpecialRegister::Laneid
+            | PtxSpecialRegister::Envreg1
+            | PtxSpecialRegister::Envreg2 => None,
         }
     }
 
@@ -5064,6 +5202,9 @@ impl PtxSpecialRegister {
             PtxSpecialRegister::LanemaskLe => "sreg_lanemask_le",
             PtxSpecialRegister::LanemaskGe => "sreg_lanemask_ge",
             PtxSpecialRegister::Laneid => "sreg_laneid",
+            // Only used by grid-wide barriers, implemented in comgr's grid.ll
+            PtxSpecialRegister::Envreg1 => "sreg_envreg1",
+            PtxSpecialRegister::Envreg2 => "sreg_envreg2",
         }
     }
 }
@@ -39,6 +39,8 @@ cuda_function_declarations!(
         cuDeviceGet,
         cuDeviceGetCount,
         cuDeviceGetMemPool,
+        cuDeviceSetMemPool,
+        cuDeviceGetDefaultMemPool,
         cuDeviceGetName,
         cuDeviceGetUuid,
         cuDeviceGetUuid_v2,
@@ -74,6 +76,8 @@ cuda_function_declarations!(
         cuCtxSynchronize,
         cuCtxSetCacheConfig,
         cuCtxGetApiVersion,
+        cuCtxEnablePeerAccess,
+        cuCtxDisablePeerAccess,
         cuFuncSetCacheConfig,
         cuLibraryLoadData,
         cuLibraryGetModule,
@@ -93,19 +97,45 @@ cuda_function_declarations!(
         cuMemAllocPitch_v2,
         cuMemFree_v2,
         cuMemFreeAsync,
+        cuMemFreeAsync_ptsz,
         cuMemFreeHost,
         cuMemHostAlloc,
         cuMemHostRegister,
         cuMemHostRegister_v2,
         cuMemHostUnregister,
         cuMemGetAddressRange_v2,
+        cuMemAllocAsync,
+        cuMemAllocAsync_ptsz,
+        cuMemAllocFromPoolAsync,
+        cuMemAllocFromPoolAsync_ptsz,
+        cuMemPoolCreate,
+        cuMemPoolDestroy,
+        cuMemPoolTrimTo,
+        cuMemPoolGetAttribute,
         cuMemPoolSetAttribute,
+        cuMemPoolSetAccess,
+        cuMemPoolGetAccess,
         cuMemPrefetchAsync,
+        cuMemCreate,
+        cuMemRelease,
+        cuMemAddressReserve,
+        cuMemAddressFree,
+        cuMemMap,
+        cuMemUnmap,
+        cuMemSetAccess,
+        cuMemGetAccess,
+        cuMemGetAllocationGranularity,
+        cuMemGetAllocationPropertiesFromHandle,
+        cuMemExportToShareableHandle,
+        cuMemImportFromShareableHandle,
+        cuMemRetainAllocationHandle,
         cuDeviceGetPCIBusId,
         cuMemcpy,
         cuMemcpy_ptds,
         cuMemcpyAsync,
         cuMemcpyAsync_ptsz,
+        cuMemcpyPeer,
+        cuMemcpyPeerAsync,
         cuMemcpyHtoD_v2,
         cuMemcpyHtoD_v2_ptds,
         cuMemcpyDtoH_v2,
@@ -122,6 +152,8 @@ cuda_function_declarations!(
         cuMemcpy2DUnaligned_v2,
         cuMemcpy3D_v2,
         cuMemcpy3DAsync_v2,
+        cuMemcpy3DPeer,
+        cuMemcpy3DPeerAsync,
         cuMemsetD8_v2,
         cuMemsetD8_v2_ptds,
         cuMemsetD8Async,
@@ -136,15 +168,27 @@ cuda_function_declarations!(
         cuArrayDestroy,
         cuArray3DCreate_v2,
         cuArray3DGetDescriptor_v2,
+        cuMipmappedArrayCreate,
+        cuMipmappedArrayDestroy,
+        cuMipmappedArrayGetLevel,
         cuPointerGetAttribute,
         cuPointerGetAttributes,
+        cuStreamBeginCapture,
+        cuStreamBeginCapture_ptsz,
+        cuStreamBeginCapture_v2,
+        cuStreamBeginCapture_v2_ptsz,
         cuStreamCreate,
         cuStreamCreateWithPriority,
+        cuStreamEndCapture,
+        cuStreamEndCapture_ptsz,
         cuStreamGetCaptureInfo,
+        cuStreamGetCaptureInfo_v2,
+        cuStreamGetCaptureInfo_v2_ptsz,
         cuStreamGetCtx,
         cuStreamGetCtx_ptsz,
         cuStreamGetFlags,
         cuStreamIsCapturing,
+        cuStreamIsCapturing_ptsz,
         cuStreamQuery,
         cuStreamSynchronize,
         cuStreamSynchronize_ptsz,
@@ -152,12 +196,36 @@ cuda_function_declarations!(
         cuStreamDestroy_v2,
         cuStreamWaitEvent,
         cuStreamWaitEvent_ptsz,
+        cuStreamUpdateCaptureDependencies,
+        cuStreamUpdateCaptureDependencies_ptsz,
+        cuThreadExchangeStreamCaptureMode,
         cuFuncGetAttribute,
         cuFuncSetAttribute,
         cuLaunchHostFunc,
         cuLaunchKernel,
         cuLaunchKernel_ptsz,
+        cuLaunchKernelEx,
+        cuLaunchKernelEx_ptsz,
+        cuLaunchCooperativeKernel,
+        cuLaunchCooperativeKernel_ptsz,
+        cuLaunchCooperativeKernelMultiDevice,
         cuMemHostGetDevicePointer_v2,
+        cuIpcGetMemHandle,
+        cuIpcOpenMemHandle,
+        cuIpcOpenMemHandle_v2,
+        cuIpcCloseMemHandle,
+        cuIpcGetEventHandle,
+        cuIpcOpenEventHandle,
+        cuImportExternalMemory,
+        cuExternalMemoryGetMappedBuffer,
+        cuExternalMemoryGetMappedMipmappedArray,
+        cuDestroyExternalMemory,
+        cuImportExternalSemaphore,
+        cuSignalExternalSemaphoresAsync,
+        cuSignalExternalSemaphoresAsync_ptsz,
+        cuWaitExternalSemaphoresAsync,
+        cuWaitExternalSemaphoresAsync_ptsz,
+        cuDestroyExternalSemaphore,
         cuOccupancyMaxActiveBlocksPerMultiprocessorWithFlags,
         cuSurfObjectCreate,
         cuSurfObjectDestroy,
@@ -175,6 +243,7 @@ cuda_function_declarations!(
         cuTexRefSetAddressMode,
         cuTexRefSetAddress_v2,
         cuTexRefSetArray,
+        cuTexRefSetMipmappedArray,
         cuTexRefSetFilterMode,
         cuTexRefSetFlags,
         cuTexRefSetFormat,
@@ -194,15 +263,58 @@ cuda_function_declarations!(
         cuEventRecord,
         cuEventRecord_ptsz,
         cuEventSynchronize,
+        cuGraphAddChildGraphNode,
         cuGraphAddDependencies,
         cuGraphAddEmptyNode,
+        cuGraphAddEventRecordNode,
+        cuGraphAddEventWaitNode,
+        cuGraphAddHostNode,
         cuGraphAddKernelNode,
+        cuGraphAddMemAllocNode,
+        cuGraphAddMemFreeNode,
+        cuGraphAddMemcpyNode,
+        cuGraphAddMemsetNode,
+        cuGraphChildGraphNodeGetGraph,
+        cuGraphClone,
         cuGraphCreate,
+        cuGraphDebugDotPrint,
         cuGraphDestroy,
+        cuGraphDestroyNode,
+        cuGraphEventRecordNodeGetEvent,
+        cuGraphEventRecordNodeSetEvent,
+        cuGraphEventWaitNodeGetEvent,
+        cuGraphEventWaitNodeSetEvent,
+        cuGraphExecChildGraphNodeSetParams,
         cuGraphExecDestroy,
+        cuGraphExecEventRecordNodeSetEvent,
+        cuGraphExecEventWaitNodeSetEvent,
+        cuGraphExecHostNodeSetParams,
+        cuGraphExecKernelNodeSetParams,
+        cuGraphExecMemcpyNodeSetParams,
+        cuGraphExecMemsetNodeSetParams,
+        cuGraphExecUpdate,
+        cuGraphExecUpdate_v2,
+        cuGraphGetEdges,
+        cuGraphGetNodes,
+        cuGraphGetRootNodes,
+        cuGraphHostNodeGetParams,
+        cuGraphHostNodeSetParams,
         cuGraphInstantiate,
         cuGraphInstantiate_v2,
+        cuGraphKernelNodeGetParams,
+        cuGraphKernelNodeSetParams,
         cuGraphLaunch,
+        cuGraphMemAllocNodeGetParams,
+        cuGraphMemFreeNodeGetParams,
+        cuGraphMemcpyNodeGetParams,
+        cuGraphMemcpyNodeSetParams,
+        cuGraphMemsetNodeGetParams,
+        cuGraphMemsetNodeSetParams,
+        cuGraphNodeFindInClone,
+        cuGraphNodeGetDependencies,
+        cuGraphNodeGetDependentNodes,
+        cuGraphNodeGetType,
+        cuGraphRemoveDependencies,
         cuGraphicsSubResourceGetMappedArray,
         cuGraphicsGLRegisterBuffer,
         cuGraphicsGLRegisterImage,
@@ -211,6 +323,8 @@ cuda_function_declarations!(
         cuGraphicsUnmapResources,
         cuGraphicsUnregisterResource,
         cuLinkAddData_v2,
+        cuLinkAddFile,
+        cuLinkAddFile_v2,
         cuLinkComplete,
         cuLinkDestroy,
         cuLinkCreate_v2,
@@ -229,12 +343,15 @@ mod definitions {
     use crate::r#impl::context;
     use crate::r#impl::dark_api;
     use crate::r#impl::device;
+    use crate::r#impl::external;
     use crate::r#impl::function;
     use crate::r#impl::gl;
     use crate::r#impl::graph;
     use crate::r#impl::hipfix;
+    use crate::r#impl::ipc;
     use crate::r#impl::library;
     use crate::r#impl::link;
+    use crate::r#impl::mem_pool;
     use crate::r#impl::memcpy2d_from_cuda;
     use crate::r#impl::memory;
     use crate::r#impl::module;
@@ -244,6 +361,7 @@ mod definitions {
     use crate::r#impl::surfref;
     use crate::r#impl::texobj;
     use crate::r#impl::texref;
+    use crate::r#impl::vmm;
 
     pub(crate) unsafe fn cuGetErrorString(
         error: hipError_t,
@@ -314,6 +432,20 @@ mod definitions {
         hipDeviceGetMemPool(pool, dev)
     }
 
+    pub(crate) unsafe fn cuDeviceSetMemPool(
+        dev: hipDevice_t,
+        pool: hipMemPool_t,
+    ) -> Result<(), CUresult> {
+        mem_pool::device_set_mem_pool(dev, pool)
+    }
+
+    pub(crate) unsafe fn cuDeviceGetDefaultMemPool(
+        pool_out: *mut hipMemPool_t,
+        dev: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        mem_pool::device_get_default_mem_pool(pool_out, dev)
+    }
+
     pub(crate) unsafe fn cuDeviceGetName(
         name: *mut ::std::os::raw::c_char,
         len: ::std::os::raw::c_int,
@@ -513,6 +645,19 @@ mod definitions {
         context::get_api_version(ctx, version)
     }
 
+    pub(crate) unsafe fn cuCtxEnablePeerAccess(
+        peerContext: *mut context::Context,
+        Flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        context::enable_peer_access(peerContext, Flags)
+    }
+
+    pub(crate) unsafe fn cuCtxDisablePeerAccess(
+        peerContext: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        context::disable_peer_access(peerContext)
+    }
+
     pub(crate) unsafe fn cuFuncSetCacheConfig(
         hfunc: *mut function::Function,
         config: hipFuncCache_t,
@@ -575,7 +720,7 @@ mod definitions {
         options: *mut CUjit_option,
         optionValues: *mut *mut ::std::os::raw::c_void,
     ) -> Result<(), CUresult> {
-        module::load_data(module, image)
+        module::load_data_ex(module, image, numOptions, options, optionValues)
     }
 
     pub(crate) unsafe fn cuModuleUnload(hmod: *mut module::Module) -> Result<(), CUresult> {
@@ -656,7 +801,14 @@ mod definitions {
         dptr: hipDeviceptr_t,
         hStream: *mut stream::Stream,
     ) -> Result<(), CUresult> {
-        memory::free_async(dptr, hStream)
+        mem_pool::free_async(dptr, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemFreeAsync_ptsz(
+        dptr: hipDeviceptr_t,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mem_pool::free_async(dptr, hStream, true)
     }
 
     pub(crate) unsafe fn cuMemFreeHost(p: *mut ::std::os::raw::c_void) -> hipError_t {
@@ -699,12 +851,88 @@ mod definitions {
         memory::get_address_range(pbase, psize, dptr)
     }
 
+    pub(crate) unsafe fn cuMemAllocAsync(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mem_pool::alloc_async(dptr, bytesize, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemAllocAsync_ptsz(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mem_pool::alloc_async(dptr, bytesize, hStream, true)
+    }
+
+    pub(crate) unsafe fn cuMemAllocFromPoolAsync(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        pool: hipMemPool_t,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mem_pool::alloc_from_pool_async(dptr, bytesize, pool, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemAllocFromPoolAsync_ptsz(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        pool: hipMemPool_t,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mem_pool::alloc_from_pool_async(dptr, bytesize, pool, hStream, true)
+    }
+
+    pub(crate) unsafe fn cuMemPoolCreate(
+        pool: *mut hipMemPool_t,
+        poolProps: *const CUmemPoolProps,
+    ) -> Result<(), CUresult> {
+        mem_pool::create(pool, poolProps)
+    }
+
+    pub(crate) unsafe fn cuMemPoolDestroy(pool: hipMemPool_t) -> Result<(), CUresult> {
+        mem_pool::destroy(pool)
+    }
+
+    pub(crate) unsafe fn cuMemPoolTrimTo(
+        pool: hipMemPool_t,
+        minBytesToKeep: usize,
+    ) -> Result<(), CUresult> {
+        mem_pool::trim_to(pool, minBytesToKeep)
+    }
+
+    pub(crate) unsafe fn cuMemPoolGetAttribute(
+        pool: hipMemPool_t,
+        attr: CUmemPool_attribute,
+        value: *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        mem_pool::get_attribute(pool, attr, value)
+    }
+
     pub(crate) unsafe fn cuMemPoolSetAttribute(
         pool: hipMemPool_t,
-        attr: hipMemPoolAttr,
+        attr: CUmemPool_attribute,
         value: *mut ::std::os::raw::c_void,
-    ) -> hipError_t {
-        hipMemPoolGetAttribute(pool, attr, value)
+    ) -> Result<(), CUresult> {
+        mem_pool::set_attribute(pool, attr, value)
+    }
+
+    pub(crate) unsafe fn cuMemPoolSetAccess(
+        pool: hipMemPool_t,
+        map: *const CUmemAccessDesc,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        mem_pool::set_access(pool, map, count)
+    }
+
+    pub(crate) unsafe fn cuMemPoolGetAccess(
+        flags: *mut CUmemAccess_flags,
+        memPool: hipMemPool_t,
+        location: *mut CUmemLocation,
+    ) -> Result<(), CUresult> {
+        mem_pool::get_access(flags, memPool, location)
     }
 
     pub(crate) unsafe fn cuMemPrefetchAsync(
@@ -716,6 +944,108 @@ mod definitions {
         memory::prefetch_async(devPtr, count, dev, hStream)
     }
 
+    pub(crate) unsafe fn cuMemCreate(
+        handle: *mut CUmemGenericAllocationHandle,
+        size: usize,
+        prop: *const CUmemAllocationProp,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::create(handle, size, prop, flags)
+    }
+
+    pub(crate) unsafe fn cuMemRelease(
+        handle: CUmemGenericAllocationHandle,
+    ) -> Result<(), CUresult> {
+        vmm::release(handle)
+    }
+
+    pub(crate) unsafe fn cuMemAddressReserve(
+        ptr: *mut hipDeviceptr_t,
+        size: usize,
+        alignment: usize,
+        addr: hipDeviceptr_t,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::address_reserve(ptr, size, alignment, addr, flags)
+    }
+
+    pub(crate) unsafe fn cuMemAddressFree(
+        ptr: hipDeviceptr_t,
+        size: usize,
+    ) -> Result<(), CUresult> {
+        vmm::address_free(ptr, size)
+    }
+
+    pub(crate) unsafe fn cuMemMap(
+        ptr: hipDeviceptr_t,
+        size: usize,
+        offset: usize,
+        handle: CUmemGenericAllocationHandle,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::map(ptr, size, offset, handle, flags)
+    }
+
+    pub(crate) unsafe fn cuMemUnmap(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+        vmm::unmap(ptr, size)
+    }
+
+    pub(crate) unsafe fn cuMemSetAccess(
+        ptr: hipDeviceptr_t,
+        size: usize,
+        desc: *const CUmemAccessDesc,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        vmm::set_access(ptr, size, desc, count)
+    }
+
+    pub(crate) unsafe fn cuMemGetAccess(
+        flags: *mut ::std::os::raw::c_ulonglong,
+        location: *const CUmemLocation,
+        ptr: hipDeviceptr_t,
+    ) -> Result<(), CUresult> {
+        vmm::get_access(flags, location, ptr)
+    }
+
+    pub(crate) unsafe fn cuMemGetAllocationGranularity(
+        granularity: *mut usize,
+        prop: *const CUmemAllocationProp,
+        option: CUmemAllocationGranularity_flags,
+    ) -> Result<(), CUresult> {
+        vmm::get_allocation_granularity(granularity, prop, option)
+    }
+
+    pub(crate) unsafe fn cuMemGetAllocationPropertiesFromHandle(
+        prop: *mut CUmemAllocationProp,
+        handle: CUmemGenericAllocationHandle,
+    ) -> Result<(), CUresult> {
+        vmm::get_allocation_properties_from_handle(prop, handle)
+    }
+
+    pub(crate) unsafe fn cuMemExportToShareableHandle(
+        shareableHandle: *mut ::std::os::raw::c_void,
+        handle: CUmemGenericAllocationHandle,
+        handleType: CUmemAllocationHandleType,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::export_to_shareable_handle(shareableHandle, handle, handleType, flags)
+    }
+
+    pub(crate) unsafe fn cuMemImportFromShareableHandle(
+        handle: *mut CUmemGenericAllocationHandle,
+        osHandle: *mut ::std::os::raw::c_void,
+        shHandleType: CUmemAllocationHandleType,
+    ) -> Result<(), CUresult> {
+        vmm::import_from_shareable_handle(handle, osHandle, shHandleType)
+    }
+
+    pub(crate) unsafe fn cuMemRetainAllocationHandle(
+        handle: *mut CUmemGenericAllocationHandle,
+        addr: *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        vmm::retain_allocation_handle(handle, addr)
+    }
+
     pub(crate) unsafe fn cuDeviceGetPCIBusId(
         pciBusId: *mut ::std::os::raw::c_char,
         len: ::std::os::raw::c_int,
@@ -758,6 +1088,29 @@ mod definitions {
         memory::copy_async(dst, src, ByteCount, hStream, true)
     }
 
+    pub(crate) unsafe fn cuMemcpyPeer(
+        dstDevice: hipDeviceptr_t,
+        dstContext: *mut context::Context,
+        srcDevice: hipDeviceptr_t,
+        srcContext: *mut context::Context,
+        ByteCount: usize,
+    ) -> Result<(), CUresult> {
+        memory::copy_peer(dstDevice, dstContext, srcDevice, srcContext, ByteCount)
+    }
+
+    pub(crate) unsafe fn cuMemcpyPeerAsync(
+        dstDevice: hipDeviceptr_t,
+        dstContext: *mut context::Context,
+        srcDevice: hipDeviceptr_t,
+        srcContext: *mut context::Context,
+        ByteCount: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        memory::copy_peer_async(
+            dstDevice, dstContext, srcDevice, srcContext, ByteCount, hStream,
+        )
+    }
+
     pub(crate) unsafe fn cuMemcpyHtoD_v2(
         dstDevice: hipDeviceptr_t,
         srcHost: *const ::std::os::raw::c_void,
@@ -888,6 +1241,17 @@ mod definitions {
         memory::copy3d_async(copy, hStream)
     }
 
+    pub(crate) unsafe fn cuMemcpy3DPeer(pCopy: *const CUDA_MEMCPY3D_PEER) -> Result<(), CUresult> {
+        memory::copy3d_peer(pCopy)
+    }
+
+    pub(crate) unsafe fn cuMemcpy3DPeerAsync(
+        pCopy: *const CUDA_MEMCPY3D_PEER,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        memory::copy3d_peer_async(pCopy, hStream)
+    }
+
     pub(crate) unsafe fn cuMemsetD8_v2(
         dstDevice: hipDeviceptr_t,
         uc: ::std::os::raw::c_uchar,
@@ -1023,6 +1387,26 @@ mod definitions {
         array::get_descriptor_3d(pArrayDescriptor, hArray)
     }
 
+    pub(crate) unsafe fn cuMipmappedArrayCreate(
+        pHandle: *mut CUmipmappedArray,
+        pMipmappedArrayDesc: *const HIP_ARRAY3D_DESCRIPTOR,
+        numMipmapLevels: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        array::mipmapped_create(pHandle, pMipmappedArrayDesc, numMipmapLevels)
+    }
+
+    pub(crate) unsafe fn cuMipmappedArrayDestroy(hMipmappedArray: CUmipmappedArray) -> hipError_t {
+        array::mipmapped_destroy(hMipmappedArray)
+    }
+
+    pub(crate) unsafe fn cuMipmappedArrayGetLevel(
+        pLevelArray: *mut CUarray,
+        hMipmappedArray: CUmipmappedArray,
+        level: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        array::mipmapped_get_level(pLevelArray, hMipmappedArray, level)
+    }
+
     pub(crate) unsafe fn cuPointerGetAttribute(
         data: *mut ::std::os::raw::c_void,
         attribute: hipPointer_attribute,
@@ -1040,6 +1424,54 @@ mod definitions {
         pointer::get_attributes(numAttributes, attributes, data, ptr)
     }
 
+    pub(crate) unsafe fn cuStreamBeginCapture(
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        stream::begin_capture(
+            hStream,
+            hipStreamCaptureMode::hipStreamCaptureModeGlobal,
+            false,
+        )
+    }
+
+    pub(crate) unsafe fn cuStreamBeginCapture_ptsz(
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        stream::begin_capture(
+            hStream,
+            hipStreamCaptureMode::hipStreamCaptureModeGlobal,
+            true,
+        )
+    }
+
+    pub(crate) unsafe fn cuStreamBeginCapture_v2(
+        hStream: *mut stream::Stream,
+        mode: hipStreamCaptureMode,
+    ) -> Result<(), CUresult> {
+        stream::begin_capture(hStream, mode, false)
+    }
+
+    pub(crate) unsafe fn cuStreamBeginCapture_v2_ptsz(
+        hStream: *mut stream::Stream,
+        mode: hipStreamCaptureMode,
+    ) -> Result<(), CUresult> {
+        stream::begin_capture(hStream, mode, true)
+    }
+
+    pub(crate) unsafe fn cuStreamEndCapture(
+        hStream: *mut stream::Stream,
+        phGraph: *mut hipGraph_t,
+    ) -> Result<(), CUresult> {
+        stream::end_capture(hStream, phGraph, false)
+    }
+
+    pub(crate) unsafe fn cuStreamEndCapture_ptsz(
+        hStream: *mut stream::Stream,
+        phGraph: *mut hipGraph_t,
+    ) -> Result<(), CUresult> {
+        stream::end_capture(hStream, phGraph, true)
+    }
+
     pub(crate) unsafe fn cuStreamCreate(
         phStream: *mut *mut stream::Stream,
         Flags: ::std::os::raw::c_uint,
@@ -1063,6 +1495,44 @@ mod definitions {
         stream::get_capture_info(stream, captureStatus_out, id_out)
     }
 
+    pub(crate) unsafe fn cuStreamGetCaptureInfo_v2(
+        hStream: *mut stream::Stream,
+        captureStatus_out: *mut hipStreamCaptureStatus,
+        id_out: *mut cuuint64_t,
+        graph_out: *mut hipGraph_t,
+        dependencies_out: *mut *const hipGraphNode_t,
+        numDependencies_out: *mut usize,
+    ) -> Result<(), CUresult> {
+        stream::get_capture_info_v2(
+            hStream,
+            captureStatus_out,
+            id_out,
+            graph_out,
+            dependencies_out,
+            numDependencies_out,
+            false,
+        )
+    }
+
+    pub(crate) unsafe fn cuStreamGetCaptureInfo_v2_ptsz(
+        hStream: *mut stream::Stream,
+        captureStatus_out: *mut hipStreamCaptureStatus,
+        id_out: *mut cuuint64_t,
+        graph_out: *mut hipGraph_t,
+        dependencies_out: *mut *const hipGraphNode_t,
+        numDependencies_out: *mut usize,
+    ) -> Result<(), CUresult> {
+        stream::get_capture_info_v2(
+            hStream,
+            captureStatus_out,
+            id_out,
+            graph_out,
+            dependencies_out,
+            numDependencies_out,
+            true,
+        )
+    }
+
     pub(crate) unsafe fn cuStreamGetCtx(
         hStream: *mut stream::Stream,
         pctx: *mut *mut context::Context,
@@ -1088,7 +1558,14 @@ mod definitions {
         hStream: *mut stream::Stream,
         captureStatus: *mut hipStreamCaptureStatus,
     ) -> Result<(), CUresult> {
-        stream::is_capturing(hStream, captureStatus)
+        stream::is_capturing(hStream, captureStatus, false)
+    }
+
+    pub(crate) unsafe fn cuStreamIsCapturing_ptsz(
+        hStream: *mut stream::Stream,
+        captureStatus: *mut hipStreamCaptureStatus,
+    ) -> Result<(), CUresult> {
+        stream::is_capturing(hStream, captureStatus, true)
     }
 
     pub(crate) unsafe fn cuStreamQuery(hStream: *mut stream::Stream) -> Result<(), CUresult> {
@@ -1129,6 +1606,30 @@ mod definitions {
         stream::wait_event(hStream, hEvent, Flags, true)
     }
 
+    pub(crate) unsafe fn cuStreamUpdateCaptureDependencies(
+        hStream: *mut stream::Stream,
+        dependencies: *mut hipGraphNode_t,
+        numDependencies: usize,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::update_capture_dependencies(hStream, dependencies, numDependencies, flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamUpdateCaptureDependencies_ptsz(
+        hStream: *mut stream::Stream,
+        dependencies: *mut hipGraphNode_t,
+        numDependencies: usize,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::update_capture_dependencies(hStream, dependencies, numDependencies, flags, true)
+    }
+
+    pub(crate) unsafe fn cuThreadExchangeStreamCaptureMode(
+        mode: *mut hipStreamCaptureMode,
+    ) -> Result<(), CUresult> {
+        stream::thread_exchange_capture_mode(mode)
+    }
+
     pub(crate) unsafe fn cuFuncGetAttribute(
         pi: *mut ::std::os::raw::c_int,
         attrib: hipFunction_attribute,
@@ -1211,41 +1712,240 @@ mod definitions {
         )
     }
 
-    pub(crate) unsafe fn cuMemHostGetDevicePointer_v2(
-        pdptr: *mut hipDeviceptr_t,
-        p: *mut ::std::os::raw::c_void,
-        Flags: ::std::os::raw::c_uint,
-    ) -> hipError_t {
-        memory::host_get_device_pointer(pdptr, p, Flags)
-    }
-
-    pub(crate) unsafe fn cuOccupancyMaxActiveBlocksPerMultiprocessorWithFlags(
-        num_blocks: *mut ::std::os::raw::c_int,
-        func: *mut function::Function,
-        block_size: ::std::os::raw::c_int,
-        dynamic_smem_size: usize,
-        flags: ::std::os::raw::c_uint,
+    pub(crate) unsafe fn cuLaunchKernelEx(
+        config: *const CUlaunchConfig,
+        f: *mut function::Function,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+        extra: *mut *mut ::std::os::raw::c_void,
     ) -> Result<(), CUresult> {
-        function::occupancy_max_potential_blocks_per_multiprocessor(
-            num_blocks,
-            func,
-            block_size,
-            dynamic_smem_size,
-            flags,
-        )
+        function::launch_kernel_ex(config, f, kernelParams, extra, false)
     }
 
-    pub(crate) unsafe fn cuSurfObjectCreate(
-        pSurfObject: *mut hipSurfaceObject_t,
-        pResDesc: *const CUDA_RESOURCE_DESC,
+    pub(crate) unsafe fn cuLaunchKernelEx_ptsz(
+        config: *const CUlaunchConfig,
+        f: *mut function::Function,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+        extra: *mut *mut ::std::os::raw::c_void,
     ) -> Result<(), CUresult> {
-        surface::create(pSurfObject, pResDesc)
-    }
-
-    pub(crate) unsafe fn cuSurfObjectDestroy(
-        surfObject: hipSurfaceObject_t,
-    ) -> hipError_t {
-        surface::destroy(surfObject)
+        function::launch_kernel_ex(config, f, kernelParams, extra, true)
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernel(
+        f: *mut function::Function,
+        gridDimX: ::std::os::raw::c_uint,
+        gridDimY: ::std::os::raw::c_uint,
+        gridDimZ: ::std::os::raw::c_uint,
+        blockDimX: ::std::os::raw::c_uint,
+        blockDimY: ::std::os::raw::c_uint,
+        blockDimZ: ::std::os::raw::c_uint,
+        sharedMemBytes: ::std::os::raw::c_uint,
+        hStream: *mut stream::Stream,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel(
+            f,
+            gridDimX,
+            gridDimY,
+            gridDimZ,
+            blockDimX,
+            blockDimY,
+            blockDimZ,
+            sharedMemBytes,
+            hStream,
+            kernelParams,
+            false,
+        )
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernel_ptsz(
+        f: *mut function::Function,
+        gridDimX: ::std::os::raw::c_uint,
+        gridDimY: ::std::os::raw::c_uint,
+        gridDimZ: ::std::os::raw::c_uint,
+        blockDimX: ::std::os::raw::c_uint,
+        blockDimY: ::std::os::raw::c_uint,
+        blockDimZ: ::std::os::raw::c_uint,
+        sharedMemBytes: ::std::os::raw::c_uint,
+        hStream: *mut stream::Stream,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel(
+            f,
+            gridDimX,
+            gridDimY,
+            gridDimZ,
+            blockDimX,
+            blockDimY,
+            blockDimZ,
+            sharedMemBytes,
+            hStream,
+            kernelParams,
+            true,
+        )
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernelMultiDevice(
+        launchParamsList: *mut CUDA_LAUNCH_PARAMS,
+        numDevices: ::std::os::raw::c_uint,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel_multi_device(launchParamsList, numDevices, flags)
+    }
+
+    pub(crate) unsafe fn cuIpcGetMemHandle(
+        pHandle: *mut CUipcMemHandle,
+        dptr: hipDeviceptr_t,
+    ) -> Result<(), CUresult> {
+        ipc::get_mem_handle(pHandle, dptr)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenMemHandle(
+        pdptr: *mut hipDeviceptr_t,
+        handle: CUipcMemHandle,
+        Flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        ipc::open_mem_handle(pdptr, handle, Flags)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenMemHandle_v2(
+        pdptr: *mut hipDeviceptr_t,
+        handle: CUipcMemHandle,
+        Flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        ipc::open_mem_handle(pdptr, handle, Flags)
+    }
+
+    pub(crate) unsafe fn cuIpcCloseMemHandle(dptr: hipDeviceptr_t) -> Result<(), CUresult> {
+        ipc::close_mem_handle(dptr)
+    }
+
+    pub(crate) unsafe fn cuIpcGetEventHandle(
+        pHandle: *mut CUipcEventHandle,
+        event: hipEvent_t,
+    ) -> Result<(), CUresult> {
+        ipc::get_event_handle(pHandle, event)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenEventHandle(
+        phEvent: *mut hipEvent_t,
+        handle: CUipcEventHandle,
+    ) -> Result<(), CUresult> {
+        ipc::open_event_handle(phEvent, handle)
+    }
+
+    pub(crate) unsafe fn cuImportExternalMemory(
+        extMem_out: *mut hipExternalMemory_t,
+        memHandleDesc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+    ) -> Result<(), CUresult> {
+        external::import_memory(extMem_out, memHandleDesc)
+    }
+
+    pub(crate) unsafe fn cuExternalMemoryGetMappedBuffer(
+        devPtr: *mut hipDeviceptr_t,
+        extMem: hipExternalMemory_t,
+        bufferDesc: *const CUDA_EXTERNAL_MEMORY_BUFFER_DESC,
+    ) -> Result<(), CUresult> {
+        external::get_mapped_buffer(devPtr, extMem, bufferDesc)
+    }
+
+    pub(crate) unsafe fn cuExternalMemoryGetMappedMipmappedArray(
+        mipmap: *mut CUmipmappedArray,
+        extMem: hipExternalMemory_t,
+        mipmapDesc: *const CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC,
+    ) -> Result<(), CUresult> {
+        external::get_mapped_mipmapped_array(mipmap, extMem, mipmapDesc)
+    }
+
+    pub(crate) unsafe fn cuDestroyExternalMemory(
+        extMem: hipExternalMemory_t,
+    ) -> Result<(), CUresult> {
+        external::destroy_memory(extMem)
+    }
+
+    pub(crate) unsafe fn cuImportExternalSemaphore(
+        extSem_out: *mut hipExternalSemaphore_t,
+        semHandleDesc: *const CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC,
+    ) -> Result<(), CUresult> {
+        external::import_semaphore(extSem_out, semHandleDesc)
+    }
+
+    pub(crate) unsafe fn cuSignalExternalSemaphoresAsync(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::signal_semaphores_async(extSemArray, paramsArray, numExtSems, stream, false)
+    }
+
+    pub(crate) unsafe fn cuSignalExternalSemaphoresAsync_ptsz(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::signal_semaphores_async(extSemArray, paramsArray, numExtSems, stream, true)
+    }
+
+    pub(crate) unsafe fn cuWaitExternalSemaphoresAsync(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::wait_semaphores_async(extSemArray, paramsArray, numExtSems, stream, false)
+    }
+
+    pub(crate) unsafe fn cuWaitExternalSemaphoresAsync_ptsz(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::wait_semaphores_async(extSemArray, paramsArray, numExtSems, stream, true)
+    }
+
+    pub(crate) unsafe fn cuDestroyExternalSemaphore(
+        extSem: hipExternalSemaphore_t,
+    ) -> Result<(), CUresult> {
+        external::destroy_semaphore(extSem)
+    }
+
+    pub(crate) unsafe fn cuMemHostGetDevicePointer_v2(
+        pdptr: *mut hipDeviceptr_t,
+        p: *mut ::std::os::raw::c_void,
+        Flags: ::std::os::raw::c_uint,
+    ) -> hipError_t {
+        memory::host_get_device_pointer(pdptr, p, Flags)
+    }
+
+    pub(crate) unsafe fn cuOccupancyMaxActiveBlocksPerMultiprocessorWithFlags(
+        num_blocks: *mut ::std::os::raw::c_int,
+        func: *mut function::Function,
+        block_size: ::std::os::raw::c_int,
+        dynamic_smem_size: usize,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        function::occupancy_max_potential_blocks_per_multiprocessor(
+            num_blocks,
+            func,
+            block_size,
+            dynamic_smem_size,
+            flags,
+        )
+    }
+
+    pub(crate) unsafe fn cuSurfObjectCreate(
+        pSurfObject: *mut hipSurfaceObject_t,
+        pResDesc: *const CUDA_RESOURCE_DESC,
+    ) -> Result<(), CUresult> {
+        surface::create(pSurfObject, pResDesc)
+    }
+
+    pub(crate) unsafe fn cuSurfObjectDestroy(
+        surfObject: hipSurfaceObject_t,
+    ) -> hipError_t {
+        surface::destroy(surfObject)
     }
 
     pub(crate) unsafe fn cuTexObjectCreate(
@@ -1353,6 +2053,14 @@ mod definitions {
         texref::set_array(hTexRef, hArray, Flags)
     }
 
+    pub(crate) unsafe fn cuTexRefSetMipmappedArray(
+        hTexRef: *mut textureReference,
+        hMipmappedArray: CUmipmappedArray,
+        Flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        texref::set_mipmapped_array(hTexRef, hMipmappedArray, Flags)
+    }
+
     pub(crate) unsafe fn cuTexRefSetFilterMode(
         tex_ref: *mut textureReference,
         fm: hipTextureFilterMode,
@@ -1433,7 +2141,8 @@ mod definitions {
         phEvent: *mut hipEvent_t,
         Flags: ::std::os::raw::c_uint,
     ) -> hipError_t {
-        hipEventCreate(phEvent)
+        // CU_EVENT_* flags have the same values as hipEvent* flags
+        hipEventCreateWithFlags(phEvent, Flags)
     }
 
     pub(crate) unsafe fn cuEventDestroy(event: hipEvent_t) -> hipError_t {
@@ -1478,6 +2187,22 @@ mod definitions {
         hipEventSynchronize(event)
     }
 
+    pub(crate) unsafe fn cuGraphAddChildGraphNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        childGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphAddChildGraphNode(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            childGraph,
+        )
+    }
+
     pub(crate) unsafe fn cuGraphAddDependencies(
         graph: hipGraph_t,
         from: *const hipGraphNode_t,
@@ -1496,6 +2221,42 @@ mod definitions {
         hipGraphAddEmptyNode(pGraphNode, graph, pDependencies, numDependencies)
     }
 
+    pub(crate) unsafe fn cuGraphAddEventRecordNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphAddEventRecordNode(phGraphNode, hGraph, dependencies, numDependencies, event)
+    }
+
+    pub(crate) unsafe fn cuGraphAddEventWaitNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphAddEventWaitNode(phGraphNode, hGraph, dependencies, numDependencies, event)
+    }
+
+    pub(crate) unsafe fn cuGraphAddHostNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        nodeParams: *const hipHostNodeParams,
+    ) -> hipError_t {
+        hipGraphAddHostNode(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            nodeParams,
+        )
+    }
+
     pub(crate) unsafe fn cuGraphAddKernelNode(
         phGraphNode: *mut hipGraphNode_t,
         hGraph: hipGraph_t,
@@ -1512,6 +2273,82 @@ mod definitions {
         )
     }
 
+    pub(crate) unsafe fn cuGraphAddMemAllocNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        nodeParams: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::add_mem_alloc_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            nodeParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemFreeNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        dptr: hipDeviceptr_t,
+    ) -> hipError_t {
+        hipGraphAddMemFreeNode(phGraphNode, hGraph, dependencies, numDependencies, dptr.0)
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemcpyNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        copyParams: *const CUDA_MEMCPY3D,
+        ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::add_memcpy_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            copyParams,
+            ctx,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemsetNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+        ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::add_memset_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            memsetParams,
+            ctx,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphChildGraphNodeGetGraph(
+        hNode: hipGraphNode_t,
+        phGraph: *mut hipGraph_t,
+    ) -> hipError_t {
+        hipGraphChildGraphNodeGetGraph(hNode, phGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphClone(
+        phGraphClone: *mut hipGraph_t,
+        originalGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphClone(phGraphClone, originalGraph)
+    }
+
     pub(crate) unsafe fn cuGraphCreate(
         phGraph: *mut hipGraph_t,
         flags: ::std::os::raw::c_uint,
@@ -1519,14 +2356,168 @@ mod definitions {
         hipGraphCreate(phGraph, flags)
     }
 
+    pub(crate) unsafe fn cuGraphDebugDotPrint(
+        hGraph: hipGraph_t,
+        path: *const ::std::os::raw::c_char,
+        flags: ::std::os::raw::c_uint,
+    ) -> hipError_t {
+        hipGraphDebugDotPrint(hGraph, path, flags)
+    }
+
     pub(crate) unsafe fn cuGraphDestroy(graph: hipGraph_t) -> hipError_t {
         hipGraphDestroy(graph)
     }
 
+    pub(crate) unsafe fn cuGraphDestroyNode(hNode: hipGraphNode_t) -> hipError_t {
+        hipGraphDestroyNode(hNode)
+    }
+
+    pub(crate) unsafe fn cuGraphEventRecordNodeGetEvent(
+        hNode: hipGraphNode_t,
+        event_out: *mut hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventRecordNodeGetEvent(hNode, event_out)
+    }
+
+    pub(crate) unsafe fn cuGraphEventRecordNodeSetEvent(
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventRecordNodeSetEvent(hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphEventWaitNodeGetEvent(
+        hNode: hipGraphNode_t,
+        event_out: *mut hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventWaitNodeGetEvent(hNode, event_out)
+    }
+
+    pub(crate) unsafe fn cuGraphEventWaitNodeSetEvent(
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventWaitNodeSetEvent(hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphExecChildGraphNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        childGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphExecChildGraphNodeSetParams(hGraphExec, hNode, childGraph)
+    }
+
     pub(crate) unsafe fn cuGraphExecDestroy(graphExec: hipGraphExec_t) -> hipError_t {
         hipGraphExecDestroy(graphExec)
     }
 
+    pub(crate) unsafe fn cuGraphExecEventRecordNodeSetEvent(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphExecEventRecordNodeSetEvent(hGraphExec, hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphExecEventWaitNodeSetEvent(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphExecEventWaitNodeSetEvent(hGraphExec, hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphExecHostNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        nodeParams: *const hipHostNodeParams,
+    ) -> hipError_t {
+        hipGraphExecHostNodeSetParams(hGraphExec, hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecKernelNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::exec_kernel_node_set_params(hGraphExec, hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecMemcpyNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        copyParams: *const CUDA_MEMCPY3D,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::exec_memcpy_node_set_params(hGraphExec, hNode, copyParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecMemsetNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::exec_memset_node_set_params(hGraphExec, hNode, memsetParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecUpdate(
+        hGraphExec: hipGraphExec_t,
+        hGraph: hipGraph_t,
+        hErrorNode_out: *mut hipGraphNode_t,
+        updateResult_out: *mut hipGraphExecUpdateResult,
+    ) -> hipError_t {
+        hipGraphExecUpdate(hGraphExec, hGraph, hErrorNode_out, updateResult_out)
+    }
+
+    pub(crate) unsafe fn cuGraphExecUpdate_v2(
+        hGraphExec: hipGraphExec_t,
+        hGraph: hipGraph_t,
+        resultInfo: *mut CUgraphExecUpdateResultInfo,
+    ) -> Result<(), CUresult> {
+        graph::exec_update(hGraphExec, hGraph, resultInfo)
+    }
+
+    pub(crate) unsafe fn cuGraphGetEdges(
+        hGraph: hipGraph_t,
+        from: *mut hipGraphNode_t,
+        to: *mut hipGraphNode_t,
+        numEdges: *mut usize,
+    ) -> hipError_t {
+        hipGraphGetEdges(hGraph, from, to, numEdges)
+    }
+
+    pub(crate) unsafe fn cuGraphGetNodes(
+        hGraph: hipGraph_t,
+        nodes: *mut hipGraphNode_t,
+        numNodes: *mut usize,
+    ) -> hipError_t {
+        hipGraphGetNodes(hGraph, nodes, numNodes)
+    }
+
+    pub(crate) unsafe fn cuGraphGetRootNodes(
+        hGraph: hipGraph_t,
+        rootNodes: *mut hipGraphNode_t,
+        numRootNodes: *mut usize,
+    ) -> hipError_t {
+        hipGraphGetRootNodes(hGraph, rootNodes, numRootNodes)
+    }
+
+    pub(crate) unsafe fn cuGraphHostNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut hipHostNodeParams,
+    ) -> hipError_t {
+        hipGraphHostNodeGetParams(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphHostNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const hipHostNodeParams,
+    ) -> hipError_t {
+        hipGraphHostNodeSetParams(hNode, nodeParams)
+    }
+
     pub(crate) unsafe fn cuGraphInstantiate(
         phGraphExec: *mut hipGraphExec_t,
         hGraph: hipGraph_t,
@@ -1547,6 +2538,20 @@ mod definitions {
         cuGraphInstantiate(phGraphExec, hGraph, phErrorNode, logBuffer, bufferSize)
     }
 
+    pub(crate) unsafe fn cuGraphKernelNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphKernelNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_set_params(hNode, nodeParams)
+    }
+
     pub(crate) unsafe fn cuGraphLaunch(
         hGraph: hipGraphExec_t,
         hStream: *mut stream::Stream,
@@ -1554,6 +2559,88 @@ mod definitions {
         graph::launch(hGraph, hStream)
     }
 
+    pub(crate) unsafe fn cuGraphMemAllocNodeGetParams(
+        hNode: hipGraphNode_t,
+        params_out: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::mem_alloc_node_get_params(hNode, params_out)
+    }
+
+    pub(crate) unsafe fn cuGraphMemFreeNodeGetParams(
+        hNode: hipGraphNode_t,
+        dptr_out: *mut hipDeviceptr_t,
+    ) -> hipError_t {
+        hipGraphMemFreeNodeGetParams(hNode, dptr_out.cast())
+    }
+
+    pub(crate) unsafe fn cuGraphMemcpyNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_MEMCPY3D,
+    ) -> Result<(), CUresult> {
+        graph::memcpy_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphMemcpyNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_MEMCPY3D,
+    ) -> Result<(), CUresult> {
+        graph::memcpy_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphMemsetNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_MEMSET_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::memset_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphMemsetNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_MEMSET_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::memset_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeFindInClone(
+        phNode: *mut hipGraphNode_t,
+        hOriginalNode: hipGraphNode_t,
+        hClonedGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphNodeFindInClone(phNode, hOriginalNode, hClonedGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetDependencies(
+        hNode: hipGraphNode_t,
+        dependencies: *mut hipGraphNode_t,
+        numDependencies: *mut usize,
+    ) -> hipError_t {
+        hipGraphNodeGetDependencies(hNode, dependencies, numDependencies)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetDependentNodes(
+        hNode: hipGraphNode_t,
+        dependentNodes: *mut hipGraphNode_t,
+        numDependentNodes: *mut usize,
+    ) -> hipError_t {
+        hipGraphNodeGetDependentNodes(hNode, dependentNodes, numDependentNodes)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetType(
+        hNode: hipGraphNode_t,
+        type_: *mut CUgraphNodeType,
+    ) -> Result<(), CUresult> {
+        graph::node_get_type(hNode, type_)
+    }
+
+    pub(crate) unsafe fn cuGraphRemoveDependencies(
+        hGraph: hipGraph_t,
+        from: *const hipGraphNode_t,
+        to: *const hipGraphNode_t,
+        numDependencies: usize,
+    ) -> hipError_t {
+        hipGraphRemoveDependencies(hGraph, from, to, numDependencies)
+    }
+
     pub(crate) unsafe fn cuGraphicsSubResourceGetMappedArray(
         pArray: *mut CUarray,
         resource: hipGraphicsResource_t,
@@ -1632,6 +2719,28 @@ mod definitions {
         )
     }
 
+    pub(crate) unsafe fn cuLinkAddFile(
+        state: *mut link::LinkState,
+        type_: CUjitInputType,
+        path: *const ::std::os::raw::c_char,
+        numOptions: ::std::os::raw::c_uint,
+        options: *mut CUjit_option,
+        optionValues: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        link::add_file(state, type_, path, numOptions, options, optionValues)
+    }
+
+    pub(crate) unsafe fn cuLinkAddFile_v2(
+        state: *mut link::LinkState,
+        type_: CUjitInputType,
+        path: *const ::std::os::raw::c_char,
+        numOptions: ::std::os::raw::c_uint,
+        options: *mut CUjit_option,
+        optionValues: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        link::add_file(state, type_, path, numOptions, options, optionValues)
+    }
+
     pub(crate) unsafe fn cuLinkComplete(
         state: *mut link::LinkState,
         cubinOut: *mut *mut ::std::os::raw::c_void,
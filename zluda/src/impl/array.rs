@@ -21,22 +21,25 @@ pub(crate) unsafe fn create_3d(
         let mut hip_array = mem::zeroed();
         hip_call_cuda!(hipArray3DCreate(&mut hip_array, &mut desc as _));
         (&mut *hip_array).textureType = hack_flag;
-        let layered_dimensions = if desc.Flags & hipArrayLayered != 0 {
-            if desc.Height == 0 {
-                1usize
-            } else {
-                2
-            }
-        } else {
-            0
-        };
-        *array_ptr = hipfix::array::to_cuda(hip_array, layered_dimensions);
+        *array_ptr = hipfix::array::to_cuda(hip_array, layered_dimensions(desc.Flags, desc.Height));
         Ok(())
     } else {
         Err(CUresult::CUDA_ERROR_INVALID_VALUE)
     }
 }
 
+fn layered_dimensions(flags: u32, height: usize) -> usize {
+    if flags & hipArrayLayered != 0 {
+        if height == 0 {
+            1
+        } else {
+            2
+        }
+    } else {
+        0
+    }
+}
+
 pub(crate) unsafe fn get_descriptor_3d(
     array_descriptor: *mut CUDA_ARRAY3D_DESCRIPTOR,
     array: CUarray,
@@ -81,3 +84,57 @@ pub(crate) unsafe fn create(
         Err(CUresult::CUDA_ERROR_INVALID_VALUE)
     }
 }
+
+pub(crate) unsafe fn mipmapped_create(
+    handle: *mut CUmipmappedArray,
+    desc: *const HIP_ARRAY3D_DESCRIPTOR,
+    num_mipmap_levels: u32,
+) -> Result<(), CUresult> {
+    if let (Some(handle), Some(desc)) = (handle.as_mut(), desc.as_ref()) {
+        let mut desc = *desc;
+        let (hack_flag, format) = hipfix::get_non_broken_format(desc.Format);
+        desc.Format = format;
+        hipfix::array_3d_create(&mut desc);
+        let mut hip_array = ptr::null_mut();
+        hip_call_cuda!(hipMipmappedArrayCreate(
+            &mut hip_array,
+            &mut desc,
+            num_mipmap_levels
+        ));
+        *handle = hipfix::array::mipmapped_to_cuda(hip_array, hack_flag);
+        Ok(())
+    } else {
+        Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+    }
+}
+
+pub(crate) unsafe fn mipmapped_get_level(
+    level_array: *mut CUarray,
+    mipmapped_array: CUmipmappedArray,
+    level: u32,
+) -> Result<(), CUresult> {
+    let level_array = level_array
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let hack_flag = hipfix::array::get_mipmapped_hack_flag(mipmapped_array);
+    let mipmapped_array = hipfix::array::get_mipmapped(mipmapped_array);
+    let (flags, height) = match mipmapped_array.as_ref() {
+        Some(array) => (array.flags, array.height as usize),
+        None => return Err(CUresult::CUDA_ERROR_INVALID_HANDLE),
+    };
+    let mut hip_array = ptr::null_mut();
+    hip_call_cuda!(hipMipmappedArrayGetLevel(
+        &mut hip_array,
+        mipmapped_array,
+        level
+    ));
+    // Levels are plain hipArrays, carry the hack flag over so they can be
+    // bound and copied like any other array
+    (&mut *hip_array).textureType = hack_flag;
+    *level_array = hipfix::array::to_cuda(hip_array, layered_dimensions(flags, height));
+    Ok(())
+}
+
+pub(crate) unsafe fn mipmapped_destroy(mipmapped_array: CUmipmappedArray) -> hipError_t {
+    hipMipmappedArrayDestroy(hipfix::array::get_mipmapped(mipmapped_array))
+}
@@ -1,3 +1,140 @@
-mod cache_file;
-pub use cache_file::*;
+use hip_common::{
+    cache::{compute, CacheLayers},
+    CompilationMode,
+};
+use static_assertions::assert_impl_one;
+use std::{borrow::Cow, convert::TryInto, ffi::CStr};
 
+pub(crate) struct KernelCache(CacheLayers);
+assert_impl_one!(KernelCache: Sync);
+
+impl KernelCache {
+    // Read-only system layers from ZLUDA_CO_CACHE_SYSTEM_DIRS, then the
+    // per-user cache
+    pub(crate) fn new() -> Option<Self> {
+        Some(Self(CacheLayers::open(
+            compute::system_dirs(),
+            compute::default_dir(),
+        )?))
+    }
+
+    pub(crate) fn save_program(
+        &self,
+        compiler_version: &str,
+        device: &CStr,
+        ptx_modules: &[Cow<'_, str>],
+        compilation_mode: CompilationMode,
+        binary: &[u8],
+    ) {
+        let hash = compute::module_hash(ptx_modules.iter().map(|module| &**module));
+        self.save(
+            &hash,
+            compiler_version,
+            device,
+            binary,
+            &compute::module_parameters(compilation_mode),
+        );
+    }
+
+    pub(crate) fn try_load_program(
+        &self,
+        compiler_version: &str,
+        device: &CStr,
+        ptx_modules: &[Cow<'_, str>],
+        compilation_mode: CompilationMode,
+    ) -> Option<Vec<u8>> {
+        let hash = compute::module_hash(ptx_modules.iter().map(|module| &**module));
+        self.try_load(
+            &hash,
+            compiler_version,
+            device,
+            &compute::module_parameters(compilation_mode),
+        )
+    }
+
+    // Remembers which of the PTX modules of a multi-arch fatbin compiled,
+    // stored next to compiled programs as a 4 byte record
+    pub(crate) fn save_ptx_selection(
+        &self,
+        compiler_version: &str,
+        device: &CStr,
+        candidates: &[(Cow<'_, str>, u32)],
+        compilation_mode: CompilationMode,
+        sm_version: u32,
+    ) {
+        let hash = Self::hash_candidates(candidates);
+        self.save(
+            &hash,
+            compiler_version,
+            device,
+            &sm_version.to_le_bytes(),
+            &compute::ptx_selection_parameters(compilation_mode),
+        );
+    }
+
+    pub(crate) fn try_load_ptx_selection(
+        &self,
+        compiler_version: &str,
+        device: &CStr,
+        candidates: &[(Cow<'_, str>, u32)],
+        compilation_mode: CompilationMode,
+    ) -> Option<u32> {
+        let hash = Self::hash_candidates(candidates);
+        let record = self.try_load(
+            &hash,
+            compiler_version,
+            device,
+            &compute::ptx_selection_parameters(compilation_mode),
+        )?;
+        Some(u32::from_le_bytes(record.as_slice().try_into().ok()?))
+    }
+
+    fn save(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        device: &CStr,
+        binary: &[u8],
+        additional_parameters: &[u8],
+    ) {
+        let git_hash = env!("VERGEN_GIT_SHA");
+        self.0
+            .save_program(
+                hash,
+                compiler_version,
+                git_hash,
+                device,
+                binary,
+                additional_parameters,
+            )
+            .ok();
+    }
+
+    fn try_load(
+        &self,
+        hash: &str,
+        compiler_version: &str,
+        device: &CStr,
+        additional_parameters: &[u8],
+    ) -> Option<Vec<u8>> {
+        let git_hash = env!("VERGEN_GIT_SHA");
+        self.0
+            .try_load_program(
+                hash,
+                compiler_version,
+                git_hash,
+                device,
+                additional_parameters,
+            )
+            .ok()
+            .flatten()
+    }
+
+    fn hash_candidates(candidates: &[(Cow<'_, str>, u32)]) -> String {
+        compute::ptx_selection_hash(
+            candidates
+                .iter()
+                .map(|(module, sm_version)| (&**module, *sm_version)),
+        )
+    }
+}
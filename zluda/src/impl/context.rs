@@ -1,7 +1,7 @@
 // HIP does not implement context APIs:
 // https://rocmdocs.amd.com/en/latest/Programming_Guides/HIP_API_Guide.html#hip-context-management-apis
 
-use super::{fold_cuda_errors, module, stream, LiveCheck, ZludaObject};
+use super::{fold_cuda_errors, module, stream, LiveCheck, ZludaObject, GLOBAL_STATE};
 use crate::hip_call_cuda;
 use cuda_types::*;
 use hip_runtime_sys::*;
@@ -166,6 +166,7 @@ pub(crate) unsafe fn destroy(ctx: *mut Context) -> Result<(), CUresult> {
         }
         Ok(())
     })?;
+    release_peer_access(ctx);
     LiveCheck::drop_box_with_result(ctx, false)
 }
 
@@ -292,6 +293,141 @@ unsafe fn push_context_stack(ctx: *mut Context) -> Result<(), CUresult> {
     Ok(())
 }
 
+// HIP tracks peer access per device, not per context. Every context that
+// enables access is recorded on its device, HIP access to a peer device is
+// enabled with the first such record and disabled when the last one is gone
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PeerAccess {
+    context: *mut Context,
+    peer_context: *mut Context,
+    peer_device: hipDevice_t,
+}
+
+pub(crate) unsafe fn enable_peer_access(
+    peer_ctx: *mut Context,
+    flags: u32,
+) -> Result<(), CUresult> {
+    if flags != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let peer_device = LiveCheck::as_result(peer_ctx)?.device;
+    let ctx = get_current_from_stack().ok_or(CUresult::CUDA_ERROR_INVALID_CONTEXT)?;
+    let device = LiveCheck::as_result(ctx)?.device;
+    let mut peer_access = GLOBAL_STATE
+        .get()?
+        .device(device)?
+        .peer_access
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let access = PeerAccess {
+        context: ctx,
+        peer_context: peer_ctx,
+        peer_device,
+    };
+    if peer_access.contains(&access) {
+        return Err(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED);
+    }
+    let mut can_access_peer = 0;
+    hip_call_cuda!(hipDeviceCanAccessPeer(
+        &mut can_access_peer,
+        device,
+        peer_device
+    ));
+    if can_access_peer == 0 {
+        return Err(CUresult::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED);
+    }
+    if !peer_access
+        .iter()
+        .any(|access| access.peer_device == peer_device)
+    {
+        let error = hipDeviceEnablePeerAccess(peer_device, 0);
+        if error != hipError_t::hipErrorPeerAccessAlreadyEnabled {
+            hip_call_cuda!(error);
+        }
+    }
+    peer_access.push(access);
+    Ok(())
+}
+
+pub(crate) unsafe fn disable_peer_access(peer_ctx: *mut Context) -> Result<(), CUresult> {
+    let peer_device = LiveCheck::as_result(peer_ctx)?.device;
+    let ctx = get_current_from_stack().ok_or(CUresult::CUDA_ERROR_INVALID_CONTEXT)?;
+    let device = LiveCheck::as_result(ctx)?.device;
+    let mut peer_access = GLOBAL_STATE
+        .get()?
+        .device(device)?
+        .peer_access
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let index = peer_access
+        .iter()
+        .position(|access| access.context == ctx && access.peer_context == peer_ctx)
+        .ok_or(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)?;
+    peer_access.swap_remove(index);
+    if !peer_access
+        .iter()
+        .any(|access| access.peer_device == peer_device)
+    {
+        let error = hipDeviceDisablePeerAccess(peer_device);
+        if error != hipError_t::hipErrorPeerAccessNotEnabled {
+            hip_call_cuda!(error);
+        }
+    }
+    Ok(())
+}
+
+// Forget peer access from and to a destroyed context, otherwise a context
+// later allocated at the same address would inherit it. Best-effort: failing
+// to disable HIP peer access must not prevent destroying the context
+unsafe fn release_peer_access(ctx: *mut Context) {
+    let global_state = match GLOBAL_STATE.get() {
+        Ok(global_state) => global_state,
+        Err(_) => return,
+    };
+    let mut disabled = Vec::new();
+    for (device, device_data) in global_state.devices.iter().enumerate() {
+        let mut peer_access = match device_data.peer_access.lock() {
+            Ok(peer_access) => peer_access,
+            Err(_) => continue,
+        };
+        let mut released = peer_access
+            .iter()
+            .filter(|access| access.context == ctx || access.peer_context == ctx)
+            .map(|access| access.peer_device)
+            .collect::<Vec<_>>();
+        if released.is_empty() {
+            continue;
+        }
+        released.sort_unstable();
+        released.dedup();
+        peer_access.retain(|access| access.context != ctx && access.peer_context != ctx);
+        for peer_device in released {
+            if peer_access
+                .iter()
+                .any(|access| access.peer_device == peer_device)
+            {
+                continue;
+            }
+            disabled.push((device as hipDevice_t, peer_device));
+        }
+    }
+    if disabled.is_empty() {
+        return;
+    }
+    // hipDeviceDisablePeerAccess applies to the current device, which has to
+    // be restored afterwards
+    let mut current_device = 0;
+    if hipGetDevice(&mut current_device) != hipError_t::hipSuccess {
+        return;
+    }
+    for (device, peer_device) in disabled {
+        if hipSetDevice(device) == hipError_t::hipSuccess {
+            let _ = hipDeviceDisablePeerAccess(peer_device);
+        }
+    }
+    let _ = hipSetDevice(current_device);
+}
+
 pub(crate) unsafe fn get_stream_priority_range(
     least_priority: *mut ::std::os::raw::c_int,
     greatest_priority: *mut ::std::os::raw::c_int,
@@ -13,6 +13,7 @@ use std::{
     mem,
     os::raw::{c_char, c_uint},
     ptr,ffi::CString,
+    sync::Mutex,
 };
 
 const ZLUDA_SUFFIX: &'static [u8] = b" [ZLUDA]\0";
@@ -27,6 +28,8 @@ pub(crate) struct Device {
     pub(crate) compilation_mode: CompilationMode,
     pub(crate) comgr_isa: CString,
     primary_context: context::Context,
+    // Peer access enabled by contexts on this device, see context::enable_peer_access
+    pub(crate) peer_access: Mutex<Vec<context::PeerAccess>>,
 }
 
 impl Device {
@@ -37,7 +40,7 @@ impl Device {
         let compilation_mode = if warp_size == 32 {
             CompilationMode::Wave32
         } else if warp_size == 64 {
-            get_wave64_mode()
+            CompilationMode::wave64()
         } else {
             return Err(CUresult::CUDA_ERROR_ILLEGAL_STATE);
         };
@@ -45,24 +48,11 @@ impl Device {
             compilation_mode,
             comgr_isa,
             primary_context: LiveCheck::new(ContextData::new_primary(index as i32)),
+            peer_access: Mutex::new(Vec::new()),
         })
     }
 }
 
-fn get_wave64_mode() -> CompilationMode {
-    match std::env::var("ZLUDA_WAVE64_SLOW_MODE") {
-        Ok(value) => {
-            if let Ok(value) = str::parse::<u32>(&value) {
-                if value != 0 {
-                    return CompilationMode::Wave32OnWave64;
-                }
-            }
-        }
-        Err(_) => {}
-    }
-    CompilationMode::DoubleWave32OnWave64
-}
-
 #[allow(warnings)]
 trait hipDeviceAttribute_t_ext {
     const hipDeviceAttributeMaximumTexture1DWidth: hipDeviceAttribute_t =
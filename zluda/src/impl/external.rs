@@ -0,0 +1,187 @@
+// External memory and semaphore interop. Only the POSIX file descriptor
+// handle types are supported, the same subset HIP implements on Linux
+use super::{hipfix, stream};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::ptr;
+
+pub(crate) unsafe fn import_memory(
+    ext_mem_out: *mut hipExternalMemory_t,
+    mem_handle_desc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+) -> Result<(), CUresult> {
+    if ext_mem_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = mem_handle_desc
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    if desc.type_ != CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    // CUDA_EXTERNAL_MEMORY_DEDICATED has the same value as
+    // hipExternalMemoryDedicated
+    let hip_desc = hipExternalMemoryHandleDesc {
+        type_: hipExternalMemoryHandleType::hipExternalMemoryHandleTypeOpaqueFd,
+        handle: hipExternalMemoryHandleDesc_st__bindgen_ty_1 { fd: desc.handle.fd },
+        size: desc.size,
+        flags: desc.flags,
+    };
+    hip_call_cuda!(hipImportExternalMemory(ext_mem_out, &hip_desc));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_mapped_buffer(
+    dptr: *mut hipDeviceptr_t,
+    ext_mem: hipExternalMemory_t,
+    buffer_desc: *const CUDA_EXTERNAL_MEMORY_BUFFER_DESC,
+) -> Result<(), CUresult> {
+    if dptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = buffer_desc
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    if desc.flags != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_desc = hipExternalMemoryBufferDesc {
+        offset: desc.offset,
+        size: desc.size,
+        flags: 0,
+    };
+    let mut dev_ptr = ptr::null_mut();
+    hip_call_cuda!(hipExternalMemoryGetMappedBuffer(
+        &mut dev_ptr,
+        ext_mem,
+        &hip_desc
+    ));
+    *dptr = hipDeviceptr_t(dev_ptr);
+    Ok(())
+}
+
+// HIP has no way to map external memory as an array
+pub(crate) unsafe fn get_mapped_mipmapped_array(
+    mipmap: *mut CUmipmappedArray,
+    _ext_mem: hipExternalMemory_t,
+    mipmap_desc: *const CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC,
+) -> Result<(), CUresult> {
+    if mipmap == ptr::null_mut() || mipmap_desc == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+}
+
+pub(crate) unsafe fn destroy_memory(ext_mem: hipExternalMemory_t) -> Result<(), CUresult> {
+    hip_call_cuda!(hipDestroyExternalMemory(ext_mem));
+    Ok(())
+}
+
+pub(crate) unsafe fn import_semaphore(
+    ext_sem_out: *mut hipExternalSemaphore_t,
+    sem_handle_desc: *const CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC,
+) -> Result<(), CUresult> {
+    if ext_sem_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = sem_handle_desc
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    // Timeline semaphores have no HIP equivalent
+    if desc.type_ != CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    let hip_desc = hipExternalSemaphoreHandleDesc {
+        type_: hipExternalSemaphoreHandleType::hipExternalSemaphoreHandleTypeOpaqueFd,
+        handle: hipExternalSemaphoreHandleDesc_st__bindgen_ty_1 { fd: desc.handle.fd },
+        flags: desc.flags,
+    };
+    hip_call_cuda!(hipImportExternalSemaphore(ext_sem_out, &hip_desc));
+    Ok(())
+}
+
+pub(crate) unsafe fn signal_semaphores_async(
+    ext_sem_array: *const hipExternalSemaphore_t,
+    params_array: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+    num_ext_sems: u32,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if num_ext_sems == 0 {
+        return Ok(());
+    }
+    if ext_sem_array == ptr::null() || params_array == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    // NvSciSync fences and the NvSciBuf flag have no meaning outside of
+    // NVIDIA's stack, only the fence value and keyed mutex key are carried over
+    let hip_params = std::slice::from_raw_parts(params_array, num_ext_sems as usize)
+        .iter()
+        .map(|params| hipExternalSemaphoreSignalParams {
+            params: hipExternalSemaphoreSignalParams_st__bindgen_ty_1 {
+                fence: hipExternalSemaphoreSignalParams_st__bindgen_ty_1__bindgen_ty_1 {
+                    value: params.params.fence.value,
+                },
+                keyedMutex: hipExternalSemaphoreSignalParams_st__bindgen_ty_1__bindgen_ty_2 {
+                    key: params.params.keyedMutex.key,
+                },
+                reserved: [0; 12],
+            },
+            flags: 0,
+            reserved: [0; 16],
+        })
+        .collect::<Vec<_>>();
+    hip_call_cuda!(hipSignalExternalSemaphoresAsync(
+        ext_sem_array,
+        hip_params.as_ptr(),
+        num_ext_sems,
+        hip_stream
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn wait_semaphores_async(
+    ext_sem_array: *const hipExternalSemaphore_t,
+    params_array: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+    num_ext_sems: u32,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if num_ext_sems == 0 {
+        return Ok(());
+    }
+    if ext_sem_array == ptr::null() || params_array == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let hip_params = std::slice::from_raw_parts(params_array, num_ext_sems as usize)
+        .iter()
+        .map(|params| hipExternalSemaphoreWaitParams {
+            params: hipExternalSemaphoreWaitParams_st__bindgen_ty_1 {
+                fence: hipExternalSemaphoreWaitParams_st__bindgen_ty_1__bindgen_ty_1 {
+                    value: params.params.fence.value,
+                },
+                keyedMutex: hipExternalSemaphoreWaitParams_st__bindgen_ty_1__bindgen_ty_2 {
+                    key: params.params.keyedMutex.key,
+                    timeoutMs: params.params.keyedMutex.timeoutMs,
+                },
+                reserved: [0; 10],
+            },
+            flags: 0,
+            reserved: [0; 16],
+        })
+        .collect::<Vec<_>>();
+    hip_call_cuda!(hipWaitExternalSemaphoresAsync(
+        ext_sem_array,
+        hip_params.as_ptr(),
+        num_ext_sems,
+        hip_stream
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn destroy_semaphore(ext_sem: hipExternalSemaphore_t) -> Result<(), CUresult> {
+    hip_call_cuda!(hipDestroyExternalSemaphore(ext_sem));
+    Ok(())
+}
@@ -3,7 +3,13 @@ use crate::{hip_call_cuda, r#impl::hipfix};
 use cuda_types::*;
 use hip_common::CompilationMode;
 use hip_runtime_sys::*;
-use std::{ffi::c_void, ptr};
+use lazy_static::lazy_static;
+use rustc_hash::FxHashMap;
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{Arc, Mutex},
+};
 
 const CU_LAUNCH_PARAM_BUFFER_POINTER: *mut c_void = 1 as *mut _;
 const CU_LAUNCH_PARAM_BUFFER_SIZE: *mut c_void = 2 as *mut _;
@@ -12,6 +18,39 @@ const HIP_LAUNCH_PARAM_END: *mut c_void = 3 as *mut _;
 
 pub(crate) type Function = LiveCheck<FunctionData>;
 
+lazy_static! {
+    // Some HIP APIs (e.g. graph kernel nodes) only give back hipFunction_t,
+    // this is how we get back to the ZLUDA function. Functions are registered
+    // on creation and removed when their module is unloaded
+    static ref HIP_FUNCTIONS: Mutex<FxHashMap<usize, usize>> = Mutex::new(FxHashMap::default());
+}
+
+pub(crate) fn register(func: &Function) -> Result<(), CUresult> {
+    let base = unsafe { func.as_ref_unchecked() }.base;
+    HIP_FUNCTIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .insert(base as usize, func as *const Function as usize);
+    Ok(())
+}
+
+pub(crate) fn unregister(base: hipFunction_t) -> Result<(), CUresult> {
+    HIP_FUNCTIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .remove(&(base as usize));
+    Ok(())
+}
+
+pub(crate) fn from_hip(base: hipFunction_t) -> Result<*mut Function, CUresult> {
+    HIP_FUNCTIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .get(&(base as usize))
+        .map(|func| *func as *mut Function)
+        .ok_or(CUresult::CUDA_ERROR_INVALID_HANDLE)
+}
+
 impl ZludaObject for FunctionData {
     #[cfg(target_pointer_width = "64")]
     const LIVENESS_COOKIE: usize = 0x86b7301e5869d145;
@@ -30,6 +69,53 @@ pub(crate) struct FunctionData {
     pub(crate) binary_version: u32,
     pub(crate) group_size: Option<(u32, u32)>,
     pub(crate) compilation_mode: CompilationMode,
+    pub(crate) grid_workspace: Arc<GridWorkspace>,
+}
+
+// Grid-wide barriers use a single workspace per module (see comgr's grid.ll),
+// so cooperative launches of kernels from the same module must not overlap,
+// even when they go to different streams. Each cooperative launch waits for
+// `last_launch` and records it again once enqueued. Launches into a stream
+// that is being captured are not ordered, the graph replays them on its own
+pub(crate) struct GridWorkspace {
+    last_launch: hipEvent_t,
+    launch_lock: Mutex<()>,
+}
+
+impl GridWorkspace {
+    pub(crate) unsafe fn new() -> Result<Self, CUresult> {
+        let mut last_launch = ptr::null_mut();
+        hip_call_cuda!(hipEventCreateWithFlags(
+            &mut last_launch,
+            hipEventDisableTiming
+        ));
+        Ok(Self {
+            last_launch,
+            launch_lock: Mutex::new(()),
+        })
+    }
+
+    pub(crate) unsafe fn destroy(&self) -> Result<(), CUresult> {
+        hip_call_cuda!(hipEventDestroy(self.last_launch));
+        Ok(())
+    }
+
+    // Returns false if the stream is being captured and the launch should not
+    // be recorded
+    unsafe fn wait(&self, stream: hipStream_t) -> Result<bool, CUresult> {
+        let mut capture_status = hipStreamCaptureStatus::hipStreamCaptureStatusNone;
+        hip_call_cuda!(hipStreamIsCapturing(stream, &mut capture_status));
+        if capture_status != hipStreamCaptureStatus::hipStreamCaptureStatusNone {
+            return Ok(false);
+        }
+        hip_call_cuda!(hipStreamWaitEvent(stream, self.last_launch, 0));
+        Ok(true)
+    }
+
+    unsafe fn record(&self, stream: hipStream_t) -> Result<(), CUresult> {
+        hip_call_cuda!(hipEventRecord(self.last_launch, stream));
+        Ok(())
+    }
 }
 
 pub(crate) unsafe fn launch_kernel(
@@ -97,6 +183,263 @@ pub(crate) unsafe fn launch_kernel(
     Ok(())
 }
 
+// Grid-wide barriers spin until every block arrives, so cooperative launches
+// go through hipModuleLaunchCooperativeKernel*, which guarantees that all
+// blocks are resident at the same time. The barrier workspace itself is a
+// module global, see GridWorkspace
+pub(crate) unsafe fn launch_cooperative_kernel(
+    f: *mut Function,
+    grid_dim_x: ::std::os::raw::c_uint,
+    grid_dim_y: ::std::os::raw::c_uint,
+    grid_dim_z: ::std::os::raw::c_uint,
+    block_dim_x: ::std::os::raw::c_uint,
+    block_dim_y: ::std::os::raw::c_uint,
+    mut block_dim_z: ::std::os::raw::c_uint,
+    shared_mem_bytes: ::std::os::raw::c_uint,
+    stream: *mut stream::Stream,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let function = LiveCheck::as_result(f)?;
+    hipfix::validate_block_size(function, block_dim_x, block_dim_y, block_dim_z)?;
+    validate_cooperative_grid(
+        function,
+        hip_stream,
+        [grid_dim_x, grid_dim_y, grid_dim_z],
+        [block_dim_x, block_dim_y, block_dim_z],
+        shared_mem_bytes,
+    )?;
+    if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+        block_dim_z *= 2;
+    }
+    let workspace = &*function.grid_workspace;
+    let _launch_guard = workspace
+        .launch_lock
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let ordered = workspace.wait(hip_stream)?;
+    hip_call_cuda!(hipModuleLaunchCooperativeKernel(
+        function.base,
+        grid_dim_x,
+        grid_dim_y,
+        grid_dim_z,
+        block_dim_x,
+        block_dim_y,
+        block_dim_z,
+        shared_mem_bytes,
+        hip_stream,
+        kernel_params,
+    ));
+    if ordered {
+        workspace.record(hip_stream)?;
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn launch_cooperative_kernel_multi_device(
+    launch_params_list: *mut CUDA_LAUNCH_PARAMS,
+    num_devices: u32,
+    flags: u32,
+) -> Result<(), CUresult> {
+    if launch_params_list == ptr::null_mut() || num_devices == 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let launches = std::slice::from_raw_parts(launch_params_list, num_devices as usize)
+        .iter()
+        .map(|params| {
+            let hip_stream = stream::as_hip_stream(params.hStream.cast())?;
+            let function = LiveCheck::as_result(params.function.cast::<Function>())?;
+            hipfix::validate_block_size(
+                function,
+                params.blockDimX,
+                params.blockDimY,
+                params.blockDimZ,
+            )?;
+            validate_cooperative_grid(
+                function,
+                hip_stream,
+                [params.gridDimX, params.gridDimY, params.gridDimZ],
+                [params.blockDimX, params.blockDimY, params.blockDimZ],
+                params.sharedMemBytes,
+            )?;
+            let mut block_dim_z = params.blockDimZ;
+            if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+                block_dim_z *= 2;
+            }
+            let hip_params = hipFunctionLaunchParams {
+                function: function.base,
+                gridDimX: params.gridDimX,
+                gridDimY: params.gridDimY,
+                gridDimZ: params.gridDimZ,
+                blockDimX: params.blockDimX,
+                blockDimY: params.blockDimY,
+                blockDimZ: block_dim_z,
+                sharedMemBytes: params.sharedMemBytes,
+                hStream: hip_stream,
+                kernelParams: params.kernelParams,
+            };
+            Ok((hip_params, &*function.grid_workspace))
+        })
+        .collect::<Result<Vec<_>, CUresult>>()?;
+    let (mut hip_params_list, workspaces): (Vec<_>, Vec<_>) = launches.into_iter().unzip();
+    // Lock every workspace once and always in the same order
+    let mut locked_workspaces = workspaces
+        .iter()
+        .map(|workspace| *workspace as *const GridWorkspace)
+        .collect::<Vec<_>>();
+    locked_workspaces.sort_unstable();
+    locked_workspaces.dedup();
+    let _launch_guards = locked_workspaces
+        .iter()
+        .map(|workspace| {
+            (**workspace)
+                .launch_lock
+                .lock()
+                .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let ordered = hip_params_list
+        .iter()
+        .zip(workspaces.iter())
+        .map(|(hip_params, workspace)| workspace.wait(hip_params.hStream))
+        .collect::<Result<Vec<_>, _>>()?;
+    // CUDA_COOPERATIVE_LAUNCH_MULTI_DEVICE_NO_*_LAUNCH_SYNC flags have the
+    // same values as hipCooperativeLaunchMultiDeviceNo*Sync
+    hip_call_cuda!(hipModuleLaunchCooperativeKernelMultiDevice(
+        hip_params_list.as_mut_ptr(),
+        num_devices,
+        flags
+    ));
+    for ((hip_params, workspace), ordered) in hip_params_list
+        .iter()
+        .zip(workspaces.iter())
+        .zip(ordered.into_iter())
+    {
+        if ordered {
+            workspace.record(hip_params.hStream)?;
+        }
+    }
+    Ok(())
+}
+
+// All blocks of a cooperative launch must be resident at the same time
+unsafe fn validate_cooperative_grid(
+    function: &FunctionData,
+    hip_stream: hipStream_t,
+    grid_dim: [u32; 3],
+    block_dim: [u32; 3],
+    shared_mem_bytes: u32,
+) -> Result<(), CUresult> {
+    let mut hip_block_size = (block_dim[0] * block_dim[1] * block_dim[2]) as i32;
+    if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+        hip_block_size *= 2;
+    }
+    let mut blocks_per_multiprocessor = 0;
+    hip_call_cuda!(hipModuleOccupancyMaxActiveBlocksPerMultiprocessor(
+        &mut blocks_per_multiprocessor,
+        function.base,
+        hip_block_size,
+        shared_mem_bytes as usize
+    ));
+    hipfix::occupancy_max_potential_blocks_per_multiprocessor(&mut blocks_per_multiprocessor);
+    let mut device = 0;
+    hip_call_cuda!(hipStreamGetDevice(hip_stream, &mut device));
+    let mut multiprocessor_count = 0;
+    hip_call_cuda!(hipDeviceGetAttribute(
+        &mut multiprocessor_count,
+        hipDeviceAttribute_t::hipDeviceAttributeMultiprocessorCount,
+        device
+    ));
+    let max_grid_size = blocks_per_multiprocessor * multiprocessor_count;
+    let grid_size = grid_dim[0] as u64 * grid_dim[1] as u64 * grid_dim[2] as u64;
+    if grid_size > max_grid_size as u64 {
+        return Err(CUresult::CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE);
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn launch_kernel_ex(
+    config: *const CUlaunchConfig,
+    f: *mut Function,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let config = config.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let attributes = if config.numAttrs == 0 {
+        &[][..]
+    } else if config.attrs == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    } else {
+        std::slice::from_raw_parts(config.attrs, config.numAttrs as usize)
+    };
+    let mut cooperative = false;
+    for attribute in attributes {
+        match attribute.id {
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_IGNORE => {}
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_COOPERATIVE => {
+                cooperative = attribute.value.cooperative != 0;
+            }
+            // HIP has no per-launch priorities and priority is only a
+            // scheduling hint, we just validate it
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PRIORITY => {
+                let mut least_priority = 0;
+                let mut greatest_priority = 0;
+                hip_call_cuda!(hipDeviceGetStreamPriorityRange(
+                    &mut least_priority,
+                    &mut greatest_priority
+                ));
+                let priority = attribute.value.priority;
+                if priority > least_priority || priority < greatest_priority {
+                    return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+                }
+            }
+            // There are no thread block clusters on AMD GPUs
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION => {
+                let cluster_dim = attribute.value.clusterDim;
+                if cluster_dim.x > 1 || cluster_dim.y > 1 || cluster_dim.z > 1 {
+                    return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+                }
+            }
+            _ => return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+        }
+    }
+    if cooperative {
+        if extra != ptr::null_mut() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        launch_cooperative_kernel(
+            f,
+            config.gridDimX,
+            config.gridDimY,
+            config.gridDimZ,
+            config.blockDimX,
+            config.blockDimY,
+            config.blockDimZ,
+            config.sharedMemBytes,
+            config.hStream.cast(),
+            kernel_params,
+            default_stream_per_thread,
+        )
+    } else {
+        launch_kernel(
+            f,
+            config.gridDimX,
+            config.gridDimY,
+            config.gridDimZ,
+            config.blockDimX,
+            config.blockDimY,
+            config.blockDimZ,
+            config.sharedMemBytes,
+            config.hStream.cast(),
+            kernel_params,
+            extra,
+            default_stream_per_thread,
+        )
+    }
+}
+
 pub(crate) unsafe fn occupancy_max_potential_block_size(
     min_grid_size: *mut i32,
     block_size: *mut i32,
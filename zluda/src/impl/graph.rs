@@ -1,7 +1,9 @@
-use super::{function, stream, LiveCheck};
+use super::{context, function, hipfix, mem_pool, stream, vmm, LiveCheck};
 use crate::hip_call_cuda;
 use cuda_types::*;
 use hip_runtime_sys::*;
+use static_assertions::assert_eq_size;
+use std::{ptr, slice};
 
 pub(crate) unsafe fn add_kernel_node(
     ph_graph_node: *mut hipGraphNode_t,
@@ -55,3 +57,483 @@ pub(crate) unsafe fn launch(
     hip_call_cuda!(hipGraphLaunch(graph, stream));
     Ok(())
 }
+
+pub(crate) unsafe fn kernel_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_params = std::mem::zeroed::<hipKernelNodeParams>();
+    hip_call_cuda!(hipGraphKernelNodeGetParams(node, &mut hip_params));
+    let func = function::from_hip(hip_params.func.cast())?;
+    *node_params = CUDA_KERNEL_NODE_PARAMS_v1 {
+        func: func.cast(),
+        gridDimX: hip_params.gridDim.x,
+        gridDimY: hip_params.gridDim.y,
+        gridDimZ: hip_params.gridDim.z,
+        blockDimX: hip_params.blockDim.x,
+        blockDimY: hip_params.blockDim.y,
+        blockDimZ: hip_params.blockDim.z,
+        sharedMemBytes: hip_params.sharedMemBytes,
+        kernelParams: hip_params.kernelParams,
+        extra: hip_params.extra,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let node_params = hip_node_params(node_params)?;
+    hip_call_cuda!(hipGraphKernelNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_kernel_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let node_params = hip_node_params(node_params)?;
+    hip_call_cuda!(hipGraphExecKernelNodeSetParams(
+        graph_exec,
+        node,
+        &node_params
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn add_mem_alloc_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    node_params: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let access_descs = if node_params.accessDescCount == 0 {
+        Vec::new()
+    } else {
+        if node_params.accessDescs == ptr::null() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        slice::from_raw_parts(node_params.accessDescs, node_params.accessDescCount)
+            .iter()
+            .map(vmm::access_desc_from_cuda)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let mut hip_params = hipMemAllocNodeParams {
+        poolProps: mem_pool::props_from_cuda(&node_params.poolProps)?,
+        accessDescs: access_descs.as_ptr(),
+        accessDescCount: access_descs.len(),
+        bytesize: node_params.bytesize,
+        dptr: ptr::null_mut(),
+    };
+    hip_call_cuda!(hipGraphAddMemAllocNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &mut hip_params,
+    ));
+    node_params.dptr = CUdeviceptr_v2(hip_params.dptr);
+    Ok(())
+}
+
+// Access descriptors returned by HIP are owned by the node, we hand them out
+// as they are. Location types and access flags we accept have the same values
+// in CUDA and HIP
+assert_eq_size!(hipMemAccessDesc, CUmemAccessDesc);
+
+pub(crate) unsafe fn mem_alloc_node_get_params(
+    node: hipGraphNode_t,
+    params_out: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let params_out = params_out
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_params = std::mem::zeroed::<hipMemAllocNodeParams>();
+    hip_call_cuda!(hipGraphMemAllocNodeGetParams(node, &mut hip_params));
+    *params_out = CUDA_MEM_ALLOC_NODE_PARAMS {
+        poolProps: mem_pool::props_to_cuda(&hip_params.poolProps),
+        accessDescs: hip_params.accessDescs.cast(),
+        accessDescCount: hip_params.accessDescCount,
+        bytesize: hip_params.bytesize,
+        dptr: CUdeviceptr_v2(hip_params.dptr),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn add_memcpy_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    copy_params: *const CUDA_MEMCPY3D,
+    ctx: *mut context::Context,
+) -> Result<(), CUresult> {
+    check_context(ctx)?;
+    let copy_params = copy_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let copy_params = hip_memcpy_params(copy_params)?;
+    hip_call_cuda!(hipGraphAddMemcpyNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &copy_params,
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn memcpy_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_params = std::mem::zeroed::<hipMemcpy3DParms>();
+    hip_call_cuda!(hipGraphMemcpyNodeGetParams(node, &mut hip_params));
+    *node_params = cuda_memcpy_params(&hip_params)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn memcpy_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let node_params = hip_memcpy_params(node_params)?;
+    hip_call_cuda!(hipGraphMemcpyNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_memcpy_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut node_params = hip_memcpy_params(node_params)?;
+    hip_call_cuda!(hipGraphExecMemcpyNodeSetParams(
+        graph_exec,
+        node,
+        &mut node_params
+    ));
+    Ok(())
+}
+
+// CUDA_MEMCPY3D is a driver API descriptor, but HIP graphs only accept
+// runtime API descriptors, where array offsets and widths are in elements
+unsafe fn hip_memcpy_params(cuda: &CUDA_MEMCPY3D) -> Result<hipMemcpy3DParms, CUresult> {
+    let (src_array, src_element_size, src_ptr) = memcpy_side(
+        cuda.srcMemoryType,
+        cuda.srcHost,
+        cuda.srcDevice,
+        cuda.srcArray,
+        cuda.srcPitch,
+        cuda.srcHeight,
+        cuda.WidthInBytes,
+    )?;
+    let (dst_array, dst_element_size, dst_ptr) = memcpy_side(
+        cuda.dstMemoryType,
+        cuda.dstHost,
+        cuda.dstDevice,
+        cuda.dstArray,
+        cuda.dstPitch,
+        cuda.dstHeight,
+        cuda.WidthInBytes,
+    )?;
+    let width_element_size = match (src_array, dst_array) {
+        (Some(_), _) => src_element_size,
+        (None, Some(_)) => dst_element_size,
+        (None, None) => 1,
+    };
+    Ok(hipMemcpy3DParms {
+        srcArray: src_array.unwrap_or(ptr::null_mut()),
+        srcPos: hipPos {
+            x: in_elements(cuda.srcXInBytes, src_element_size)?,
+            y: cuda.srcY,
+            z: cuda.srcZ,
+        },
+        srcPtr: src_ptr,
+        dstArray: dst_array.unwrap_or(ptr::null_mut()),
+        dstPos: hipPos {
+            x: in_elements(cuda.dstXInBytes, dst_element_size)?,
+            y: cuda.dstY,
+            z: cuda.dstZ,
+        },
+        dstPtr: dst_ptr,
+        extent: hipExtent {
+            width: in_elements(cuda.WidthInBytes, width_element_size)?,
+            height: cuda.Height,
+            depth: cuda.Depth,
+        },
+        kind: hipMemcpyKind::hipMemcpyDefault,
+    })
+}
+
+unsafe fn memcpy_side(
+    memory_type: CUmemorytype,
+    host: *const std::ffi::c_void,
+    device: CUdeviceptr,
+    array: CUarray,
+    pitch: usize,
+    height: usize,
+    width_in_bytes: usize,
+) -> Result<(Option<hipArray_t>, usize, hipPitchedPtr), CUresult> {
+    let pointer = match memory_type {
+        CUmemorytype::CU_MEMORYTYPE_HOST => host as *mut _,
+        CUmemorytype::CU_MEMORYTYPE_DEVICE | CUmemorytype::CU_MEMORYTYPE_UNIFIED => {
+            device.0 as *mut _
+        }
+        CUmemorytype::CU_MEMORYTYPE_ARRAY => {
+            // HIP 3D copies fail on 1D layered arrays. Outside of graphs
+            // hipfix::array::copy3d_async turns them into 2D copies, but graph
+            // memcpy nodes can only be built from 3D parameters
+            if hipfix::array::get_layered_dimensions(array) == 1 {
+                return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+            }
+            let array = hipfix::array::get(array);
+            let element_size = element_size(array)?;
+            return Ok((Some(array), element_size, std::mem::zeroed()));
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    Ok((
+        None,
+        1,
+        hipPitchedPtr {
+            ptr: pointer,
+            pitch,
+            xsize: width_in_bytes,
+            ysize: height,
+        },
+    ))
+}
+
+unsafe fn element_size(array: hipArray_t) -> Result<usize, CUresult> {
+    let array = array.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let channel_size = match array.Format {
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT8
+        | hipArray_Format::HIP_AD_FORMAT_SIGNED_INT8 => 1,
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT16
+        | hipArray_Format::HIP_AD_FORMAT_SIGNED_INT16
+        | hipArray_Format::HIP_AD_FORMAT_HALF => 2,
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT32
+        | hipArray_Format::HIP_AD_FORMAT_SIGNED_INT32
+        | hipArray_Format::HIP_AD_FORMAT_FLOAT => 4,
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    Ok(channel_size * array.NumChannels as usize)
+}
+
+fn in_elements(bytes: usize, element_size: usize) -> Result<usize, CUresult> {
+    if bytes % element_size != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    Ok(bytes / element_size)
+}
+
+// Original memory types are lost, pointers are reported as unified memory
+unsafe fn cuda_memcpy_params(hip: &hipMemcpy3DParms) -> Result<CUDA_MEMCPY3D, CUresult> {
+    let src_element_size = if hip.srcArray.is_null() {
+        1
+    } else {
+        element_size(hip.srcArray)?
+    };
+    let dst_element_size = if hip.dstArray.is_null() {
+        1
+    } else {
+        element_size(hip.dstArray)?
+    };
+    let width_element_size = if !hip.srcArray.is_null() {
+        src_element_size
+    } else {
+        dst_element_size
+    };
+    let memory_type = |array: hipArray_t| {
+        if array.is_null() {
+            CUmemorytype::CU_MEMORYTYPE_UNIFIED
+        } else {
+            CUmemorytype::CU_MEMORYTYPE_ARRAY
+        }
+    };
+    Ok(CUDA_MEMCPY3D {
+        srcXInBytes: hip.srcPos.x * src_element_size,
+        srcY: hip.srcPos.y,
+        srcZ: hip.srcPos.z,
+        srcLOD: 0,
+        srcMemoryType: memory_type(hip.srcArray),
+        srcHost: ptr::null(),
+        srcDevice: CUdeviceptr_v2(hip.srcPtr.ptr as _),
+        srcArray: hipfix::array::to_cuda(hip.srcArray, 0),
+        reserved0: ptr::null_mut(),
+        srcPitch: hip.srcPtr.pitch,
+        srcHeight: hip.srcPtr.ysize,
+        dstXInBytes: hip.dstPos.x * dst_element_size,
+        dstY: hip.dstPos.y,
+        dstZ: hip.dstPos.z,
+        dstLOD: 0,
+        dstMemoryType: memory_type(hip.dstArray),
+        dstHost: ptr::null_mut(),
+        dstDevice: CUdeviceptr_v2(hip.dstPtr.ptr as _),
+        dstArray: hipfix::array::to_cuda(hip.dstArray, 0),
+        reserved1: ptr::null_mut(),
+        dstPitch: hip.dstPtr.pitch,
+        dstHeight: hip.dstPtr.ysize,
+        WidthInBytes: hip.extent.width * width_element_size,
+        Height: hip.extent.height,
+        Depth: hip.extent.depth,
+    })
+}
+
+pub(crate) unsafe fn add_memset_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    memset_params: *const CUDA_MEMSET_NODE_PARAMS,
+    ctx: *mut context::Context,
+) -> Result<(), CUresult> {
+    check_context(ctx)?;
+    let memset_params = memset_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    hip_call_cuda!(hipGraphAddMemsetNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &hip_memset_params(memset_params),
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn memset_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_params = std::mem::zeroed::<hipMemsetParams>();
+    hip_call_cuda!(hipGraphMemsetNodeGetParams(node, &mut hip_params));
+    *node_params = CUDA_MEMSET_NODE_PARAMS {
+        dst: CUdeviceptr_v2(hip_params.dst as _),
+        pitch: hip_params.pitch,
+        value: hip_params.value,
+        elementSize: hip_params.elementSize,
+        width: hip_params.width,
+        height: hip_params.height,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn memset_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    hip_call_cuda!(hipGraphMemsetNodeSetParams(
+        node,
+        &hip_memset_params(node_params)
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_memset_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    hip_call_cuda!(hipGraphExecMemsetNodeSetParams(
+        graph_exec,
+        node,
+        &hip_memset_params(node_params)
+    ));
+    Ok(())
+}
+
+// HIP graph nodes run on the device of the stream the graph is launched on,
+// context is only checked
+unsafe fn check_context(ctx: *mut context::Context) -> Result<(), CUresult> {
+    if ctx != ptr::null_mut() {
+        LiveCheck::as_result(ctx)?;
+    }
+    Ok(())
+}
+
+fn hip_memset_params(cuda: &CUDA_MEMSET_NODE_PARAMS) -> hipMemsetParams {
+    hipMemsetParams {
+        dst: cuda.dst.0 as _,
+        elementSize: cuda.elementSize,
+        height: cuda.height,
+        pitch: cuda.pitch,
+        value: cuda.value,
+        width: cuda.width,
+    }
+}
+
+pub(crate) unsafe fn node_get_type(
+    node: hipGraphNode_t,
+    type_: *mut CUgraphNodeType,
+) -> Result<(), CUresult> {
+    let type_ = type_.as_mut().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_type = hipGraphNodeType::hipGraphNodeTypeCount;
+    hip_call_cuda!(hipGraphNodeGetType(node, &mut hip_type));
+    *type_ = match hip_type {
+        // CUDA has no separate symbol copies, they are plain memcpy nodes
+        hipGraphNodeType::hipGraphNodeTypeMemcpyFromSymbol
+        | hipGraphNodeType::hipGraphNodeTypeMemcpyToSymbol => {
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMCPY
+        }
+        // values are compatible
+        hipGraphNodeType(value) => CUgraphNodeType(value),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_update(
+    graph_exec: hipGraphExec_t,
+    graph: hipGraph_t,
+    result_info: *mut CUgraphExecUpdateResultInfo,
+) -> Result<(), CUresult> {
+    let result_info = result_info
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut error_node = ptr::null_mut();
+    let mut update_result = hipGraphExecUpdateResult::hipGraphExecUpdateError;
+    let error = hipGraphExecUpdate(graph_exec, graph, &mut error_node, &mut update_result);
+    // Result info is filled in also when the update fails
+    *result_info = CUgraphExecUpdateResultInfo {
+        // values are compatible
+        result: CUgraphExecUpdateResult(update_result.0),
+        errorNode: error_node.cast(),
+        errorFromNode: ptr::null_mut(),
+    };
+    hip_call_cuda!(error);
+    Ok(())
+}
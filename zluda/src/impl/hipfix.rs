@@ -59,6 +59,11 @@ pub(crate) mod array {
             let mut cuda = *cuda;
             cuda.res.array.hArray = mem::transmute(get(cuda.res.array.hArray));
             fn_((&cuda as *const CUDA_RESOURCE_DESC).cast::<HIP_RESOURCE_DESC>())
+        } else if cuda.resType == CUresourcetype::CU_RESOURCE_TYPE_MIPMAPPED_ARRAY {
+            let mut cuda = *cuda;
+            cuda.res.mipmap.hMipmappedArray =
+                mem::transmute(get_mipmapped(cuda.res.mipmap.hMipmappedArray));
+            fn_((&cuda as *const CUDA_RESOURCE_DESC).cast::<HIP_RESOURCE_DESC>())
         } else {
             fn_((cuda as *const CUDA_RESOURCE_DESC).cast::<HIP_RESOURCE_DESC>())
         }
@@ -77,6 +82,23 @@ pub(crate) mod array {
         cuda as usize & 3usize
     }
 
+    // hipMipmappedArray has no spare field like hipArray::textureType, so the
+    // format hack flag of a mipmapped array is kept in the low bits instead
+    pub(crate) fn get_mipmapped(cuda: CUmipmappedArray) -> hipMipmappedArray_t {
+        (cuda as usize & !3usize) as hipMipmappedArray_t
+    }
+
+    pub(crate) fn mipmapped_to_cuda(
+        array: hipMipmappedArray_t,
+        hack_flag: u32,
+    ) -> CUmipmappedArray {
+        ((array as usize) | hack_flag as usize) as CUmipmappedArray
+    }
+
+    pub(crate) fn get_mipmapped_hack_flag(cuda: CUmipmappedArray) -> u32 {
+        (cuda as usize & 3usize) as u32
+    }
+
     pub(crate) fn copy3d_async(
         stream: hipStream_t,
         copy_desc: &CUDA_MEMCPY3D,
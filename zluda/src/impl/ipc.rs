@@ -0,0 +1,79 @@
+// Inter-process memory and event handles. CUDA and HIP handles are both
+// 64 opaque bytes, we only copy them between the two types
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::ptr;
+
+const CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS: u32 = 1;
+
+pub(crate) unsafe fn get_mem_handle(
+    handle: *mut CUipcMemHandle,
+    dptr: hipDeviceptr_t,
+) -> Result<(), CUresult> {
+    let handle = handle.as_mut().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_handle = hipIpcMemHandle_t { reserved: [0; 64] };
+    hip_call_cuda!(hipIpcGetMemHandle(&mut hip_handle, dptr.0));
+    *handle = CUipcMemHandle {
+        reserved: hip_handle.reserved,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn open_mem_handle(
+    dptr: *mut hipDeviceptr_t,
+    handle: CUipcMemHandle,
+    flags: u32,
+) -> Result<(), CUresult> {
+    if dptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS is 1, hipIpcMemLazyEnablePeerAccess
+    // is 0 and HIP accepts no other flags
+    if flags & !CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_handle = hipIpcMemHandle_t {
+        reserved: handle.reserved,
+    };
+    let mut dev_ptr = ptr::null_mut();
+    hip_call_cuda!(hipIpcOpenMemHandle(
+        &mut dev_ptr,
+        hip_handle,
+        hipIpcMemLazyEnablePeerAccess
+    ));
+    *dptr = hipDeviceptr_t(dev_ptr);
+    Ok(())
+}
+
+pub(crate) unsafe fn close_mem_handle(dptr: hipDeviceptr_t) -> Result<(), CUresult> {
+    hip_call_cuda!(hipIpcCloseMemHandle(dptr.0));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_event_handle(
+    handle: *mut CUipcEventHandle,
+    event: hipEvent_t,
+) -> Result<(), CUresult> {
+    let handle = handle.as_mut().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_handle = hipIpcEventHandle_t { reserved: [0; 64] };
+    hip_call_cuda!(hipIpcGetEventHandle(&mut hip_handle, event));
+    *handle = CUipcEventHandle {
+        reserved: hip_handle.reserved,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn open_event_handle(
+    event: *mut hipEvent_t,
+    handle: CUipcEventHandle,
+) -> Result<(), CUresult> {
+    if event == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_handle = hipIpcEventHandle_t {
+        reserved: handle.reserved,
+    };
+    hip_call_cuda!(hipIpcOpenEventHandle(event, hip_handle));
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use cuda_types::*;
+use std::{
+    ffi::c_void,
+    os::raw::{c_char, c_uint},
+    ptr,
+    time::Duration,
+};
+
+// Options accepted by cuLinkCreate, cuLinkAddData and cuModuleLoadDataEx.
+// We only honor the options that report something back to the caller, all the
+// other ones tune NVIDIA compiler and have no equivalent in ZLUDA
+pub(crate) struct JitOptions {
+    info_log: Option<LogBuffer>,
+    error_log: Option<LogBuffer>,
+    // Output value is written in-place, into the option value array
+    wall_time: Option<*mut *mut c_void>,
+}
+
+impl JitOptions {
+    pub(crate) fn empty() -> Self {
+        Self {
+            info_log: None,
+            error_log: None,
+            wall_time: None,
+        }
+    }
+
+    pub(crate) unsafe fn new(
+        num_options: c_uint,
+        options: *mut CUjit_option,
+        option_values: *mut *mut c_void,
+    ) -> Result<Self, CUresult> {
+        let mut result = Self::empty();
+        if num_options == 0 {
+            return Ok(result);
+        }
+        if options == ptr::null_mut() || option_values == ptr::null_mut() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        let mut info_buffer = None;
+        let mut info_size = None;
+        let mut error_buffer = None;
+        let mut error_size = None;
+        for i in 0..num_options as usize {
+            let value = option_values.add(i);
+            match *options.add(i) {
+                CUjit_option::CU_JIT_INFO_LOG_BUFFER => info_buffer = Some((*value).cast()),
+                CUjit_option::CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES => info_size = Some(value),
+                CUjit_option::CU_JIT_ERROR_LOG_BUFFER => error_buffer = Some((*value).cast()),
+                CUjit_option::CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES => error_size = Some(value),
+                CUjit_option::CU_JIT_WALL_TIME => result.wall_time = Some(value),
+                _ => {}
+            }
+        }
+        result.info_log = LogBuffer::new(info_buffer, info_size);
+        result.error_log = LogBuffer::new(error_buffer, error_size);
+        Ok(result)
+    }
+
+    pub(crate) unsafe fn write_results(&self, log: &JitLog, wall_time: Duration) {
+        if let Some(ref info_log) = self.info_log {
+            info_log.write(&log.info);
+        }
+        if let Some(ref error_log) = self.error_log {
+            error_log.write(&log.errors);
+        }
+        if let Some(wall_time_out) = self.wall_time {
+            // Milliseconds, as a float stored directly in the option value
+            *wall_time_out.cast::<f32>() = wall_time.as_secs_f32() * 1000.0;
+        }
+    }
+}
+
+struct LogBuffer {
+    buffer: *mut c_char,
+    // In: size of the buffer, out: number of bytes written, both stored
+    // directly in the option value
+    size: *mut *mut c_void,
+    // Read once, linking writes the logs after every input and the output
+    // size must not shrink the buffer for the next write
+    capacity: usize,
+}
+
+impl LogBuffer {
+    unsafe fn new(buffer: Option<*mut c_char>, size: Option<*mut *mut c_void>) -> Option<Self> {
+        match (buffer, size) {
+            (Some(buffer), Some(size)) if buffer != ptr::null_mut() => Some(Self {
+                buffer,
+                size,
+                capacity: *size as usize as c_uint as usize,
+            }),
+            _ => None,
+        }
+    }
+
+    unsafe fn write(&self, messages: &[String]) {
+        let capacity = self.capacity;
+        if capacity == 0 {
+            return;
+        }
+        let text = messages.join("\n");
+        let length = usize::min(text.len(), capacity - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), self.buffer.cast::<u8>(), length);
+        *self.buffer.add(length) = 0;
+        *self.size = (length + 1) as *mut c_void;
+    }
+}
+
+// Messages produced while compiling a module, end up in the log buffers
+#[derive(Default)]
+pub(crate) struct JitLog {
+    info: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl JitLog {
+    pub(crate) fn info(&mut self, message: impl Into<String>) {
+        self.info.push(message.into());
+    }
+
+    pub(crate) fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+
+    pub(crate) fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub(crate) fn append(&mut self, other: JitLog) {
+        self.info.extend(other.info);
+        self.errors.extend(other.errors);
+    }
+}
@@ -5,10 +5,12 @@
 // TODO: make libraries lazy-loadable
 use super::{
     context, fold_cuda_errors,
+    jit::{JitLog, JitOptions},
     module::{self, ModuleData},
     LiveCheck, ZludaObject, GLOBAL_STATE,
 };
 use cuda_types::{CUjit_option, CUlibraryOption, CUresult};
+use std::time::Instant;
 
 pub(crate) type Library = LiveCheck<LibraryData>;
 
@@ -35,10 +37,9 @@ pub(crate) struct LibraryData {
 pub(crate) unsafe fn load_data(
     library: *mut *mut Library,
     code: *const ::std::os::raw::c_void,
-    // TODO: start handling JIT options
-    _jit_options: *mut CUjit_option,
-    _jit_options_values: *mut *mut ::std::os::raw::c_void,
-    _num_jit_options: ::std::os::raw::c_uint,
+    jit_options: *mut CUjit_option,
+    jit_options_values: *mut *mut ::std::os::raw::c_void,
+    num_jit_options: ::std::os::raw::c_uint,
     library_options: *mut CUlibraryOption,
     _library_option_values: *mut *mut ::std::os::raw::c_void,
     num_library_options: ::std::os::raw::c_uint,
@@ -48,7 +49,10 @@ pub(crate) unsafe fn load_data(
             return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
         }
     }
+    let jit_options = JitOptions::new(num_jit_options, jit_options, jit_options_values)?;
     let global_state = GLOBAL_STATE.get()?;
+    let mut log = JitLog::default();
+    let start = Instant::now();
     let modules = global_state
         .devices
         .iter()
@@ -59,10 +63,13 @@ pub(crate) unsafe fn load_data(
                 &device.comgr_isa,
                 zluda_dark_api::CUmoduleContent::from_ptr(code.cast())
                     .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?,
+                &mut log,
             )?;
             Ok(ModuleData::alloc(module_data))
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Result<Vec<_>, _>>();
+    jit_options.write_results(&log, start.elapsed());
+    let modules = modules?;
     let library_data = LibraryData { modules };
     *library = Box::into_raw(Box::new(LiveCheck::new(library_data)));
     Ok(())
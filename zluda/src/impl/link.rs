@@ -1,6 +1,14 @@
+use super::jit::{JitLog, JitOptions};
 use super::{context, module, LiveCheck, ZludaObject, GLOBAL_STATE};
 use cuda_types::*;
-use std::{borrow::Cow, ptr, sync::Mutex};
+use std::{
+    borrow::Cow,
+    ffi::CStr,
+    ptr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use zluda_dark_api::{CUmoduleContent, CudaFatbin, PtxSelection, FATBIN_MAGIC};
 
 pub(crate) type LinkState = LiveCheck<LinkStateData>;
 
@@ -17,49 +25,168 @@ impl ZludaObject for LinkStateData {
 }
 
 pub(crate) struct LinkStateData {
+    // Option values passed to cuLinkCreate must stay valid until cuLinkDestroy,
+    // log buffers and wall time get written on every cuLinkAddData and cuLinkComplete
+    options: JitOptions,
     ptx_modules: Mutex<Vec<Cow<'static, str>>>,
+    // PTX of archive members, those are linked only when referenced
+    library_modules: Mutex<Vec<Cow<'static, str>>>,
+    log: Mutex<JitLog>,
 }
 
 pub(crate) unsafe fn add_data(
     state: *mut LinkState,
     type_: CUjitInputType,
     data: *mut ::std::os::raw::c_void,
-    mut size: usize,
-    _name: *const ::std::os::raw::c_char,
-    _num_options: ::std::os::raw::c_uint,
-    _options: *mut CUjit_option,
-    _option_values: *mut *mut ::std::os::raw::c_void,
+    size: usize,
+    name: *const ::std::os::raw::c_char,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
 ) -> Result<(), CUresult> {
     let state = LiveCheck::as_result(state)?;
+    // Per-input options only tune NVIDIA compiler, messages go to the log
+    // buffers passed to cuLinkCreate
+    JitOptions::new(num_options, options, option_values)?;
+    if data == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let selection = GLOBAL_STATE.get()?.ptx_selection;
+    let name = input_name(name);
+    let input = match type_ {
+        CUjitInputType::CU_JIT_INPUT_FATBINARY => match CUmoduleContent::from_ptr(data.cast()) {
+            Ok(CUmoduleContent::Fatbin(fatbin)) => module::extract_fatbin_ptx(fatbin, selection),
+            Ok(CUmoduleContent::Elf(elf)) => {
+                module::extract_host_object_ptx(hip_common::elf::as_slice(elf), selection)
+            }
+            _ => Err(CUresult::CUDA_ERROR_INVALID_IMAGE),
+        },
+        _ => {
+            let data = std::slice::from_raw_parts(data.cast::<u8>(), size);
+            extract_input(type_, data, selection)
+        }
+    };
+    push_input(state, type_, &name, input)
+}
+
+pub(crate) unsafe fn add_file(
+    state: *mut LinkState,
+    type_: CUjitInputType,
+    path: *const ::std::os::raw::c_char,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
+) -> Result<(), CUresult> {
+    let state = LiveCheck::as_result(state)?;
+    JitOptions::new(num_options, options, option_values)?;
+    if path == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let selection = GLOBAL_STATE.get()?.ptx_selection;
+    let name = input_name(path);
+    let input = CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)
+        .and_then(|path| std::fs::read(path).map_err(|_| CUresult::CUDA_ERROR_FILE_NOT_FOUND))
+        .and_then(|data| match type_ {
+            CUjitInputType::CU_JIT_INPUT_FATBINARY => {
+                // Only bare fatbins can be stored in a file, wrappers contain pointers
+                if data.get(..4) != Some(&FATBIN_MAGIC.to_le_bytes()[..]) {
+                    return Err(CUresult::CUDA_ERROR_INVALID_IMAGE);
+                }
+                // Fatbin headers must be 8-byte aligned
+                let mut aligned = vec![0u64; (data.len() + 7) / 8];
+                ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    aligned.as_mut_ptr().cast::<u8>(),
+                    data.len(),
+                );
+                module::extract_fatbin_ptx(
+                    CudaFatbin::from_header(aligned.as_ptr().cast()),
+                    selection,
+                )
+            }
+            _ => extract_input(type_, &data, selection),
+        });
+    push_input(state, type_, &name, input)
+}
+
+unsafe fn input_name(name: *const ::std::os::raw::c_char) -> String {
+    if name == ptr::null() {
+        "<unnamed>".to_string()
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    }
+}
+
+fn extract_input(
+    type_: CUjitInputType,
+    data: &[u8],
+    selection: PtxSelection,
+) -> Result<Vec<Cow<'static, str>>, CUresult> {
     match type_ {
         CUjitInputType::CU_JIT_INPUT_PTX => {
-            let data = data.cast::<u8>();
-            loop {
-                if *data.add(size - 1) == 0 {
-                    size -= 1;
-                } else {
-                    break;
-                }
+            let mut size = data.len();
+            while size > 0 && data[size - 1] == 0 {
+                size -= 1;
             }
-            let buffer = std::slice::from_raw_parts(data.cast::<u8>(), size);
-            let buffer =
-                std::str::from_utf8(buffer).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
-            let ptx = buffer.to_string();
-            let mut modules = state
-                .ptx_modules
-                .lock()
-                .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
-            modules.push(Cow::Owned(ptx));
-            Ok(())
+            let ptx = std::str::from_utf8(&data[..size])
+                .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
+            Ok(vec![Cow::Owned(ptx.to_string())])
         }
-        // Right now only user of this data type is
-        // V-Ray, which passes CUDA Runtime archive
-        // that is not used anyway
-        CUjitInputType::CU_JIT_INPUT_LIBRARY => Ok(()),
+        // Host object or cubin, only fatbins embedded in host objects carry PTX
+        CUjitInputType::CU_JIT_INPUT_OBJECT | CUjitInputType::CU_JIT_INPUT_CUBIN => {
+            module::extract_host_object_ptx(data, selection)
+        }
+        CUjitInputType::CU_JIT_INPUT_LIBRARY => module::extract_archive_ptx(data, selection),
         _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
     }
 }
 
+fn push_input(
+    state: &LinkStateData,
+    type_: CUjitInputType,
+    name: &str,
+    input: Result<Vec<Cow<'static, str>>, CUresult>,
+) -> Result<(), CUresult> {
+    let mut log = state.log.lock().map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let result = match (type_, input) {
+        // V-Ray passes CUDA Runtime archive here, it's not referenced by V-Ray
+        // modules, so none of its members get selected during linking.
+        // Archives we can't make sense of (e.g. COFF objects) are ignored like before
+        (CUjitInputType::CU_JIT_INPUT_LIBRARY, Err(_)) => {
+            log.info(format!("{}: no device code found in library", name));
+            Ok(())
+        }
+        (CUjitInputType::CU_JIT_INPUT_LIBRARY, Ok(members)) => state
+            .library_modules
+            .lock()
+            .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)
+            .map(|mut library_modules| library_modules.extend(members)),
+        (_, Ok(modules)) if modules.is_empty() => {
+            log.error(format!(
+                "{}: input contains no PTX, ZLUDA can't use precompiled NVIDIA GPU code",
+                name
+            ));
+            Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+        }
+        (_, Ok(modules)) => state
+            .ptx_modules
+            .lock()
+            .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)
+            .map(|mut ptx_modules| ptx_modules.extend(modules)),
+        (_, Err(err)) => {
+            log.error(format!(
+                "{}: failed to load input of type {} ({})",
+                name, type_.0, err.0
+            ));
+            Err(err)
+        }
+    };
+    unsafe { state.options.write_results(&log, Duration::ZERO) };
+    result
+}
+
 pub(crate) unsafe fn complete(
     state: *mut LinkState,
     cubin_out: *mut *mut ::std::os::raw::c_void,
@@ -69,20 +196,31 @@ pub(crate) unsafe fn complete(
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
     }
     let state = LiveCheck::as_result(state)?;
-    let modules = state
+    let mut modules = state
         .ptx_modules
         .lock()
-        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .clone();
+    let library_modules = state
+        .library_modules
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .clone();
+    modules.extend(module::select_archive_members(&modules, library_modules));
     let device = context::with_current(|ctx| ctx.device)?;
     let global_state = GLOBAL_STATE.get()?;
     let device_object = global_state.device(device)?;
+    let mut log = state.log.lock().map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let start = Instant::now();
     let module = module::link_build_zluda_module(
         global_state,
         device_object.compilation_mode,
         &device_object.comgr_isa,
         &modules,
-    )?;
-    let module = module.into_boxed_slice();
+        &mut log,
+    );
+    state.options.write_results(&log, start.elapsed());
+    let module = module?.into_boxed_slice();
     let size = module.len();
     let ptr = Box::into_raw(module);
     *size_out = size;
@@ -91,13 +229,19 @@ pub(crate) unsafe fn complete(
 }
 
 pub(crate) unsafe fn create(
-    _num_options: ::std::os::raw::c_uint,
-    _options: *mut CUjit_option,
-    _option_values: *mut *mut ::std::os::raw::c_void,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
     state_out: *mut *mut LinkState,
 ) -> Result<(), CUresult> {
+    if state_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
     let link_state = LinkState::new(LinkStateData {
+        options: JitOptions::new(num_options, options, option_values)?,
         ptx_modules: Mutex::new(Vec::new()),
+        library_modules: Mutex::new(Vec::new()),
+        log: Mutex::new(JitLog::default()),
     });
     let link_state = Box::into_raw(Box::new(link_state));
     *state_out = link_state;
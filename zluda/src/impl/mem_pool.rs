@@ -0,0 +1,243 @@
+// Stream-ordered allocator and memory pools
+use super::{hipfix, stream, vmm};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{ffi::c_void, ptr};
+
+pub(crate) unsafe fn alloc_async(
+    dptr: *mut hipDeviceptr_t,
+    bytesize: usize,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if dptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let mut dev_ptr = ptr::null_mut();
+    hip_call_cuda!(hipMallocAsync(&mut dev_ptr, bytesize, hip_stream));
+    *dptr = hipDeviceptr_t(dev_ptr);
+    Ok(())
+}
+
+pub(crate) unsafe fn alloc_from_pool_async(
+    dptr: *mut hipDeviceptr_t,
+    bytesize: usize,
+    pool: hipMemPool_t,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if dptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let mut dev_ptr = ptr::null_mut();
+    hip_call_cuda!(hipMallocFromPoolAsync(
+        &mut dev_ptr,
+        bytesize,
+        pool,
+        hip_stream
+    ));
+    *dptr = hipDeviceptr_t(dev_ptr);
+    Ok(())
+}
+
+pub(crate) unsafe fn free_async(
+    dptr: hipDeviceptr_t,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    hip_call_cuda!(hipFreeAsync(dptr.0, hip_stream));
+    Ok(())
+}
+
+pub(crate) unsafe fn create(
+    pool: *mut hipMemPool_t,
+    pool_props: *const CUmemPoolProps,
+) -> Result<(), CUresult> {
+    if pool == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let pool_props = pool_props
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let hip_props = props_from_cuda(pool_props)?;
+    hip_call_cuda!(hipMemPoolCreate(pool, &hip_props));
+    Ok(())
+}
+
+pub(crate) fn props_from_cuda(props: &CUmemPoolProps) -> Result<hipMemPoolProps, CUresult> {
+    if props.allocType != CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // HIP pools can't be capped, maxSize is only an upper bound so ignoring
+    // it is safe
+    Ok(hipMemPoolProps {
+        allocType: hipMemAllocationType::hipMemAllocationTypePinned,
+        handleTypes: vmm::handle_type_from_cuda(props.handleTypes)?,
+        location: vmm::location_from_cuda(&props.location)?,
+        win32SecurityAttributes: props.win32SecurityAttributes,
+        reserved: [0; 64],
+    })
+}
+
+pub(crate) fn props_to_cuda(props: &hipMemPoolProps) -> CUmemPoolProps {
+    CUmemPoolProps {
+        allocType: CUmemAllocationType(props.allocType.0),
+        handleTypes: CUmemAllocationHandleType(props.handleTypes.0),
+        location: CUmemLocation {
+            type_: CUmemLocationType(props.location.type_.0),
+            id: props.location.id,
+        },
+        win32SecurityAttributes: props.win32SecurityAttributes,
+        maxSize: 0,
+        reserved: [0; 56],
+    }
+}
+
+pub(crate) unsafe fn destroy(pool: hipMemPool_t) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemPoolDestroy(pool));
+    Ok(())
+}
+
+pub(crate) unsafe fn trim_to(pool: hipMemPool_t, min_bytes_to_keep: usize) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemPoolTrimTo(pool, min_bytes_to_keep));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_attribute(
+    pool: hipMemPool_t,
+    attr: CUmemPool_attribute,
+    value: *mut c_void,
+) -> Result<(), CUresult> {
+    if value == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    hip_call_cuda!(hipMemPoolGetAttribute(
+        pool,
+        attribute_from_cuda(attr)?,
+        value
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn set_attribute(
+    pool: hipMemPool_t,
+    attr: CUmemPool_attribute,
+    value: *mut c_void,
+) -> Result<(), CUresult> {
+    if value == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    match attr {
+        // Current counters are read-only
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT
+        | CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT => {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+        }
+        // High watermarks can only be reset
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH
+        | CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH => {
+            if *value.cast::<u64>() != 0 {
+                return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+            }
+        }
+        _ => {}
+    }
+    hip_call_cuda!(hipMemPoolSetAttribute(
+        pool,
+        attribute_from_cuda(attr)?,
+        value
+    ));
+    Ok(())
+}
+
+// Values have the same types in CUDA and HIP: int for reuse policies and
+// 64 bit integers for the threshold and memory counters
+fn attribute_from_cuda(attr: CUmemPool_attribute) -> Result<hipMemPoolAttr, CUresult> {
+    Ok(match attr {
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_FOLLOW_EVENT_DEPENDENCIES => {
+            hipMemPoolAttr::hipMemPoolReuseFollowEventDependencies
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_OPPORTUNISTIC => {
+            hipMemPoolAttr::hipMemPoolReuseAllowOpportunistic
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_INTERNAL_DEPENDENCIES => {
+            hipMemPoolAttr::hipMemPoolReuseAllowInternalDependencies
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD => {
+            hipMemPoolAttr::hipMemPoolAttrReleaseThreshold
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT => {
+            hipMemPoolAttr::hipMemPoolAttrReservedMemCurrent
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH => {
+            hipMemPoolAttr::hipMemPoolAttrReservedMemHigh
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT => {
+            hipMemPoolAttr::hipMemPoolAttrUsedMemCurrent
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH => {
+            hipMemPoolAttr::hipMemPoolAttrUsedMemHigh
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    })
+}
+
+pub(crate) unsafe fn set_access(
+    pool: hipMemPool_t,
+    map: *const CUmemAccessDesc,
+    count: usize,
+) -> Result<(), CUresult> {
+    if map == ptr::null() && count != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let map = if count == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(map, count)
+            .iter()
+            .map(vmm::access_desc_from_cuda)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    hip_call_cuda!(hipMemPoolSetAccess(pool, map.as_ptr(), map.len()));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_access(
+    flags: *mut CUmemAccess_flags,
+    pool: hipMemPool_t,
+    location: *mut CUmemLocation,
+) -> Result<(), CUresult> {
+    let flags = flags.as_mut().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let location = location
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut location = vmm::location_from_cuda(location)?;
+    let mut hip_flags = hipMemAccessFlags::hipMemAccessFlagsProtNone;
+    hip_call_cuda!(hipMemPoolGetAccess(&mut hip_flags, pool, &mut location));
+    // values are compatible
+    *flags = CUmemAccess_flags(hip_flags.0);
+    Ok(())
+}
+
+pub(crate) unsafe fn device_set_mem_pool(
+    dev: hipDevice_t,
+    pool: hipMemPool_t,
+) -> Result<(), CUresult> {
+    hip_call_cuda!(hipDeviceSetMemPool(dev, pool));
+    Ok(())
+}
+
+pub(crate) unsafe fn device_get_default_mem_pool(
+    pool_out: *mut hipMemPool_t,
+    dev: hipDevice_t,
+) -> Result<(), CUresult> {
+    if pool_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    hip_call_cuda!(hipDeviceGetDefaultMemPool(pool_out, dev));
+    Ok(())
+}
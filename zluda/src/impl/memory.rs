@@ -1,5 +1,5 @@
 use super::stream::Stream;
-use super::{hipfix, stream};
+use super::{context, hipfix, stream, LiveCheck};
 use crate::hip_call_cuda;
 use crate::r#impl::{memcpy2d_from_cuda, GLOBAL_STATE};
 use cuda_types::*;
@@ -105,6 +105,86 @@ pub(crate) unsafe fn copy3d_async(
     }
 }
 
+pub(crate) unsafe fn copy_peer(
+    dst: hipDeviceptr_t,
+    dst_ctx: *mut context::Context,
+    src: hipDeviceptr_t,
+    src_ctx: *mut context::Context,
+    byte_count: usize,
+) -> Result<(), CUresult> {
+    let dst_device = LiveCheck::as_result(dst_ctx)?.device;
+    let src_device = LiveCheck::as_result(src_ctx)?.device;
+    hip_call_cuda!(hipMemcpyPeer(
+        dst.0, dst_device, src.0, src_device, byte_count
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn copy_peer_async(
+    dst: hipDeviceptr_t,
+    dst_ctx: *mut context::Context,
+    src: hipDeviceptr_t,
+    src_ctx: *mut context::Context,
+    byte_count: usize,
+    stream: *mut Stream,
+) -> Result<(), CUresult> {
+    let dst_device = LiveCheck::as_result(dst_ctx)?.device;
+    let src_device = LiveCheck::as_result(src_ctx)?.device;
+    let hip_stream = stream::as_hip_stream(stream)?;
+    hip_call_cuda!(hipMemcpyPeerAsync(
+        dst.0, dst_device, src.0, src_device, byte_count, hip_stream
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn copy3d_peer(copy: *const CUDA_MEMCPY3D_PEER) -> Result<(), CUresult> {
+    let copy = copy.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    hipfix::array::copy3d(&memcpy3d_from_peer(copy)?)
+}
+
+pub(crate) unsafe fn copy3d_peer_async(
+    copy: *const CUDA_MEMCPY3D_PEER,
+    stream: *mut Stream,
+) -> Result<(), CUresult> {
+    let copy = copy.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let hip_stream = stream::as_hip_stream(stream)?;
+    hipfix::array::copy3d_async(hip_stream, &memcpy3d_from_peer(copy)?)
+}
+
+// With unified addressing HIP copies between devices based on the pointers
+// alone, contexts are only validated
+unsafe fn memcpy3d_from_peer(copy: &CUDA_MEMCPY3D_PEER) -> Result<CUDA_MEMCPY3D, CUresult> {
+    LiveCheck::as_result(copy.srcContext.cast::<context::Context>())?;
+    LiveCheck::as_result(copy.dstContext.cast::<context::Context>())?;
+    Ok(CUDA_MEMCPY3D {
+        srcXInBytes: copy.srcXInBytes,
+        srcY: copy.srcY,
+        srcZ: copy.srcZ,
+        srcLOD: copy.srcLOD,
+        srcMemoryType: copy.srcMemoryType,
+        srcHost: copy.srcHost,
+        srcDevice: copy.srcDevice,
+        srcArray: copy.srcArray,
+        reserved0: ptr::null_mut(),
+        srcPitch: copy.srcPitch,
+        srcHeight: copy.srcHeight,
+        dstXInBytes: copy.dstXInBytes,
+        dstY: copy.dstY,
+        dstZ: copy.dstZ,
+        dstLOD: copy.dstLOD,
+        dstMemoryType: copy.dstMemoryType,
+        dstHost: copy.dstHost,
+        dstDevice: copy.dstDevice,
+        dstArray: copy.dstArray,
+        reserved1: ptr::null_mut(),
+        dstPitch: copy.dstPitch,
+        dstHeight: copy.dstHeight,
+        WidthInBytes: copy.WidthInBytes,
+        Height: copy.Height,
+        Depth: copy.Depth,
+    })
+}
+
 pub(crate) unsafe fn copy2d(copy: *const CUDA_MEMCPY2D) -> hipError_t {
     if let Some(copy) = copy.as_ref() {
         let copy = memcpy2d_from_cuda(copy);
@@ -186,15 +266,6 @@ pub(crate) unsafe fn copy_async(
     Ok(())
 }
 
-pub(crate) unsafe fn free_async(
-    dptr: hipDeviceptr_t,
-    stream: *mut stream::Stream,
-) -> Result<(), CUresult> {
-    let hip_stream = stream::as_hip_stream(stream)?;
-    hip_call_cuda! { hipFreeAsync(dptr.0, hip_stream) };
-    Ok(())
-}
-
 pub(crate) unsafe fn prefetch_async(
     dev_ptr: hipDeviceptr_t,
     count: usize,
@@ -6,7 +6,6 @@ use static_assertions::assert_impl_one;
 use std::{
     cell::Cell,
     ffi::{c_void, CStr},
-    fs,
     mem::{self, ManuallyDrop, MaybeUninit},
     ptr::{self, NonNull},
     sync::{atomic::AtomicI32, Once},
@@ -21,12 +20,16 @@ pub(crate) mod cache;
 pub(crate) mod context;
 pub(crate) mod dark_api;
 pub(crate) mod device;
+pub(crate) mod external;
 pub(crate) mod function;
 pub(crate) mod gl;
 pub(crate) mod graph;
 pub(crate) mod hipfix;
+pub(crate) mod ipc;
+pub(crate) mod jit;
 pub(crate) mod library;
 pub(crate) mod link;
+pub(crate) mod mem_pool;
 pub(crate) mod memory;
 pub(crate) mod module;
 #[cfg_attr(windows, path = "os_win.rs")]
@@ -38,6 +41,7 @@ pub(crate) mod surface;
 pub(crate) mod surfref;
 pub(crate) mod texobj;
 pub(crate) mod texref;
+pub(crate) mod vmm;
 
 #[cfg(debug_assertions)]
 pub(crate) fn unimplemented() -> cuda_types::CUresult {
@@ -85,6 +89,7 @@ pub(crate) struct GlobalState {
     pub(crate) comgr: Comgr,
     pub(crate) comgr_version: String,
     pub(crate) zero_buffers: bool,
+    pub(crate) ptx_selection: zluda_dark_api::PtxSelection,
 
     pub(crate) progressbar: Option<ProgressBarManager>,
 }
@@ -213,6 +218,19 @@ impl FromCuda<CUfunction_attribute> for CUfunction_attribute {}
 // Same layout, but if it's a an array resource it needs an adjustment in hipfix
 impl FromCuda<CUDA_MEMCPY2D> for CUDA_MEMCPY2D {}
 impl FromCuda<CUDA_MEMCPY3D> for CUDA_MEMCPY3D {}
+impl FromCuda<CUDA_MEMCPY3D_PEER> for CUDA_MEMCPY3D_PEER {}
+impl FromCuda<CUipcMemHandle> for CUipcMemHandle {}
+impl FromCuda<CUipcEventHandle> for CUipcEventHandle {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_HANDLE_DESC> for CUDA_EXTERNAL_MEMORY_HANDLE_DESC {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_BUFFER_DESC> for CUDA_EXTERNAL_MEMORY_BUFFER_DESC {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC>
+    for CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC
+{
+}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC> for CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC {}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS> for CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS {}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS> for CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS {}
+impl FromCuda<CUmipmappedArray> for CUmipmappedArray {}
 impl FromCuda<CUDA_ARRAY3D_DESCRIPTOR> for CUDA_ARRAY3D_DESCRIPTOR {}
 impl FromCuda<c_void> for c_void {}
 impl FromCuda<CUarray> for CUarray {}
@@ -222,6 +240,19 @@ impl FromCuda<CUdriverProcAddressQueryResult_enum> for CUdriverProcAddressQueryR
 impl FromCuda<CUmoduleLoadingMode> for CUmoduleLoadingMode {}
 impl FromCuda<CUlibraryOption> for CUlibraryOption {}
 impl FromCuda<CUDA_KERNEL_NODE_PARAMS_v1> for CUDA_KERNEL_NODE_PARAMS_v1 {}
+impl FromCuda<CUDA_MEMSET_NODE_PARAMS> for CUDA_MEMSET_NODE_PARAMS {}
+impl FromCuda<CUgraphNodeType> for CUgraphNodeType {}
+impl FromCuda<CUgraphExecUpdateResultInfo> for CUgraphExecUpdateResultInfo {}
+impl FromCuda<CUmemAllocationProp> for CUmemAllocationProp {}
+impl FromCuda<CUmemAccessDesc> for CUmemAccessDesc {}
+impl FromCuda<CUmemLocation> for CUmemLocation {}
+impl FromCuda<CUmemAllocationGranularity_flags> for CUmemAllocationGranularity_flags {}
+impl FromCuda<CUmemAllocationHandleType> for CUmemAllocationHandleType {}
+impl FromCuda<CUmemPoolProps> for CUmemPoolProps {}
+impl FromCuda<CUmemPool_attribute> for CUmemPool_attribute {}
+impl FromCuda<CUmemAccess_flags> for CUmemAccess_flags {}
+impl FromCuda<CUlaunchConfig> for CUlaunchConfig {}
+impl FromCuda<CUDA_LAUNCH_PARAMS> for CUDA_LAUNCH_PARAMS {}
 impl FromCuda<CUjitInputType> for CUjitInputType {}
 impl FromCuda<CUDA_RESOURCE_DESC> for CUDA_RESOURCE_DESC {}
 
@@ -236,12 +267,14 @@ impl FromCuda<CUlinkState> for *mut link::LinkState {}
 impl FromCuda<CUtexref> for *mut textureReference {}
 impl FromCuda<CUsurfref> for *mut textureReference {}
 impl FromCuda<CUevent> for hipEvent_t {}
+impl FromCuda<CUexternalMemory> for hipExternalMemory_t {}
+impl FromCuda<CUexternalSemaphore> for hipExternalSemaphore_t {}
 impl FromCuda<CUtexObject> for hipTextureObject_t {}
 impl FromCuda<CUmemoryPool> for hipMemPool_t {}
 // values are compatible
 impl FromCuda<CUstreamCaptureStatus> for hipStreamCaptureStatus {}
 // values are compatible
-impl FromCuda<CUmemPool_attribute> for hipMemPoolAttr {}
+impl FromCuda<CUstreamCaptureMode> for hipStreamCaptureMode {}
 // values are compatible
 impl FromCuda<CUpointer_attribute> for hipPointer_attribute {}
 impl FromCuda<CUfunction_attribute> for hipFunction_attribute {}
@@ -258,6 +291,11 @@ impl FromCuda<CUfunc_cache> for hipFuncCache_t {}
 impl FromCuda<CUgraph> for hipGraph_t {}
 impl FromCuda<CUgraphNode> for hipGraphNode_t {}
 impl FromCuda<CUgraphExec> for hipGraphExec_t {}
+// values are compatible
+impl FromCuda<CUgraphExecUpdateResult> for hipGraphExecUpdateResult {}
+// Same layout
+impl FromCuda<CUDA_HOST_NODE_PARAMS> for hipHostNodeParams {}
+impl FromCuda<CUDA_MEM_ALLOC_NODE_PARAMS> for CUDA_MEM_ALLOC_NODE_PARAMS {}
 impl FromCuda<CUgraphicsResource> for hipGraphicsResource_t {}
 impl FromCuda<CUlimit> for hipLimit_t {}
 impl FromCuda<CUsurfObject> for hipSurfaceObject_t {}
@@ -461,8 +499,9 @@ pub(crate) fn init(flags: u32) -> Result<(), CUresult> {
     if global_heap == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_OUT_OF_MEMORY);
     }
-    let kernel_cache = create_default_cache();
+    let kernel_cache = KernelCache::new();
     let zero_buffers = hipfix::should_zero_buffers().unwrap_or(false);
+    let ptx_selection = zluda_dark_api::PtxSelection::from_env();
 
     let progress_bar_manager = {
         if let Some(mut switch) = CommManagerSwitch::new() {
@@ -479,32 +518,13 @@ pub(crate) fn init(flags: u32) -> Result<(), CUresult> {
         comgr,
         comgr_version,
         zero_buffers,
+        ptx_selection,
 
         progressbar: progress_bar_manager,
     });
     Ok(())
 }
 
-fn create_default_cache() -> Option<KernelCache> {
-
-    let disk_cache_location = {
-        if let Some(co_cache_location_from_env) = std::env::var("ZLUDA_CO_CACHE_DIR").ok() {
-            std::path::PathBuf::from(co_cache_location_from_env)
-        } else if let Some(disk_cache_location) = std::env::var("ZLUDA_CACHE_DIR").ok() {
-            let mut disk_cache_location = std::path::PathBuf::from(disk_cache_location);
-            disk_cache_location.push("ComputeCache");
-            disk_cache_location
-        } else {
-            let mut disk_cache_location = dirs::cache_dir()?;
-            disk_cache_location.push("ZLUDA");
-            disk_cache_location.push("ComputeCache");
-            disk_cache_location
-        }
-    };
-
-    fs::create_dir_all(&disk_cache_location).ok()?;
-    KernelCache::new(&disk_cache_location)
-}
 
 pub(crate) static MAXIMUM_PROC_VERSION: AtomicI32 = AtomicI32::new(0);
 
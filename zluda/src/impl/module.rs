@@ -1,20 +1,23 @@
 use super::context::Context;
-use super::{context, function, LiveCheck, ZludaObject};
+use super::jit::{JitLog, JitOptions};
+use super::{context, fold_cuda_errors, function, LiveCheck, ZludaObject};
 use crate::hip_call_cuda;
 use crate::r#impl::function::FunctionData;
 use crate::r#impl::{comgr_error_to_cuda, device, hipfix, GLOBAL_STATE};
-use cuda_types::{CUmoduleLoadingMode, CUresult};
+use cuda_types::{CUjit_option, CUmoduleLoadingMode, CUresult};
+use hip_common::cache::compute;
 use hip_common::CompilationMode;
 use hip_runtime_sys::*;
-use ptx::ModuleParserExt;
-use rustc_hash::FxHashMap;
+use ptx::{ast, ModuleParserExt, PassInstrumentation};
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::borrow::Cow;
-use std::cmp;
 use std::collections::hash_map;
 use std::ffi::{CStr, CString};
+use std::path::PathBuf;
 use std::ptr::{self, NonNull};
-use std::sync::Mutex;
-use zluda_dark_api::{CUmoduleContent, FatbinFileKind};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use zluda_dark_api::{extract_ptx, CUmoduleContent, PtxSelection};
 
 const EMPTY_MODULE: &'static str = include_str!("empty_module.ptx");
 
@@ -41,9 +44,20 @@ impl ZludaObject for ModuleData {
         } else {
             Ok(())
         };
+        let unregistration_err = match self.functions.get_mut() {
+            Ok(functions) => fold_cuda_errors(
+                functions
+                    .values()
+                    .map(|func| function::unregister(unsafe { func.as_ref_unchecked() }.base)),
+            ),
+            Err(_) => Err(CUresult::CUDA_ERROR_UNKNOWN),
+        };
+        let workspace_err = unsafe { self.grid_workspace.destroy() };
         // Crashes HIP in 5.6 and 5.7.1
         //deregistration_err.and(unsafe { hipModuleUnload(self.base) }.into_cuda().into())
         deregistration_err
+            .and(unregistration_err)
+            .and(workspace_err)
     }
 }
 
@@ -56,6 +70,7 @@ pub(crate) struct ModuleData {
     device_version: u32,
     hipfix_max_group_sizes: FxHashMap<CString, (u32, u32)>,
     compilation_mode: CompilationMode,
+    grid_workspace: Arc<function::GridWorkspace>,
 }
 
 impl ModuleData {
@@ -84,9 +99,37 @@ pub(crate) unsafe fn load_data(
     )
 }
 
+pub(crate) unsafe fn load_data_ex(
+    module: *mut *mut Module,
+    image: *const ::std::os::raw::c_void,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
+) -> Result<(), CUresult> {
+    if image == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let options = JitOptions::new(num_options, options, option_values)?;
+    let input =
+        CUmoduleContent::from_ptr(image.cast()).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut log = JitLog::default();
+    let start = Instant::now();
+    let result = load_impl_with_log(module, input, &mut log);
+    options.write_results(&log, start.elapsed());
+    result
+}
+
 pub(crate) unsafe fn load_impl(
     output: *mut *mut Module,
     input: CUmoduleContent,
+) -> Result<(), CUresult> {
+    load_impl_with_log(output, input, &mut JitLog::default())
+}
+
+unsafe fn load_impl_with_log(
+    output: *mut *mut Module,
+    input: CUmoduleContent,
+    log: &mut JitLog,
 ) -> Result<(), CUresult> {
     if output == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
@@ -101,6 +144,7 @@ pub(crate) unsafe fn load_impl(
             device.compilation_mode,
             isa,
             input,
+            log,
         )?);
         ctx.with_inner_mut(|ctx_mutable| {
             ctx_mutable.modules.insert(module);
@@ -115,16 +159,30 @@ unsafe fn link_build_or_load_cuda_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     input: CUmoduleContent,
+    log: &mut JitLog,
 ) -> Result<Cow<'static, [u8]>, CUresult> {
     match input {
         CUmoduleContent::Elf(ptr) => Ok(Cow::Borrowed(hip_common::elf::as_slice(ptr))),
-        CUmoduleContent::Archive(..) => return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+        CUmoduleContent::Archive(archive) => {
+            let ptx_files = extract_archive_ptx(archive, global_state.ptx_selection)?;
+            if ptx_files.is_empty() {
+                return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+            }
+            link_build_zluda_module(global_state, compilation_mode, isa, &*ptx_files, log)
+                .map(Cow::Owned)
+        }
         CUmoduleContent::RawText(ptr) => {
             let ptx = CStr::from_ptr(ptr.cast())
                 .to_str()
                 .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
-            link_build_zluda_module(global_state, compilation_mode, isa, &[Cow::Borrowed(ptx)])
-                .map(Cow::Owned)
+            link_build_zluda_module(
+                global_state,
+                compilation_mode,
+                isa,
+                &[Cow::Borrowed(ptx)],
+                log,
+            )
+            .map(Cow::Owned)
         }
         CUmoduleContent::File(file) => {
             let name = CStr::from_ptr(file)
@@ -132,21 +190,25 @@ unsafe fn link_build_or_load_cuda_module(
                 .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
             let ptx =
                 std::fs::read_to_string(name).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
-            link_build_zluda_module(global_state, compilation_mode, isa, &[Cow::Owned(ptx)])
+            link_build_zluda_module(global_state, compilation_mode, isa, &[Cow::Owned(ptx)], log)
                 .map(Cow::Owned)
         }
         CUmoduleContent::Fatbin(files) => match files {
             zluda_dark_api::CudaFatbin::Version1(module) => {
-                link_build_or_load_fatbin_module(global_state, compilation_mode, isa, module)
+                link_build_or_load_fatbin_module(global_state, compilation_mode, isa, module, log)
                     .map(Cow::Owned)
             }
             zluda_dark_api::CudaFatbin::Version2 {
                 post_link,
                 pre_link,
             } => {
-                if let Ok(binary) =
-                    link_build_or_load_fatbin_module(global_state, compilation_mode, isa, post_link)
-                {
+                if let Ok(binary) = link_build_or_load_fatbin_module(
+                    global_state,
+                    compilation_mode,
+                    isa,
+                    post_link,
+                    log,
+                ) {
                     return Ok(Cow::Owned(binary));
                 }
                 let ptx_files = pre_link
@@ -159,16 +221,17 @@ unsafe fn link_build_or_load_cuda_module(
                                 return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
                             }
                             zluda_dark_api::FatbinModule::Files(files) => {
-                                let ptx_files = extract_ptx(files);
-                                if ptx_files.is_empty() {
-                                    return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+                                let ptx_files =
+                                    global_state.ptx_selection.select(extract_ptx(files));
+                                match ptx_files.into_iter().next() {
+                                    Some((ptx, _)) => Ok(ptx),
+                                    None => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
                                 }
-                                Ok(ptx_files.into_iter().next().unwrap().0)
                             }
                         }
                     })
                     .collect::<Result<Vec<_>, _>>()?;
-                link_build_zluda_module(global_state, compilation_mode, isa, &*ptx_files)
+                link_build_zluda_module(global_state, compilation_mode, isa, &*ptx_files, log)
                     .map(Cow::Owned)
             }
         },
@@ -180,6 +243,7 @@ fn link_build_or_load_fatbin_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     module: zluda_dark_api::FatbinModuleHandle,
+    log: &mut JitLog,
 ) -> Result<Vec<u8>, CUresult> {
     let module = unsafe { module.get() }.map_err(|_| CUresult::CUDA_ERROR_NOT_SUPPORTED)?;
     match module {
@@ -187,53 +251,251 @@ fn link_build_or_load_fatbin_module(
             return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
         }
         zluda_dark_api::FatbinModule::Files(files) => {
-            let ptx_files = extract_ptx(files);
-            for (ptx, _) in ptx_files {
-                if let Ok(binary) =
-                    link_build_zluda_module(global_state, compilation_mode, isa, &[ptx])
-                {
-                    return Ok(binary);
+            link_build_ptx_candidates(global_state, compilation_mode, isa, extract_ptx(files), log)
+        }
+    }
+}
+
+fn link_build_ptx_candidates(
+    global_state: &super::GlobalState,
+    compilation_mode: CompilationMode,
+    isa: &CStr,
+    ptx_files: Vec<(Cow<'static, str>, u32)>,
+    log: &mut JitLog,
+) -> Result<Vec<u8>, CUresult> {
+    let selection = global_state.ptx_selection;
+    if let PtxSelection::Exact(sm_version) = selection {
+        if !ptx_files.iter().any(|(_, sm)| *sm == sm_version) {
+            log.info(format!(
+                "ZLUDA: no sm_{} PTX in the module, available: {}",
+                sm_version,
+                ptx_files
+                    .iter()
+                    .map(|(_, sm)| format!("sm_{}", sm))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    let mut candidates = selection.select(ptx_files.iter().map(|(ptx, sm)| (ptx, *sm)).collect());
+    if candidates.is_empty() {
+        log.error("ZLUDA: module contains no usable PTX");
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    let cache = global_state
+        .kernel_cache
+        .as_ref()
+        .filter(|_| candidates.len() > 1);
+    let cached_choice = cache.and_then(|cache| {
+        cache.try_load_ptx_selection(
+            &global_state.comgr_version,
+            isa,
+            &ptx_files,
+            compilation_mode,
+        )
+    });
+    if let Some(sm_version) = cached_choice {
+        if let Some(index) = candidates.iter().position(|(_, sm)| *sm == sm_version) {
+            candidates[..=index].rotate_right(1);
+        }
+    }
+    for (ptx, sm_version) in candidates.iter() {
+        let mut candidate_log = JitLog::default();
+        match link_build_zluda_module_impl(
+            global_state,
+            compilation_mode,
+            isa,
+            std::slice::from_ref(*ptx),
+            false,
+            &mut candidate_log,
+        ) {
+            Ok(binary) => {
+                log.append(candidate_log);
+                if let Some(cache) = cache {
+                    if cached_choice != Some(*sm_version) {
+                        cache.save_ptx_selection(
+                            &global_state.comgr_version,
+                            isa,
+                            &ptx_files,
+                            compilation_mode,
+                            *sm_version,
+                        );
+                    }
                 }
+                return Ok(binary);
+            }
+            Err(err) => {
+                log.info(format!(
+                    "ZLUDA: rejected sm_{} PTX (error {}){}",
+                    sm_version,
+                    err.0,
+                    candidate_log
+                        .errors()
+                        .iter()
+                        .map(|error| format!("\n  {}", error))
+                        .collect::<String>()
+                ));
+            }
+        }
+    }
+    // None of the candidates compiled, let the preferred one fall back to an
+    // empty module like any other PTX
+    link_build_zluda_module(
+        global_state,
+        compilation_mode,
+        isa,
+        std::slice::from_ref(candidates[0].0),
+        log,
+    )
+}
+
+// Extracts the best PTX from every host object of a static library,
+// e.g. one created with `nvcc -lib -rdc=true`
+pub(crate) fn extract_archive_ptx(
+    archive: &[u8],
+    selection: PtxSelection,
+) -> Result<Vec<Cow<'static, str>>, CUresult> {
+    let fatbins = zluda_dark_api::HostObjectFatbins::from_archive(archive)
+        .map_err(|_| CUresult::CUDA_ERROR_INVALID_IMAGE)?;
+    extract_host_fatbins_ptx(&fatbins, selection)
+}
+
+pub(crate) fn extract_host_object_ptx(
+    object: &[u8],
+    selection: PtxSelection,
+) -> Result<Vec<Cow<'static, str>>, CUresult> {
+    let fatbins = zluda_dark_api::HostObjectFatbins::from_host_object(object)
+        .map_err(|_| CUresult::CUDA_ERROR_INVALID_IMAGE)?;
+    extract_host_fatbins_ptx(&fatbins, selection)
+}
+
+fn extract_host_fatbins_ptx(
+    fatbins: &zluda_dark_api::HostObjectFatbins,
+    selection: PtxSelection,
+) -> Result<Vec<Cow<'static, str>>, CUresult> {
+    let mut ptx_files = Vec::new();
+    for module in fatbins.modules() {
+        let module = unsafe { module.get() }.map_err(|_| CUresult::CUDA_ERROR_INVALID_IMAGE)?;
+        if let zluda_dark_api::FatbinModule::Files(files) = module {
+            // Borrowed text points into `fatbins`, it has to be copied out
+            if let Some((ptx, _)) = selection.select(extract_ptx(files)).into_iter().next() {
+                ptx_files.push(Cow::Owned(ptx.into_owned()));
             }
-            Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
         }
     }
+    Ok(ptx_files)
 }
 
-fn extract_ptx(files: zluda_dark_api::FatbinModuleFiles) -> Vec<(Cow<'static, str>, u32)> {
-    let mut ptx_files = files
-        .filter_map(|file| {
-            file.ok()
-                .map(|file| {
-                    if file.kind == FatbinFileKind::Ptx {
-                        unsafe { file.get_or_decompress() }
-                            .ok()
-                            .map(|f| {
-                                // TODO: implement support for envreg
-                                // %envreg is currently used by global grid sync in PETSc on never CUDA architectures:
-                                //  auto g = cooperative_groups::this_grid();
-                                //  g.sync();
-                                if memchr::memmem::find(&*f, b"%envreg").is_some() {
-                                    return None;
-                                }
-                                let text = match f {
-                                    Cow::Borrowed(slice) => {
-                                        Cow::Borrowed(std::str::from_utf8(slice).ok()?)
-                                    }
-                                    Cow::Owned(vec) => Cow::Owned(String::from_utf8(vec).ok()?),
-                                };
-                                Some((text, file.sm_version))
-                            })
-                            .flatten()
-                    } else {
-                        None
-                    }
-                })
-                .flatten()
+// Best PTX of every fatbin module that takes part in linking. Input of
+// cuLinkAddData is not guaranteed to outlive the call, so the text is copied
+pub(crate) unsafe fn extract_fatbin_ptx(
+    fatbin: zluda_dark_api::CudaFatbin,
+    selection: PtxSelection,
+) -> Result<Vec<Cow<'static, str>>, CUresult> {
+    let mut ptx_files = Vec::new();
+    let mut push_best_ptx = |module: &zluda_dark_api::FatbinModuleHandle| {
+        let module = module
+            .get()
+            .map_err(|_| CUresult::CUDA_ERROR_INVALID_IMAGE)?;
+        if let zluda_dark_api::FatbinModule::Files(files) = module {
+            if let Some((ptx, _)) = selection.select(extract_ptx(files)).into_iter().next() {
+                ptx_files.push(Cow::Owned(ptx.into_owned()));
+            }
+        }
+        Ok::<_, CUresult>(())
+    };
+    match fatbin {
+        zluda_dark_api::CudaFatbin::Version1(module) => push_best_ptx(&module)?,
+        // Relocatable device code is linked from its pre-link modules
+        zluda_dark_api::CudaFatbin::Version2 {
+            post_link,
+            pre_link,
+        } => {
+            if pre_link.is_empty() {
+                push_best_ptx(&post_link)?;
+            } else {
+                for module in pre_link {
+                    push_best_ptx(module)?;
+                }
+            }
+        }
+    }
+    Ok(ptx_files)
+}
+
+// Follows static library semantics: an archive member gets linked only if it
+// defines a symbol that is declared, but not defined, by the modules linked
+// so far. Actual symbol resolution is then done by the compiler in
+// `resolve_linking`, this only decides which members take part in it
+pub(crate) fn select_archive_members(
+    modules: &[Cow<'static, str>],
+    members: Vec<Cow<'static, str>>,
+) -> Vec<Cow<'static, str>> {
+    let mut defined = FxHashSet::default();
+    let mut declared = FxHashSet::default();
+    for module in modules {
+        collect_linking_symbols(module, &mut defined, &mut declared);
+    }
+    let mut candidates = members
+        .into_iter()
+        .map(|member| {
+            let mut member_defined = FxHashSet::default();
+            let mut member_declared = FxHashSet::default();
+            collect_linking_symbols(&member, &mut member_defined, &mut member_declared);
+            Some((member, member_defined, member_declared))
         })
         .collect::<Vec<_>>();
-    ptx_files.sort_unstable_by_key(|(_, sm_version)| cmp::Reverse(*sm_version));
-    ptx_files
+    let mut selected = Vec::new();
+    loop {
+        let next = candidates.iter().position(|candidate| match candidate {
+            Some((_, member_defined, _)) => member_defined
+                .iter()
+                .any(|symbol| declared.contains(symbol) && !defined.contains(symbol)),
+            None => false,
+        });
+        let (member, member_defined, member_declared) = match next {
+            Some(index) => candidates[index].take().unwrap(),
+            None => break,
+        };
+        defined.extend(member_defined);
+        declared.extend(member_declared);
+        selected.push(member);
+    }
+    selected
+}
+
+fn collect_linking_symbols(
+    ptx_text: &str,
+    defined: &mut FxHashSet<String>,
+    declared: &mut FxHashSet<String>,
+) {
+    let (ast, _) = ptx::ModuleParser::parse_unchecked(ptx_text);
+    for directive in ast.directives {
+        let (linking, name, is_definition) = match directive {
+            ast::Directive::Variable(linking, multivar) => (linking, multivar.variable.name, true),
+            ast::Directive::Method(linking, method) => {
+                let name = match method.func_directive.name {
+                    ast::MethodName::Kernel(name) | ast::MethodName::Func(name) => name,
+                };
+                (linking, name, method.body.is_some())
+            }
+        };
+        match linking {
+            ast::LinkingDirective::None => {}
+            ast::LinkingDirective::Extern => {
+                declared.insert(name.to_string());
+            }
+            ast::LinkingDirective::Visible
+            | ast::LinkingDirective::Weak
+            | ast::LinkingDirective::Common => {
+                if is_definition {
+                    defined.insert(name.to_string());
+                } else {
+                    declared.insert(name.to_string());
+                }
+            }
+        }
+    }
 }
 
 pub(crate) unsafe fn load_data_any(
@@ -241,13 +503,16 @@ pub(crate) unsafe fn load_data_any(
     compilation_mode: CompilationMode,
     isa: &CStr,
     input: CUmoduleContent,
+    log: &mut JitLog,
 ) -> Result<ModuleData, CUresult> {
     let global_state = GLOBAL_STATE.get()?;
-    let gpu_module = link_build_or_load_cuda_module(global_state, compilation_mode, isa, input)?;
+    let gpu_module =
+        link_build_or_load_cuda_module(global_state, compilation_mode, isa, input, log)?;
     let (hipfix_max_group_sizes, sm_version) = load_kernel_metadata(&*gpu_module)?;
     let mut hip_module = ptr::null_mut();
     hip_call_cuda! { hipModuleLoadData(&mut hip_module, gpu_module.as_ptr() as _) };
     let device_version = device::COMPUTE_CAPABILITY_MAJOR * 10 + device::COMPUTE_CAPABILITY_MINOR;
+    let grid_workspace = Arc::new(function::GridWorkspace::new()?);
     Ok(ModuleData {
         compilation_mode,
         base: hip_module,
@@ -256,6 +521,7 @@ pub(crate) unsafe fn load_data_any(
         sm_version,
         hipfix_max_group_sizes,
         functions: Mutex::new(FxHashMap::default()),
+        grid_workspace,
     })
 }
 
@@ -292,19 +558,42 @@ pub(crate) fn link_build_zluda_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     ptx_text: &[Cow<'_, str>],
+    log: &mut JitLog,
+) -> Result<Vec<u8>, CUresult> {
+    link_build_zluda_module_impl(global_state, compilation_mode, isa, ptx_text, true, log)
+}
+
+// With `allow_empty_module` release builds replace modules that fail to
+// compile with an empty module instead of returning an error
+fn link_build_zluda_module_impl(
+    global_state: &super::GlobalState,
+    compilation_mode: CompilationMode,
+    isa: &CStr,
+    ptx_text: &[Cow<'_, str>],
+    allow_empty_module: bool,
+    log: &mut JitLog,
 ) -> Result<Vec<u8>, CUresult> {
     if ptx_text.is_empty() {
+        log.error("No PTX modules to compile");
         return Err(CUresult::CUDA_ERROR_UNKNOWN);
     }
     if let Some(ref cache) = global_state.kernel_cache {
         if let Some(binary) =
             cache.try_load_program(&global_state.comgr_version, isa, ptx_text, compilation_mode)
         {
+            log.info("ZLUDA: loaded compiled module from cache");
             return Ok(binary);
         }
     }
 
-    let progress = global_state.progressbar.as_ref().map(|pb| pb.create_progress(None, Some(true), Some(0.0), Some("ZLUDA 正在转译 PTX 模块".to_owned())));
+    let progress = global_state.progressbar.as_ref().map(|pb| {
+        pb.create_progress(
+            None,
+            Some(true),
+            Some(0.0),
+            Some("ZLUDA 正在转译 PTX 模块".to_owned()),
+        )
+    });
 
     if let Some(ref progressbar) = progress {
         progressbar.set_right_text(Some("正在将 PTX 模块解析为 AST (0/3)".to_owned()));
@@ -316,10 +605,23 @@ pub(crate) fn link_build_zluda_module(
     // to enable a few applications (but only in release mode)
     let asts = ptx_text
         .iter()
-        .map(|ptx_mod| {
+        .enumerate()
+        .map(|(index, ptx_mod)| {
             let mut module = ptx::ModuleParser::parse_checked(&*ptx_mod);
-            if !cfg!(debug_assertions) {
-                module = module.or_else(|_| ptx::ModuleParser::parse_checked(EMPTY_MODULE))
+            if let Err(ref errors) = module {
+                for error in errors {
+                    let error = unsafe { ptx::DisplayParseError::new(error, &*ptx_mod) };
+                    log.error(format!("PTX module {}: {}", index, error));
+                }
+            }
+            if allow_empty_module && !cfg!(debug_assertions) {
+                module = module.or_else(|_| {
+                    log.info(format!(
+                        "ZLUDA: PTX module {} replaced with an empty module",
+                        index
+                    ));
+                    ptx::ModuleParser::parse_checked(EMPTY_MODULE)
+                })
             }
             module
         })
@@ -330,9 +632,25 @@ pub(crate) fn link_build_zluda_module(
         progressbar.set_right_text(Some("正在转换 LLVM 模块 (1/3)".to_owned()))
     }
 
-    let mut llvm_module = ptx::to_llvm_module(compilation_mode, asts);
-    if !cfg!(debug_assertions) {
+    let mut instrumentation = PassInstrumentation::from_env().unwrap_or_else(|err| {
+        log.error(err);
+        None
+    });
+    let mut llvm_module = match instrumentation {
+        Some(ref mut instrumentation) => {
+            ptx::to_llvm_module_instrumented(compilation_mode, asts, instrumentation)
+        }
+        None => ptx::to_llvm_module(compilation_mode, asts),
+    };
+    if let Some(ref instrumentation) = instrumentation {
+        write_instrumentation(instrumentation, ptx_text, log);
+    }
+    if let Err(ref error) = llvm_module {
+        log.error(format!("PTX translation failed: {}", error));
+    }
+    if allow_empty_module && !cfg!(debug_assertions) {
         llvm_module = llvm_module.or_else(|_| {
+            log.info("ZLUDA: translated module replaced with an empty module");
             ptx::to_llvm_module(
                 compilation_mode,
                 vec![ptx::ModuleParser::parse_checked(EMPTY_MODULE)
@@ -346,15 +664,21 @@ pub(crate) fn link_build_zluda_module(
         progressbar.set_right_text(Some("正在编译到 AMD 显卡架构 (2/3)".to_owned()))
     }
 
-    let binary = global_state
-        .comgr
-        .compile(
-            compilation_mode,
-            isa,
-            ptx::Module::get_bitcode_multi(std::iter::once(&llvm_module)).into_iter(),
-            &llvm_module.metadata.to_elf_section(),
-        )
-        .map_err(comgr_error_to_cuda)?;
+    let mut comgr_log = Vec::new();
+    let binary = global_state.comgr.compile_with_log(
+        compilation_mode,
+        isa,
+        ptx::Module::get_bitcode_multi(std::iter::once(&llvm_module)).into_iter(),
+        &llvm_module.metadata.to_elf_section(),
+        &mut comgr_log,
+    );
+    for message in comgr_log {
+        log.error(message);
+    }
+    let binary = binary.map_err(|err| {
+        log.error(format!("AMD GPU code compilation failed: {:?}", err));
+        comgr_error_to_cuda(err)
+    })?;
 
     if let Some(ref progressbar) = progress {
         progressbar.set_right_text(Some("正在将编译结果缓存到硬盘 (3/3)".to_owned()))
@@ -372,6 +696,41 @@ pub(crate) fn link_build_zluda_module(
     Ok(binary)
 }
 
+// Pass timings go to the JIT info log, both timings and module dumps are
+// written to ZLUDA_PTX_DUMP_DIR (temporary directory by default) as
+// `<module hash>.<pass>.txt` and `<module hash>.passes.txt`
+fn write_instrumentation(
+    instrumentation: &PassInstrumentation,
+    ptx_text: &[Cow<'_, str>],
+    log: &mut JitLog,
+) {
+    let dump_dir = std::env::var_os("ZLUDA_PTX_DUMP_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let hash = compute::module_hash(ptx_text.iter().map(|module| &**module));
+    let mut files = instrumentation
+        .dumps
+        .iter()
+        .map(|dump| (format!("{}.{}.txt", hash, dump.pass), dump.text.clone()))
+        .collect::<Vec<_>>();
+    if !instrumentation.timings.is_empty() {
+        let report = instrumentation.timing_report();
+        log.info(format!("ZLUDA: PTX translation passes\n{}", report));
+        files.push((format!("{}.passes.txt", hash), report));
+    }
+    if let Err(err) = std::fs::create_dir_all(&dump_dir) {
+        log.error(format!("{}: {}", dump_dir.display(), err));
+        return;
+    }
+    for (name, text) in files {
+        let path = dump_dir.join(name);
+        match std::fs::write(&path, text) {
+            Ok(()) => log.info(format!("ZLUDA: wrote {}", path.display())),
+            Err(err) => log.error(format!("{}: {}", path.display(), err)),
+        }
+    }
+}
+
 pub(crate) unsafe fn unload(hmod: *mut Module) -> Result<(), CUresult> {
     if hmod == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
@@ -416,7 +775,9 @@ pub(crate) unsafe fn get_function(
                     ptx_version: module.sm_version,
                     group_size: module.hipfix_max_group_sizes.get(&name).copied(),
                     compilation_mode: module.compilation_mode,
+                    grid_workspace: module.grid_workspace.clone(),
                 })));
+            function::register(function)?;
             function as *const function::Function as *mut _
         }
     };
@@ -484,3 +845,43 @@ pub(crate) unsafe fn get_loading_mode(result: *mut CUmoduleLoadingMode) -> CUres
         CUresult::CUDA_SUCCESS
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::select_archive_members;
+    use std::borrow::Cow;
+
+    const HEADER: &'static str = ".version 6.5\n.target sm_30\n.address_size 64\n";
+
+    fn module(body: &str) -> Cow<'static, str> {
+        Cow::Owned(format!("{}{}", HEADER, body))
+    }
+
+    #[test]
+    fn select_archive_members_only_referenced() {
+        let main = module(
+            ".extern .func (.reg .u32 res) foo();
+            .entry kernel() { .reg .u32 x; call (x), foo; ret; }",
+        );
+        let foo = module(
+            ".extern .func (.reg .u32 res) bar();
+            .visible .func (.reg .u32 res) foo() { call (res), bar; ret; }",
+        );
+        let bar = module(".visible .func (.reg .u32 res) bar() { mov.u32 res, 1; ret; }");
+        let unused = module(".visible .func (.reg .u32 res) baz() { mov.u32 res, 2; ret; }");
+        let selected =
+            select_archive_members(&[main], vec![unused.clone(), bar.clone(), foo.clone()]);
+        assert_eq!(selected, vec![foo, bar]);
+    }
+
+    #[test]
+    fn select_archive_members_skips_already_defined() {
+        let main = module(
+            ".extern .global .u32 foo;
+            .visible .global .u32 foo = 1;",
+        );
+        let foo = module(".visible .global .u32 foo = 2;");
+        let selected = select_archive_members(&[main], vec![foo]);
+        assert!(selected.is_empty());
+    }
+}
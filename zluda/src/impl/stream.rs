@@ -184,8 +184,85 @@ pub(crate) unsafe fn get_flags(stream: *mut Stream, flags: *mut u32) -> Result<(
 pub(crate) unsafe fn is_capturing(
     stream: *mut Stream,
     capture_status: *mut hipStreamCaptureStatus,
+    default_stream_per_thread: bool,
 ) -> Result<(), CUresult> {
-    let hip_stream = as_hip_stream(stream)?;
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
     hip_call_cuda! { hipStreamIsCapturing(hip_stream, capture_status) };
     Ok(())
 }
+
+pub(crate) unsafe fn begin_capture(
+    stream: *mut Stream,
+    mode: hipStreamCaptureMode,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    // Legacy default stream synchronizes with every other stream, it can't
+    // be captured
+    if hip_stream == hipStreamNull {
+        return Err(CUresult::CUDA_ERROR_STREAM_CAPTURE_UNSUPPORTED);
+    }
+    hip_call_cuda! { hipStreamBeginCapture(hip_stream, mode) };
+    Ok(())
+}
+
+pub(crate) unsafe fn end_capture(
+    stream: *mut Stream,
+    graph_out: *mut hipGraph_t,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if graph_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    hip_call_cuda! { hipStreamEndCapture(hip_stream, graph_out) };
+    Ok(())
+}
+
+pub(crate) unsafe fn get_capture_info_v2(
+    stream: *mut Stream,
+    capture_status_out: *mut hipStreamCaptureStatus,
+    id_out: *mut u64,
+    graph_out: *mut hipGraph_t,
+    dependencies_out: *mut *const hipGraphNode_t,
+    num_dependencies_out: *mut usize,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    hip_call_cuda! { hipStreamGetCaptureInfo_v2(
+        hip_stream,
+        capture_status_out,
+        id_out,
+        graph_out,
+        dependencies_out,
+        num_dependencies_out
+    ) };
+    Ok(())
+}
+
+pub(crate) unsafe fn update_capture_dependencies(
+    stream: *mut Stream,
+    dependencies: *mut hipGraphNode_t,
+    num_dependencies: usize,
+    flags: ::std::os::raw::c_uint,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    hip_call_cuda! { hipStreamUpdateCaptureDependencies(
+        hip_stream,
+        dependencies,
+        num_dependencies,
+        flags
+    ) };
+    Ok(())
+}
+
+pub(crate) unsafe fn thread_exchange_capture_mode(
+    mode: *mut hipStreamCaptureMode,
+) -> Result<(), CUresult> {
+    if mode == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    hip_call_cuda! { hipThreadExchangeStreamCaptureMode(mode) };
+    Ok(())
+}
@@ -104,6 +104,33 @@ pub(crate) unsafe fn set_array(
     }
 }
 
+pub(crate) unsafe fn set_mipmapped_array(
+    texref: *mut textureReference,
+    mipmapped_array: CUmipmappedArray,
+    flags: u32,
+) -> Result<(), CUresult> {
+    if (flags & !1u32) != 0 {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    let hack_flag = hipfix::array::get_mipmapped_hack_flag(mipmapped_array);
+    let mipmapped_array = hipfix::array::get_mipmapped(mipmapped_array);
+    if let Some(array) = mipmapped_array.as_ref() {
+        hip_call_cuda!(hipTexRefSetFormat(
+            texref,
+            hipfix::get_broken_format(hack_flag, array.format),
+            array.num_channels as i32,
+        ));
+        hip_call_cuda!(hipTexRefSetMipmappedArray(
+            texref,
+            mipmapped_array,
+            HIP_TRSA_OVERRIDE_FORMAT
+        ));
+        Ok(())
+    } else {
+        Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+    }
+}
+
 unsafe fn reset(tex_ref: *mut textureReference) -> Result<(), CUresult> {
     if tex_ref == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
@@ -0,0 +1,297 @@
+// Virtual memory management: physical allocations, address ranges and
+// mappings between them. HIP has the same model, we only translate
+// descriptors and handles
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{ffi::c_void, mem, ptr};
+
+pub(crate) unsafe fn create(
+    handle: *mut CUmemGenericAllocationHandle,
+    size: usize,
+    prop: *const CUmemAllocationProp,
+    flags: u64,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let prop = prop.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let prop = allocation_prop_from_cuda(prop)?;
+    let mut hip_handle = ptr::null_mut();
+    hip_call_cuda!(hipMemCreate(&mut hip_handle, size, &prop, flags));
+    *handle = handle_to_cuda(hip_handle);
+    Ok(())
+}
+
+pub(crate) unsafe fn release(handle: CUmemGenericAllocationHandle) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemRelease(handle_from_cuda(handle)));
+    Ok(())
+}
+
+pub(crate) unsafe fn address_reserve(
+    ptr: *mut hipDeviceptr_t,
+    size: usize,
+    alignment: usize,
+    addr: hipDeviceptr_t,
+    flags: u64,
+) -> Result<(), CUresult> {
+    if ptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut reserved = ptr::null_mut();
+    hip_call_cuda!(hipMemAddressReserve(
+        &mut reserved,
+        size,
+        alignment,
+        addr.0,
+        flags
+    ));
+    *ptr = hipDeviceptr_t(reserved);
+    Ok(())
+}
+
+pub(crate) unsafe fn address_free(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemAddressFree(ptr.0, size));
+    Ok(())
+}
+
+pub(crate) unsafe fn map(
+    ptr: hipDeviceptr_t,
+    size: usize,
+    offset: usize,
+    handle: CUmemGenericAllocationHandle,
+    flags: u64,
+) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemMap(
+        ptr.0,
+        size,
+        offset,
+        handle_from_cuda(handle),
+        flags
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn unmap(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemUnmap(ptr.0, size));
+    Ok(())
+}
+
+pub(crate) unsafe fn set_access(
+    ptr: hipDeviceptr_t,
+    size: usize,
+    desc: *const CUmemAccessDesc,
+    count: usize,
+) -> Result<(), CUresult> {
+    if desc == ptr::null() || count == 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = std::slice::from_raw_parts(desc, count)
+        .iter()
+        .map(access_desc_from_cuda)
+        .collect::<Result<Vec<_>, _>>()?;
+    hip_call_cuda!(hipMemSetAccess(ptr.0, size, desc.as_ptr(), desc.len()));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_access(
+    flags: *mut u64,
+    location: *const CUmemLocation,
+    ptr: hipDeviceptr_t,
+) -> Result<(), CUresult> {
+    let location = location
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let location = location_from_cuda(location)?;
+    hip_call_cuda!(hipMemGetAccess(flags, &location, ptr.0));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_allocation_granularity(
+    granularity: *mut usize,
+    prop: *const CUmemAllocationProp,
+    option: CUmemAllocationGranularity_flags,
+) -> Result<(), CUresult> {
+    let granularity = granularity
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let prop = prop.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let prop = allocation_prop_from_cuda(prop)?;
+    let option = match option {
+        CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM => {
+            hipMemAllocationGranularity_flags::hipMemAllocationGranularityMinimum
+        }
+        CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_RECOMMENDED => {
+            hipMemAllocationGranularity_flags::hipMemAllocationGranularityRecommended
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    let mut hip_granularity = 0;
+    hip_call_cuda!(hipMemGetAllocationGranularity(
+        &mut hip_granularity,
+        &prop,
+        option
+    ));
+    // Applications use granularity as a divisor when rounding sizes
+    if hip_granularity == 0 {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    *granularity = hip_granularity;
+    Ok(())
+}
+
+pub(crate) unsafe fn export_to_shareable_handle(
+    shareable_handle: *mut c_void,
+    handle: CUmemGenericAllocationHandle,
+    handle_type: CUmemAllocationHandleType,
+    flags: u64,
+) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemExportToShareableHandle(
+        shareable_handle,
+        handle_from_cuda(handle),
+        handle_type_from_cuda(handle_type)?,
+        flags
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn import_from_shareable_handle(
+    handle: *mut CUmemGenericAllocationHandle,
+    os_handle: *mut c_void,
+    handle_type: CUmemAllocationHandleType,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_handle = ptr::null_mut();
+    hip_call_cuda!(hipMemImportFromShareableHandle(
+        &mut hip_handle,
+        os_handle,
+        handle_type_from_cuda(handle_type)?
+    ));
+    *handle = handle_to_cuda(hip_handle);
+    Ok(())
+}
+
+pub(crate) unsafe fn get_allocation_properties_from_handle(
+    prop: *mut CUmemAllocationProp,
+    handle: CUmemGenericAllocationHandle,
+) -> Result<(), CUresult> {
+    let prop = prop.as_mut().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_prop = mem::zeroed::<hipMemAllocationProp>();
+    hip_call_cuda!(hipMemGetAllocationPropertiesFromHandle(
+        &mut hip_prop,
+        handle_from_cuda(handle)
+    ));
+    *prop = allocation_prop_to_cuda(&hip_prop);
+    Ok(())
+}
+
+pub(crate) unsafe fn retain_allocation_handle(
+    handle: *mut CUmemGenericAllocationHandle,
+    addr: *mut c_void,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_handle = ptr::null_mut();
+    hip_call_cuda!(hipMemRetainAllocationHandle(&mut hip_handle, addr));
+    *handle = handle_to_cuda(hip_handle);
+    Ok(())
+}
+
+// CUDA handles are opaque 64 bit integers, HIP handles are pointers
+fn handle_from_cuda(handle: CUmemGenericAllocationHandle) -> hipMemGenericAllocationHandle_t {
+    handle as usize as _
+}
+
+fn handle_to_cuda(handle: hipMemGenericAllocationHandle_t) -> CUmemGenericAllocationHandle {
+    handle as usize as _
+}
+
+fn allocation_prop_from_cuda(prop: &CUmemAllocationProp) -> Result<hipMemAllocationProp, CUresult> {
+    if prop.type_ != CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    Ok(hipMemAllocationProp {
+        type_: hipMemAllocationType::hipMemAllocationTypePinned,
+        requestedHandleType: handle_type_from_cuda(prop.requestedHandleTypes)?,
+        location: location_from_cuda(&prop.location)?,
+        win32HandleMetaData: prop.win32HandleMetaData,
+        allocFlags: hipMemAllocationProp__bindgen_ty_1 {
+            // Compression is only a hint
+            compressionType: 0,
+            gpuDirectRDMACapable: prop.allocFlags.gpuDirectRDMACapable,
+            usage: prop.allocFlags.usage,
+        },
+    })
+}
+
+fn allocation_prop_to_cuda(prop: &hipMemAllocationProp) -> CUmemAllocationProp {
+    CUmemAllocationProp {
+        type_: CUmemAllocationType(prop.type_.0),
+        requestedHandleTypes: CUmemAllocationHandleType(prop.requestedHandleType.0),
+        location: CUmemLocation {
+            type_: CUmemLocationType(prop.location.type_.0),
+            id: prop.location.id,
+        },
+        win32HandleMetaData: prop.win32HandleMetaData,
+        allocFlags: CUmemAllocationProp_st__bindgen_ty_1 {
+            compressionType: prop.allocFlags.compressionType,
+            gpuDirectRDMACapable: prop.allocFlags.gpuDirectRDMACapable,
+            usage: prop.allocFlags.usage,
+            reserved: [0; 4],
+        },
+    }
+}
+
+pub(crate) fn handle_type_from_cuda(
+    handle_type: CUmemAllocationHandleType,
+) -> Result<hipMemAllocationHandleType, CUresult> {
+    Ok(match handle_type {
+        CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE => {
+            hipMemAllocationHandleType::hipMemHandleTypeNone
+        }
+        CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR => {
+            hipMemAllocationHandleType::hipMemHandleTypePosixFileDescriptor
+        }
+        CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_WIN32 => {
+            hipMemAllocationHandleType::hipMemHandleTypeWin32
+        }
+        CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_WIN32_KMT => {
+            hipMemAllocationHandleType::hipMemHandleTypeWin32Kmt
+        }
+        // Fabric handles and combinations of handle types
+        _ => return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    })
+}
+
+pub(crate) fn location_from_cuda(location: &CUmemLocation) -> Result<hipMemLocation, CUresult> {
+    // HIP can't back virtual memory with host memory
+    if location.type_ != CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    Ok(hipMemLocation {
+        type_: hipMemLocationType::hipMemLocationTypeDevice,
+        id: location.id,
+    })
+}
+
+pub(crate) fn access_desc_from_cuda(desc: &CUmemAccessDesc) -> Result<hipMemAccessDesc, CUresult> {
+    let flags = match desc.flags {
+        CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_NONE => {
+            hipMemAccessFlags::hipMemAccessFlagsProtNone
+        }
+        CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READ => {
+            hipMemAccessFlags::hipMemAccessFlagsProtRead
+        }
+        CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE => {
+            hipMemAccessFlags::hipMemAccessFlagsProtReadWrite
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    Ok(hipMemAccessDesc {
+        location: location_from_cuda(&desc.location)?,
+        flags,
+    })
+}
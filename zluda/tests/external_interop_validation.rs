@@ -0,0 +1,36 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::ptr;
+
+mod common;
+
+cuda_driver_test!(external_interop_validation);
+
+unsafe fn external_interop_validation<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut ext_mem = ptr::null_mut();
+    assert_eq!(
+        cuda.cuImportExternalMemory(&mut ext_mem, ptr::null()),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    let mut ext_sem = ptr::null_mut();
+    assert_eq!(
+        cuda.cuImportExternalSemaphore(&mut ext_sem, ptr::null()),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    // Empty semaphore lists are a no-op
+    assert_eq!(
+        cuda.cuSignalExternalSemaphoresAsync(ptr::null(), ptr::null(), 0, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuWaitExternalSemaphoresAsync(ptr::null(), ptr::null(), 0, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,122 @@
+#![cfg(not(windows))]
+
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(external_memory_round_trip);
+
+// Exports a VMM allocation as a file descriptor and imports it back as
+// external memory, both mappings must see the same data
+unsafe fn external_memory_round_trip<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let location = CUmemLocation {
+        type_: CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE,
+        id: 0,
+    };
+    let mut prop = mem::zeroed::<CUmemAllocationProp>();
+    prop.type_ = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    prop.requestedHandleTypes = CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR;
+    prop.location = location;
+    let mut granularity = 0;
+    assert_eq!(
+        cuda.cuMemGetAllocationGranularity(
+            &mut granularity,
+            &prop,
+            CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut handle = 0;
+    assert_eq!(
+        cuda.cuMemCreate(&mut handle, granularity, &prop, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut allocation = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAddressReserve(&mut allocation, granularity, 0, mem::zeroed(), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemMap(allocation, granularity, 0, handle, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let access = CUmemAccessDesc {
+        location,
+        flags: CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE,
+    };
+    assert_eq!(
+        cuda.cuMemSetAccess(allocation, granularity, &access, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(allocation, 0x11223344, granularity / 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut fd = -1i32;
+    assert_eq!(
+        cuda.cuMemExportToShareableHandle(
+            &mut fd as *mut _ as _,
+            handle,
+            CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR,
+            0
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(fd >= 0);
+    let mut mem_desc = mem::zeroed::<CUDA_EXTERNAL_MEMORY_HANDLE_DESC>();
+    mem_desc.type_ = CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD;
+    mem_desc.handle.fd = fd;
+    mem_desc.size = granularity as u64;
+    // On success the file descriptor is owned by the external memory
+    let mut ext_mem = ptr::null_mut();
+    assert_eq!(
+        cuda.cuImportExternalMemory(&mut ext_mem, &mem_desc),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_desc = mem::zeroed::<CUDA_EXTERNAL_MEMORY_BUFFER_DESC>();
+    buffer_desc.size = granularity as u64;
+    let mut buffer = mem::zeroed();
+    assert_eq!(
+        cuda.cuExternalMemoryGetMappedBuffer(&mut buffer, ext_mem, &buffer_desc),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = 0u32;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, buffer, 4),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x11223344);
+    assert_eq!(
+        cuda.cuMemsetD32_v2(buffer, 0x55667788, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, allocation, 4),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x55667788);
+    // Cleanup
+    assert_eq!(cuda.cuMemFree_v2(buffer), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuDestroyExternalMemory(ext_mem),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemUnmap(allocation, granularity),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuMemRelease(handle), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuMemAddressFree(allocation, granularity),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
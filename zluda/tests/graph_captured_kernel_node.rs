@@ -0,0 +1,96 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(graph_captured_kernel_node);
+
+unsafe fn graph_captured_kernel_node<T: CudaDriverFns>(cuda: T) {
+    let kernel = include_str!("kernel_extra.ptx");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut kernel = mem::zeroed();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut kernel, module, b"add\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_input = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_input, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [
+        &mut buffer_input as *mut _ as *mut c_void,
+        &mut buffer_output as *mut _ as _,
+    ];
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuStreamBeginCapture_v2(stream, CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_GLOBAL),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuLaunchKernel(
+            kernel,
+            1,
+            1,
+            1,
+            2,
+            1,
+            1,
+            0,
+            stream,
+            args.as_mut_ptr(),
+            ptr::null_mut()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut graph = ptr::null_mut();
+    assert_eq!(
+        cuda.cuStreamEndCapture(stream, &mut graph),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut node = ptr::null_mut();
+    let mut num_nodes = 1;
+    assert_eq!(
+        cuda.cuGraphGetNodes(graph, &mut node, &mut num_nodes),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(num_nodes, 1);
+    let mut node_type = CUgraphNodeType::CU_GRAPH_NODE_TYPE_EMPTY;
+    assert_eq!(
+        cuda.cuGraphNodeGetType(node, &mut node_type),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(node_type == CUgraphNodeType::CU_GRAPH_NODE_TYPE_KERNEL);
+    // Captured kernel nodes never went through cuGraphAddKernelNode
+    let mut params = mem::zeroed::<CUDA_KERNEL_NODE_PARAMS_v1>();
+    assert_eq!(
+        cuda.cuGraphKernelNodeGetParams(node, &mut params),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(params.func, kernel);
+    assert_eq!(params.blockDimX, 2);
+    // Cleanup
+    assert_eq!(cuda.cuGraphDestroy(graph), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer_input), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer_output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,98 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(graph_memset_node);
+
+unsafe fn graph_memset_node<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer, 4 * mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut graph = ptr::null_mut();
+    assert_eq!(cuda.cuGraphCreate(&mut graph, 0), CUresult::CUDA_SUCCESS);
+    let mut params = CUDA_MEMSET_NODE_PARAMS {
+        dst: buffer,
+        pitch: 0,
+        value: 1,
+        elementSize: 4,
+        width: 4,
+        height: 1,
+    };
+    let mut node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphAddMemsetNode(&mut node, graph, ptr::null(), 0, &params, ctx),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut node_type = CUgraphNodeType::CU_GRAPH_NODE_TYPE_EMPTY;
+    assert_eq!(
+        cuda.cuGraphNodeGetType(node, &mut node_type),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(node_type == CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMSET);
+    let mut params_out = mem::zeroed::<CUDA_MEMSET_NODE_PARAMS>();
+    assert_eq!(
+        cuda.cuGraphMemsetNodeGetParams(node, &mut params_out),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(params_out.dst == buffer);
+    assert_eq!(params_out.value, 1);
+    assert_eq!(params_out.width, 4);
+    let mut graph_exec = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphInstantiate_v2(&mut graph_exec, graph, ptr::null_mut(), ptr::null_mut(), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    // Update the executable graph from a modified clone
+    let mut clone = ptr::null_mut();
+    assert_eq!(cuda.cuGraphClone(&mut clone, graph), CUresult::CUDA_SUCCESS);
+    let mut cloned_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphNodeFindInClone(&mut cloned_node, node, clone),
+        CUresult::CUDA_SUCCESS
+    );
+    params.value = 2;
+    assert_eq!(
+        cuda.cuGraphMemsetNodeSetParams(cloned_node, &params),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut update_info = mem::zeroed::<CUgraphExecUpdateResultInfo>();
+    assert_eq!(
+        cuda.cuGraphExecUpdate_v2(graph_exec, clone, &mut update_info),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(update_info.result == CUgraphExecUpdateResult::CU_GRAPH_EXEC_UPDATE_SUCCESS);
+    assert_eq!(
+        cuda.cuGraphLaunch(graph_exec, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamSynchronize(ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = [0u32; 4];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(
+            result.as_mut_ptr().cast(),
+            buffer,
+            mem::size_of_val(&result)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, [2; 4]);
+    // Cleanup
+    assert_eq!(cuda.cuGraphExecDestroy(graph_exec), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuGraphDestroy(clone), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuGraphDestroy(graph), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
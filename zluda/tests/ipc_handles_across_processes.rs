@@ -0,0 +1,146 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{
+    env,
+    io::{Read, Write},
+    mem,
+    os::raw::c_char,
+    process::{Command, Stdio},
+    ptr, slice,
+};
+
+mod common;
+
+cuda_driver_test!(ipc_handles_across_processes);
+
+const CU_EVENT_DISABLE_TIMING: u32 = 2;
+const CU_EVENT_INTERPROCESS: u32 = 4;
+const CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS: u32 = 1;
+// Set in the child process, which runs only this test
+const CHILD_ENV: &str = "ZLUDA_TEST_IPC_CHILD";
+
+unsafe fn ipc_handles_across_processes<T: CudaDriverFns>(cuda: T) {
+    if env::var_os(CHILD_ENV).is_some() {
+        return ipc_child(&cuda);
+    }
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(buffer, 0x11223344, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuEventCreate(&mut event, CU_EVENT_INTERPROCESS | CU_EVENT_DISABLE_TIMING),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuEventRecord(event, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut mem_handle = mem::zeroed::<CUipcMemHandle>();
+    assert_eq!(
+        cuda.cuIpcGetMemHandle(&mut mem_handle, buffer),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event_handle = mem::zeroed::<CUipcEventHandle>();
+    assert_eq!(
+        cuda.cuIpcGetEventHandle(&mut event_handle, event),
+        CUresult::CUDA_SUCCESS
+    );
+    // Forking the multithreaded test harness is not safe, run this test again
+    // in a fresh process instead and send it the handles through its stdin
+    let variant = std::any::type_name::<T>()
+        .rsplit("::")
+        .next()
+        .unwrap()
+        .to_lowercase();
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args([
+            &format!("ipc_handles_across_processes_{}", variant),
+            "--exact",
+            "--nocapture",
+        ])
+        .env(CHILD_ENV, "1")
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut to_child = child.stdin.take().unwrap();
+    write_handle(&mut to_child, &mem_handle.reserved);
+    write_handle(&mut to_child, &event_handle.reserved);
+    drop(to_child);
+    assert!(child.wait().unwrap().success());
+    // Child overwrites the buffer through its own mapping
+    let mut result = 0u32;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, buffer, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x55667788);
+    // Cleanup
+    assert_eq!(cuda.cuEventDestroy_v2(event), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+unsafe fn ipc_child<T: CudaDriverFns>(cuda: &T) {
+    let mut from_parent = std::io::stdin();
+    let mut mem_handle = mem::zeroed::<CUipcMemHandle>();
+    read_handle(&mut from_parent, &mut mem_handle.reserved);
+    let mut event_handle = mem::zeroed::<CUipcEventHandle>();
+    read_handle(&mut from_parent, &mut event_handle.reserved);
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuIpcOpenEventHandle(&mut event, event_handle),
+        CUresult::CUDA_SUCCESS
+    );
+    // Wait for the parent's memset before reading the buffer
+    assert_eq!(cuda.cuEventSynchronize(event), CUresult::CUDA_SUCCESS);
+    let mut buffer = mem::zeroed();
+    assert_eq!(
+        cuda.cuIpcOpenMemHandle_v2(&mut buffer, mem_handle, CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = 0u32;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, buffer, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x11223344);
+    assert_eq!(
+        cuda.cuMemsetD32_v2(buffer, 0x55667788, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuCtxSynchronize(), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuIpcCloseMemHandle(buffer), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuEventDestroy_v2(event), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+unsafe fn write_handle(to: &mut impl Write, handle: &[c_char; 64]) {
+    to.write_all(slice::from_raw_parts(handle.as_ptr().cast(), handle.len()))
+        .unwrap();
+}
+
+unsafe fn read_handle(from: &mut impl Read, handle: &mut [c_char; 64]) {
+    from.read_exact(slice::from_raw_parts_mut(
+        handle.as_mut_ptr().cast(),
+        handle.len(),
+    ))
+    .unwrap();
+}
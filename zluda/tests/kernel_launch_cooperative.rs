@@ -0,0 +1,211 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(kernel_launch_cooperative);
+
+unsafe fn kernel_launch_cooperative<T: CudaDriverFns>(cuda: T) {
+    let kernel = include_str!("kernel_launch_cooperative.ptx");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut kernel = mem::zeroed();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut kernel, module, b"add\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_input = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_input, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut input = 41u64;
+    assert_eq!(
+        cuda.cuMemcpyHtoD_v2(buffer_input, &mut input as *mut _ as _, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [
+        &mut buffer_input as *mut _ as *mut c_void,
+        &mut buffer_output as *mut _ as _,
+    ];
+    // Whole grid must fit on the device at once
+    assert_eq!(
+        cuda.cuLaunchCooperativeKernel(
+            kernel,
+            u32::MAX,
+            1,
+            1,
+            1,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr()
+        ),
+        CUresult::CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE
+    );
+    let mut attributes = [mem::zeroed::<CUlaunchAttribute>(); 2];
+    attributes[0].id = CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_COOPERATIVE;
+    attributes[0].value.cooperative = 1;
+    attributes[1].id = CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION;
+    attributes[1].value.clusterDim.x = 2;
+    attributes[1].value.clusterDim.y = 1;
+    attributes[1].value.clusterDim.z = 1;
+    let mut config = CUlaunchConfig {
+        gridDimX: 1,
+        gridDimY: 1,
+        gridDimZ: 1,
+        blockDimX: 1,
+        blockDimY: 1,
+        blockDimZ: 1,
+        sharedMemBytes: 0,
+        hStream: ptr::null_mut(),
+        attrs: attributes.as_mut_ptr(),
+        numAttrs: 2,
+    };
+    // Thread block clusters are not supported
+    assert_eq!(
+        cuda.cuLaunchKernelEx(&config, kernel, args.as_mut_ptr(), ptr::null_mut()),
+        CUresult::CUDA_ERROR_NOT_SUPPORTED
+    );
+    config.numAttrs = 1;
+    assert_eq!(
+        cuda.cuLaunchKernelEx(&config, kernel, args.as_mut_ptr(), ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamSynchronize(ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut output = 0u64;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut output as *mut _ as _, buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(output, 42);
+    let mut grid_sync = mem::zeroed();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut grid_sync, module, b"grid_sync\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    const BLOCKS: usize = 4;
+    let mut counter = mem::zeroed();
+    assert_eq!(cuda.cuMemAlloc_v2(&mut counter, 4), CUresult::CUDA_SUCCESS);
+    let mut grid_output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut grid_output, BLOCKS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut grid_args = [
+        &mut counter as *mut _ as *mut c_void,
+        &mut grid_output as *mut _ as _,
+    ];
+    // Run twice, the barrier workspace must be reusable across launches
+    for _ in 0..2 {
+        assert_eq!(cuda.cuMemsetD32_v2(counter, 0, 1), CUresult::CUDA_SUCCESS);
+        assert_eq!(
+            cuda.cuLaunchCooperativeKernel(
+                grid_sync,
+                BLOCKS as u32,
+                1,
+                1,
+                32,
+                1,
+                1,
+                0,
+                ptr::null_mut(),
+                grid_args.as_mut_ptr()
+            ),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(
+            cuda.cuStreamSynchronize(ptr::null_mut()),
+            CUresult::CUDA_SUCCESS
+        );
+        let mut result = [0u32; BLOCKS];
+        assert_eq!(
+            cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, grid_output, BLOCKS * 4),
+            CUresult::CUDA_SUCCESS
+        );
+        // Every block must observe the increments of all the other blocks
+        assert_eq!(result, [BLOCKS as u32; BLOCKS]);
+    }
+    // Launches on different streams share the module's barrier workspace,
+    // they must not run into each other
+    let mut streams = [ptr::null_mut(); 2];
+    let mut counters = [mem::zeroed(); 2];
+    let mut outputs = [mem::zeroed(); 2];
+    for i in 0..2 {
+        assert_eq!(
+            cuda.cuStreamCreate(&mut streams[i], 0),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(
+            cuda.cuMemAlloc_v2(&mut counters[i], 4),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(
+            cuda.cuMemsetD32_v2(counters[i], 0, 1),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(
+            cuda.cuMemAlloc_v2(&mut outputs[i], BLOCKS * 4),
+            CUresult::CUDA_SUCCESS
+        );
+    }
+    for i in 0..2 {
+        let mut stream_args = [
+            &mut counters[i] as *mut _ as *mut c_void,
+            &mut outputs[i] as *mut _ as _,
+        ];
+        assert_eq!(
+            cuda.cuLaunchCooperativeKernel(
+                grid_sync,
+                BLOCKS as u32,
+                1,
+                1,
+                32,
+                1,
+                1,
+                0,
+                streams[i],
+                stream_args.as_mut_ptr()
+            ),
+            CUresult::CUDA_SUCCESS
+        );
+    }
+    for i in 0..2 {
+        assert_eq!(cuda.cuStreamSynchronize(streams[i]), CUresult::CUDA_SUCCESS);
+        let mut result = [0u32; BLOCKS];
+        assert_eq!(
+            cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, outputs[i], BLOCKS * 4),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(result, [BLOCKS as u32; BLOCKS]);
+        assert_eq!(cuda.cuMemFree_v2(counters[i]), CUresult::CUDA_SUCCESS);
+        assert_eq!(cuda.cuMemFree_v2(outputs[i]), CUresult::CUDA_SUCCESS);
+        assert_eq!(cuda.cuStreamDestroy_v2(streams[i]), CUresult::CUDA_SUCCESS);
+    }
+    // Cleanup
+    assert_eq!(cuda.cuMemFree_v2(counter), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(grid_output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer_input), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer_output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
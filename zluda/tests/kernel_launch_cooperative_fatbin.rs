@@ -0,0 +1,114 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(kernel_launch_cooperative_fatbin);
+
+const FATBIN_MAGIC: u32 = 0xBA55ED50;
+const FATBIN_FILE_HEADER_KIND_PTX: u16 = 0x01;
+const FATBIN_FILE_HEADER_VERSION_CURRENT: u16 = 0x101;
+
+// Wraps PTX in an uncompressed fatbin, the way nvcc embeds it in binaries
+fn fatbin_with_ptx(ptx: &[u8], sm_version: u32) -> Vec<u64> {
+    let mut file = Vec::new();
+    file.extend_from_slice(&FATBIN_FILE_HEADER_KIND_PTX.to_le_bytes());
+    file.extend_from_slice(&FATBIN_FILE_HEADER_VERSION_CURRENT.to_le_bytes());
+    // header_size, padded_payload_size, unknown0, payload_size, unknown1,
+    // ptx_version, sm_version
+    for field in [
+        64,
+        ptx.len() as u32,
+        0,
+        ptx.len() as u32,
+        0,
+        0x10005,
+        sm_version,
+    ] {
+        file.extend_from_slice(&field.to_le_bytes());
+    }
+    file.resize(64, 0);
+    file.extend_from_slice(ptx);
+    let mut fatbin = Vec::new();
+    fatbin.extend_from_slice(&FATBIN_MAGIC.to_le_bytes());
+    fatbin.extend_from_slice(&1u16.to_le_bytes());
+    fatbin.extend_from_slice(&16u16.to_le_bytes());
+    fatbin.extend_from_slice(&(file.len() as u64).to_le_bytes());
+    fatbin.extend(file);
+    // Fatbin headers must be 8-byte aligned
+    let mut aligned = vec![0u64; (fatbin.len() + 7) / 8];
+    unsafe {
+        ptr::copy_nonoverlapping(
+            fatbin.as_ptr(),
+            aligned.as_mut_ptr().cast::<u8>(),
+            fatbin.len(),
+        )
+    };
+    aligned
+}
+
+// Grid sync kernels read the barrier workspace from %envreg, they must be
+// loadable from fatbins and not only from raw PTX
+unsafe fn kernel_launch_cooperative_fatbin<T: CudaDriverFns>(cuda: T) {
+    let fatbin = fatbin_with_ptx(include_bytes!("kernel_launch_cooperative.ptx"), 30);
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, fatbin.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut grid_sync = mem::zeroed();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut grid_sync, module, b"grid_sync\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    const BLOCKS: usize = 4;
+    let mut counter = mem::zeroed();
+    assert_eq!(cuda.cuMemAlloc_v2(&mut counter, 4), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemsetD32_v2(counter, 0, 1), CUresult::CUDA_SUCCESS);
+    let mut grid_output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut grid_output, BLOCKS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [
+        &mut counter as *mut _ as *mut c_void,
+        &mut grid_output as *mut _ as _,
+    ];
+    assert_eq!(
+        cuda.cuLaunchCooperativeKernel(
+            grid_sync,
+            BLOCKS as u32,
+            1,
+            1,
+            32,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamSynchronize(ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = [0u32; BLOCKS];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, grid_output, BLOCKS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, [BLOCKS as u32; BLOCKS]);
+    // Cleanup
+    assert_eq!(cuda.cuMemFree_v2(counter), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(grid_output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
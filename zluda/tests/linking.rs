@@ -1107,3 +1107,72 @@ unsafe fn emit_weak_fn<T: CudaDriverFns>(cuda: T) {
         CUresult::CUDA_SUCCESS
     );
 }
+
+cuda_driver_test!(link_error_log_survives_inputs);
+
+unsafe fn link_error_log_survives_inputs<T: CudaDriverFns>(cuda: T) {
+    let valid = "
+        .version 6.5
+        .target sm_60
+        .address_size 64
+        .entry valid() { ret; }\0";
+    let unresolved = "
+        .version 6.5
+        .target sm_60
+        .address_size 64
+        .extern .func foobar();
+        .entry unresolved() {
+            call foobar, ();
+            ret;
+        }\0";
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut error_log = [0u8; 1024];
+    let mut options = [
+        CUjit_option::CU_JIT_ERROR_LOG_BUFFER,
+        CUjit_option::CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES,
+    ];
+    let mut option_values = [
+        error_log.as_mut_ptr().cast::<c_void>(),
+        error_log.len() as *mut c_void,
+    ];
+    let mut linker = mem::zeroed();
+    assert_eq!(
+        cuda.cuLinkCreate_v2(
+            options.len() as u32,
+            options.as_mut_ptr(),
+            option_values.as_mut_ptr(),
+            &mut linker
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    // Inputs without errors must not shrink the log buffer
+    for input in [valid, unresolved] {
+        assert_eq!(
+            cuda.cuLinkAddData_v2(
+                linker,
+                CUjitInputType::CU_JIT_INPUT_PTX,
+                input.as_ptr().cast_mut().cast(),
+                input.len(),
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            ),
+            CUresult::CUDA_SUCCESS
+        );
+    }
+    let mut binary = ptr::null_mut();
+    let mut size = 0;
+    assert_ne!(
+        cuda.cuLinkComplete(linker, &mut binary, &mut size),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_ne!(error_log[0], 0);
+    assert_eq!(cuda.cuLinkDestroy(linker), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
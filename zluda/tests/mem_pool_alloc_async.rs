@@ -0,0 +1,82 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(mem_pool_alloc_async);
+
+unsafe fn mem_pool_alloc_async<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let mut props = mem::zeroed::<CUmemPoolProps>();
+    props.allocType = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    props.handleTypes = CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE;
+    props.location = CUmemLocation {
+        type_: CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE,
+        id: 0,
+    };
+    let mut pool = ptr::null_mut();
+    assert_eq!(
+        cuda.cuMemPoolCreate(&mut pool, &props),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut threshold = u64::MAX;
+    assert_eq!(
+        cuda.cuMemPoolSetAttribute(
+            pool,
+            CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD,
+            &mut threshold as *mut u64 as *mut c_void
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer = mem::zeroed();
+    let size = 4 * mem::size_of::<u32>();
+    assert_eq!(
+        cuda.cuMemAllocFromPoolAsync(&mut buffer, size, pool, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32Async(buffer, 7, 4, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = [0u32; 4];
+    assert_eq!(
+        cuda.cuMemcpyDtoHAsync_v2(result.as_mut_ptr().cast(), buffer, size, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(result, [7; 4]);
+    let mut used = 0u64;
+    assert_eq!(
+        cuda.cuMemPoolGetAttribute(
+            pool,
+            CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT,
+            &mut used as *mut u64 as *mut c_void
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(used >= size as u64);
+    // Current counters are read-only
+    assert_eq!(
+        cuda.cuMemPoolSetAttribute(
+            pool,
+            CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT,
+            &mut used as *mut u64 as *mut c_void
+        ),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    assert_eq!(cuda.cuMemFreeAsync(buffer, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemPoolTrimTo(pool, 0), CUresult::CUDA_SUCCESS);
+    // Cleanup
+    assert_eq!(cuda.cuMemPoolDestroy(pool), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
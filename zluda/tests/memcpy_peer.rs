@@ -0,0 +1,54 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(memcpy_peer);
+
+unsafe fn memcpy_peer<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut src_ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut src_ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut src = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut src, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(src, 0x11223344, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dst_ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut dst_ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dst = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut dst, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuCtxDisablePeerAccess(src_ctx),
+        CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED
+    );
+    assert_eq!(
+        cuda.cuMemcpyPeer(dst, dst_ctx, src, src_ctx, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = 0u32;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, dst, mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x11223344);
+    // Cleanup
+    assert_eq!(cuda.cuMemFree_v2(dst), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(dst_ctx), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(src), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(src_ctx), CUresult::CUDA_SUCCESS);
+}
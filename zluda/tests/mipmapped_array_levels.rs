@@ -0,0 +1,79 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(mipmapped_array_levels);
+
+unsafe fn mipmapped_array_levels<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let desc = CUDA_ARRAY3D_DESCRIPTOR {
+        Width: 64,
+        Height: 32,
+        Depth: 0,
+        Format: CUarray_format::CU_AD_FORMAT_SIGNED_INT16,
+        NumChannels: 2,
+        Flags: 0,
+    };
+    let mut mipmapped_array = ptr::null_mut();
+    assert_eq!(
+        cuda.cuMipmappedArrayCreate(&mut mipmapped_array, &desc, 3),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut level = ptr::null_mut();
+    assert_eq!(
+        cuda.cuMipmappedArrayGetLevel(&mut level, mipmapped_array, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut level_desc = mem::zeroed::<CUDA_ARRAY3D_DESCRIPTOR>();
+    assert_eq!(
+        cuda.cuArray3DGetDescriptor_v2(&mut level_desc, level),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(level_desc.Width, 32);
+    assert_eq!(level_desc.Height, 16);
+    assert_eq!(level_desc.NumChannels, 2);
+    let res_desc = CUDA_RESOURCE_DESC {
+        resType: CUresourcetype::CU_RESOURCE_TYPE_MIPMAPPED_ARRAY,
+        res: CUDA_RESOURCE_DESC_st__bindgen_ty_1 {
+            mipmap: CUDA_RESOURCE_DESC_st__bindgen_ty_1__bindgen_ty_2 {
+                hMipmappedArray: mipmapped_array,
+            },
+        },
+        flags: 0,
+    };
+    let tex_desc = CUDA_TEXTURE_DESC {
+        addressMode: [
+            CUaddress_mode::CU_TR_ADDRESS_MODE_CLAMP,
+            CUaddress_mode::CU_TR_ADDRESS_MODE_CLAMP,
+            CUaddress_mode::CU_TR_ADDRESS_MODE_CLAMP,
+        ],
+        filterMode: CUfilter_mode::CU_TR_FILTER_MODE_POINT,
+        flags: 0,
+        maxAnisotropy: 0,
+        mipmapFilterMode: CUfilter_mode::CU_TR_FILTER_MODE_POINT,
+        mipmapLevelBias: 0.0,
+        minMipmapLevelClamp: 0.0,
+        maxMipmapLevelClamp: 2.0,
+        borderColor: [0.0, 0.0, 0.0, 0.0],
+        reserved: mem::zeroed(),
+    };
+    let mut texobj = mem::zeroed();
+    assert_eq!(
+        cuda.cuTexObjectCreate(&mut texobj, &res_desc, &tex_desc, ptr::null()),
+        CUresult::CUDA_SUCCESS
+    );
+    // Cleanup
+    assert_eq!(cuda.cuTexObjectDestroy(texobj), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuMipmappedArrayDestroy(mipmapped_array),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
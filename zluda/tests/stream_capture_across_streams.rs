@@ -0,0 +1,114 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(stream_capture_across_streams);
+
+unsafe fn stream_capture_across_streams<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut origin = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut origin, 0), CUresult::CUDA_SUCCESS);
+    let mut forked = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut forked, 0), CUresult::CUDA_SUCCESS);
+    let mut fork_event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuEventCreate(&mut fork_event, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut join_event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuEventCreate(&mut join_event, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer, 2 * mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    let second_half = CUdeviceptr_v2((buffer.0 as usize + mem::size_of::<u32>()) as _);
+    // Legacy default stream can't be captured
+    assert_eq!(
+        cuda.cuStreamBeginCapture_v2(
+            ptr::null_mut(),
+            CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_GLOBAL
+        ),
+        CUresult::CUDA_ERROR_STREAM_CAPTURE_UNSUPPORTED
+    );
+    assert_eq!(
+        cuda.cuStreamBeginCapture_v2(origin, CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_GLOBAL),
+        CUresult::CUDA_SUCCESS
+    );
+    // Fork into the second stream and join it back through events
+    assert_eq!(
+        cuda.cuEventRecord(fork_event, origin),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamWaitEvent(forked, fork_event, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut status = CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE;
+    assert_eq!(
+        cuda.cuStreamIsCapturing(forked, &mut status),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(status == CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_ACTIVE);
+    assert_eq!(
+        cuda.cuMemsetD32Async(buffer, 1, 1, origin),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32Async(second_half, 2, 1, forked),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuEventRecord(join_event, forked),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamWaitEvent(origin, join_event, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut graph = ptr::null_mut();
+    assert_eq!(
+        cuda.cuStreamEndCapture(origin, &mut graph),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamIsCapturing(forked, &mut status),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(status == CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE);
+    let mut graph_exec = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphInstantiate_v2(&mut graph_exec, graph, ptr::null_mut(), ptr::null_mut(), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuGraphLaunch(graph_exec, origin),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuStreamSynchronize(origin), CUresult::CUDA_SUCCESS);
+    let mut result = [0u32; 2];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(
+            result.as_mut_ptr().cast(),
+            buffer,
+            mem::size_of_val(&result)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, [1, 2]);
+    // Cleanup
+    assert_eq!(cuda.cuGraphExecDestroy(graph_exec), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuGraphDestroy(graph), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(buffer), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,84 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(vmm_map_and_access);
+
+unsafe fn vmm_map_and_access<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let location = CUmemLocation {
+        type_: CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE,
+        id: 0,
+    };
+    let mut prop = mem::zeroed::<CUmemAllocationProp>();
+    prop.type_ = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    prop.location = location;
+    let mut granularity = 0;
+    assert_eq!(
+        cuda.cuMemGetAllocationGranularity(
+            &mut granularity,
+            &prop,
+            CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_ne!(granularity, 0);
+    let mut handle = 0;
+    assert_eq!(
+        cuda.cuMemCreate(&mut handle, granularity, &prop, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut ptr = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAddressReserve(&mut ptr, 2 * granularity, 0, mem::zeroed(), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    // Map the allocation into the second half of the reserved range
+    let mapped = CUdeviceptr_v2((ptr.0 as usize + granularity) as _);
+    assert_eq!(
+        cuda.cuMemMap(mapped, granularity, 0, handle, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let access = CUmemAccessDesc {
+        location,
+        flags: CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE,
+    };
+    assert_eq!(
+        cuda.cuMemSetAccess(mapped, granularity, &access, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut flags = 0;
+    assert_eq!(
+        cuda.cuMemGetAccess(&mut flags, &location, mapped),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        flags,
+        CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE.0 as u64
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(mapped, 0x11223344, granularity / 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = 0u32;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut result as *mut _ as _, mapped, 4),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, 0x11223344);
+    // Cleanup
+    assert_eq!(cuda.cuMemUnmap(mapped, granularity), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemRelease(handle), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuMemAddressFree(ptr, 2 * granularity),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,5 @@
+use vergen::{Config, vergen};
+
+fn main() {
+  vergen(Config::default()).unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,537 @@
+use argh::FromArgs;
+use hip_common::cache::{
+    self, compute, decode_entry, encode_program, Backend, EntryMetadata, FileRepository,
+    KernelRepository, SqliteRepository, StoredProgram,
+};
+use hip_common::CompilationMode;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{env, fs};
+
+type DynError = Box<dyn std::error::Error>;
+
+// Portable archive: magic followed by (u64 length, entry) pairs. Entries are
+// stored the way the file backend writes them, so they carry their own
+// checksums
+const ARCHIVE_MAGIC: &[u8; 8] = b"ZLCACHE1";
+
+#[derive(FromArgs)]
+/// Inspect and manage ZLUDA compiled code cache
+struct Arguments {
+    /// cache directory, defaults to the one used by ZLUDA
+    #[argh(option)]
+    cache_dir: Option<PathBuf>,
+    /// use OptiX cache instead of the compute cache
+    #[argh(switch)]
+    optix: bool,
+    #[argh(subcommand)]
+    command: Subcommand,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Subcommand {
+    List(ListCommand),
+    Stats(StatsCommand),
+    Verify(VerifyCommand),
+    Prune(PruneCommand),
+    Export(ExportCommand),
+    Import(ImportCommand),
+    Migrate(MigrateCommand),
+}
+
+#[derive(FromArgs)]
+/// List cache entries, most recently used first
+#[argh(subcommand, name = "list")]
+struct ListCommand {}
+
+#[derive(FromArgs)]
+/// Show cache size, hit statistics and sizes per version and device
+#[argh(subcommand, name = "stats")]
+struct StatsCommand {
+    /// clear hit statistics
+    #[argh(switch)]
+    reset: bool,
+}
+
+#[derive(FromArgs)]
+/// Check integrity of every cache entry
+#[argh(subcommand, name = "verify")]
+struct VerifyCommand {
+    /// remove corrupt entries and leftovers of interrupted writes
+    #[argh(switch)]
+    remove: bool,
+}
+
+#[derive(FromArgs)]
+/// Remove cache entries
+#[argh(subcommand, name = "prune")]
+struct PruneCommand {
+    /// remove entries not used in the given number of days
+    #[argh(option)]
+    older_than: Option<u64>,
+    /// remove least recently used entries until the cache fits in the given size (e.g. 2G)
+    #[argh(option, from_str_fn(parse_size))]
+    max_size: Option<u64>,
+    /// remove entries created by ZLUDA builds other than this one
+    #[argh(switch)]
+    other_versions: bool,
+    /// only print what would be removed
+    #[argh(switch)]
+    dry_run: bool,
+}
+
+#[derive(FromArgs)]
+/// Write all valid cache entries into a portable archive
+#[argh(subcommand, name = "export")]
+struct ExportCommand {
+    /// path of the archive
+    #[argh(positional)]
+    archive: PathBuf,
+    /// only export entries for this device ISA (e.g. gfx1030)
+    #[argh(option)]
+    device: Option<String>,
+}
+
+#[derive(FromArgs)]
+/// Add entries from an archive created with export
+#[argh(subcommand, name = "import")]
+struct ImportCommand {
+    /// path of the archive
+    #[argh(positional)]
+    archive: PathBuf,
+}
+
+#[derive(FromArgs)]
+/// Move all entries to another storage backend (ZLUDA_CACHE_BACKEND)
+#[argh(subcommand, name = "migrate")]
+struct MigrateCommand {
+    /// target backend: file or sqlite
+    #[argh(positional, from_str_fn(parse_backend))]
+    to: Backend,
+    /// keep entries in the old backend
+    #[argh(switch)]
+    keep: bool,
+}
+
+fn main() -> Result<(), DynError> {
+    let args: Arguments = argh::from_env();
+    let cache_dir = match args.cache_dir {
+        Some(cache_dir) => cache_dir,
+        None => default_cache_dir(args.optix).ok_or("Could not find cache directory")?,
+    };
+    if let Subcommand::Migrate(command) = args.command {
+        return migrate(cache_dir, command);
+    }
+    let cache = Backend::detect(&cache_dir).open(cache_dir.clone(), None)?;
+    match args.command {
+        Subcommand::List(_) => list(&*cache, args.optix),
+        Subcommand::Stats(command) => stats(&*cache, &cache_dir, command),
+        Subcommand::Verify(command) => verify(&*cache, command),
+        Subcommand::Prune(command) => prune(&*cache, command),
+        Subcommand::Export(command) => export(&*cache, command),
+        Subcommand::Import(command) => import(&*cache, command),
+        Subcommand::Migrate(_) => unreachable!(),
+    }
+}
+
+// Same as ZLUDA and ZLUDA OptiX, see compute::default_dir()
+fn default_cache_dir(optix: bool) -> Option<PathBuf> {
+    let (dir_variable, subdir) = if optix {
+        ("ZLUDA_OPTIX_CACHE_DIR", "OptixCache")
+    } else {
+        ("ZLUDA_CO_CACHE_DIR", "ComputeCache")
+    };
+    if let Ok(dir) = env::var(dir_variable) {
+        return Some(PathBuf::from(dir));
+    }
+    let mut dir = match env::var("ZLUDA_CACHE_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let mut dir = dirs::cache_dir()?;
+            dir.push("ZLUDA");
+            dir
+        }
+    };
+    dir.push(subdir);
+    Some(dir)
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    cache::parse_size(value).ok_or_else(|| format!("invalid size: {}", value))
+}
+
+fn parse_backend(value: &str) -> Result<Backend, String> {
+    Backend::parse(value).ok_or_else(|| format!("invalid backend: {}", value))
+}
+
+fn list(cache: &dyn KernelRepository, optix: bool) -> Result<(), DynError> {
+    let mut entries = cache.list_programs()?;
+    entries.sort_unstable_by_key(|entry| std::cmp::Reverse(entry.last_used));
+    println!(
+        "{:>9} {:>10} {:<12} {:<22} {:<10} {}",
+        "LAST USED", "SIZE", "DEVICE", "MODE", "VERSION", "CONTENT"
+    );
+    for entry in entries.iter().filter(|entry| !entry.temporary) {
+        let (device, mode, version, content) = match entry.metadata {
+            Some(ref metadata) => {
+                let (mode, content) = describe_parameters(optix, metadata);
+                (
+                    metadata.device.to_string_lossy().into_owned(),
+                    mode,
+                    short_git_hash(&metadata.git_hash).to_string(),
+                    content,
+                )
+            }
+            None => (
+                "?".to_string(),
+                "?".to_string(),
+                "?".to_string(),
+                "<corrupt>".to_string(),
+            ),
+        };
+        println!(
+            "{:>9} {:>10} {:<12} {:<22} {:<10} {}",
+            format_age(entry.last_used),
+            format_size(entry.size),
+            device,
+            mode,
+            version,
+            content
+        );
+    }
+    Ok(())
+}
+
+fn describe_parameters(optix: bool, metadata: &EntryMetadata) -> (String, String) {
+    let parameters = &metadata.additional_parameters;
+    if optix {
+        // "<program name>:<input attributes>:<HIP RT version>"
+        let parameters = String::from_utf8_lossy(parameters);
+        let program = parameters.split(':').next().unwrap_or_default();
+        let hiprt_version = parameters.rsplit(':').next().unwrap_or_default();
+        (
+            "-".to_string(),
+            format!("{} (HIP RT {})", program, hiprt_version),
+        )
+    } else {
+        let mode = match parameters
+            .first()
+            .copied()
+            .and_then(CompilationMode::from_u8)
+        {
+            Some(CompilationMode::Wave32) => "Wave32",
+            Some(CompilationMode::Wave32OnWave64) => "Wave32OnWave64",
+            Some(CompilationMode::DoubleWave32OnWave64) => "DoubleWave32OnWave64",
+            None => "?",
+        };
+        let content = if parameters.get(1) == Some(&compute::PTX_SELECTION_RECORD) {
+            "PTX selection"
+        } else {
+            "module"
+        };
+        (
+            mode.to_string(),
+            format!(
+                "{} {}",
+                content,
+                &metadata.hash[..16.min(metadata.hash.len())]
+            ),
+        )
+    }
+}
+
+fn stats(
+    cache: &dyn KernelRepository,
+    cache_dir: &Path,
+    command: StatsCommand,
+) -> Result<(), DynError> {
+    if command.reset {
+        cache.reset_stats()?;
+        return Ok(());
+    }
+    let mut total = (0usize, 0u64);
+    let mut per_version = BTreeMap::new();
+    let mut per_device = BTreeMap::new();
+    for entry in cache.list_programs()? {
+        total.0 += 1;
+        total.1 += entry.size;
+        let (version, device) = match entry.metadata {
+            Some(ref metadata) => (
+                format!(
+                    "{} ({})",
+                    short_git_hash(&metadata.git_hash),
+                    metadata.compiler_version
+                ),
+                metadata.device.to_string_lossy().into_owned(),
+            ),
+            None => ("<corrupt>".to_string(), "<corrupt>".to_string()),
+        };
+        add_entry(&mut per_version, version, &entry);
+        add_entry(&mut per_device, device, &entry);
+    }
+    let stats = cache.stats();
+    println!("Cache directory: {}", cache_dir.display());
+    println!("Entries: {} ({})", total.0, format_size(total.1));
+    let lookups = stats.hits + stats.misses;
+    let hit_rate = if lookups == 0 {
+        0.0
+    } else {
+        stats.hits as f64 * 100.0 / lookups as f64
+    };
+    println!(
+        "Hits: {}, misses: {} ({:.1}% hit rate)",
+        stats.hits, stats.misses, hit_rate
+    );
+    println!(
+        "Saves: {}, evicted: {}, corrupt: {}",
+        stats.saves, stats.evicted, stats.corrupt
+    );
+    println!("Per version:");
+    for (version, (count, size)) in per_version {
+        println!("  {}: {} entries ({})", version, count, format_size(size));
+    }
+    println!("Per device:");
+    for (device, (count, size)) in per_device {
+        println!("  {}: {} entries ({})", device, count, format_size(size));
+    }
+    Ok(())
+}
+
+fn add_entry(groups: &mut BTreeMap<String, (usize, u64)>, key: String, entry: &StoredProgram) {
+    let group = groups.entry(key).or_default();
+    group.0 += 1;
+    group.1 += entry.size;
+}
+
+fn verify(cache: &dyn KernelRepository, command: VerifyCommand) -> Result<(), DynError> {
+    let mut valid = 0;
+    let mut invalid = 0;
+    for entry in cache.list_programs()? {
+        let problem = if entry.temporary {
+            // Might be a write that is still in progress
+            if age(entry.last_used) < Duration::from_secs(60 * 60) {
+                continue;
+            }
+            "incomplete write"
+        } else if !cache.verify_program(&entry)? {
+            "corrupt"
+        } else {
+            valid += 1;
+            continue;
+        };
+        invalid += 1;
+        println!("{}: {}", entry.location, problem);
+        if command.remove {
+            cache.remove_program(&entry)?;
+        }
+    }
+    println!("{} valid, {} invalid", valid, invalid);
+    if invalid > 0 && !command.remove {
+        println!("Run with --remove to delete invalid entries");
+    }
+    Ok(())
+}
+
+fn prune(cache: &dyn KernelRepository, command: PruneCommand) -> Result<(), DynError> {
+    let mut entries = cache.list_programs()?;
+    entries.sort_unstable_by_key(|entry| entry.last_used);
+    let mut to_remove = Vec::new();
+    let mut to_keep = Vec::new();
+    for entry in entries {
+        let too_old = match command.older_than {
+            Some(days) => age(entry.last_used) > Duration::from_secs(days * 24 * 60 * 60),
+            None => false,
+        };
+        let other_version = command.other_versions
+            && match entry.metadata {
+                Some(ref metadata) => metadata.git_hash != env!("VERGEN_GIT_SHA"),
+                None => true,
+            };
+        if too_old || other_version {
+            to_remove.push(entry);
+        } else {
+            to_keep.push(entry);
+        }
+    }
+    if let Some(max_size) = command.max_size {
+        let mut size = to_keep.iter().map(|entry| entry.size).sum::<u64>();
+        // Oldest first
+        let mut to_keep_iter = to_keep.into_iter();
+        while size > max_size {
+            match to_keep_iter.next() {
+                Some(entry) => {
+                    size -= entry.size;
+                    to_remove.push(entry);
+                }
+                None => break,
+            }
+        }
+    }
+    let removed_size = to_remove.iter().map(|entry| entry.size).sum::<u64>();
+    for entry in to_remove.iter() {
+        if command.dry_run {
+            println!("{}", entry.location);
+        } else {
+            cache.remove_program(entry)?;
+        }
+    }
+    println!(
+        "{} {} entries ({})",
+        if command.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        to_remove.len(),
+        format_size(removed_size)
+    );
+    Ok(())
+}
+
+// Corrupt entries are skipped, see verify
+fn export(cache: &dyn KernelRepository, command: ExportCommand) -> Result<(), DynError> {
+    let mut archive = BufWriter::new(File::create(&command.archive)?);
+    archive.write_all(ARCHIVE_MAGIC)?;
+    let mut exported = 0;
+    let mut result = Ok(());
+    cache.for_each_entry(&mut |metadata, binary| {
+        if result.is_err() {
+            return;
+        }
+        if let Some(ref device) = command.device {
+            if metadata.device.to_bytes() != device.as_bytes() {
+                return;
+            }
+        }
+        let content = encode_program(metadata, binary);
+        result = archive
+            .write_all(&(content.len() as u64).to_le_bytes())
+            .and_then(|()| archive.write_all(&content));
+        exported += 1;
+    })?;
+    result?;
+    archive.flush()?;
+    println!("Exported {} entries", exported);
+    Ok(())
+}
+
+fn import(cache: &dyn KernelRepository, command: ImportCommand) -> Result<(), DynError> {
+    let mut archive = BufReader::new(File::open(&command.archive)?);
+    let mut magic = [0u8; 8];
+    archive.read_exact(&mut magic)?;
+    if &magic != ARCHIVE_MAGIC {
+        return Err(format!("{} is not a ZLUDA cache archive", command.archive.display()).into());
+    }
+    let mut imported = 0;
+    let mut invalid = 0;
+    loop {
+        let mut length = [0u8; 8];
+        match archive.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        // Don't trust the length to allocate the whole entry upfront
+        let length = u64::from_le_bytes(length);
+        let mut content = Vec::new();
+        (&mut archive).take(length).read_to_end(&mut content)?;
+        if content.len() as u64 != length {
+            return Err(format!("{} is truncated", command.archive.display()).into());
+        }
+        match decode_entry(&content) {
+            Some((metadata, binary)) => {
+                cache.save_program(
+                    &metadata.hash,
+                    &metadata.compiler_version,
+                    &metadata.git_hash,
+                    &metadata.device,
+                    binary,
+                    &metadata.additional_parameters,
+                )?;
+                imported += 1;
+            }
+            None => invalid += 1,
+        }
+    }
+    println!("Imported {} entries, skipped {} invalid", imported, invalid);
+    Ok(())
+}
+
+fn short_git_hash(git_hash: &str) -> &str {
+    &git_hash[..8.min(git_hash.len())]
+}
+
+fn age(time: SystemTime) -> Duration {
+    SystemTime::now().duration_since(time).unwrap_or_default()
+}
+
+fn format_age(time: SystemTime) -> String {
+    let seconds = age(time).as_secs();
+    if seconds < 60 * 60 {
+        format!("{}m ago", seconds / 60)
+    } else if seconds < 24 * 60 * 60 {
+        format!("{}h ago", seconds / (60 * 60))
+    } else {
+        format!("{}d ago", seconds / (24 * 60 * 60))
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn migrate(cache_dir: PathBuf, command: MigrateCommand) -> Result<(), DynError> {
+    let source_backend = match command.to {
+        Backend::File => Backend::Sqlite,
+        Backend::Sqlite => Backend::File,
+    };
+    let database = SqliteRepository::database_path(&cache_dir);
+    if source_backend == Backend::Sqlite && !database.is_file() {
+        return Err(format!("{} does not exist", database.display()).into());
+    }
+    fs::create_dir_all(&cache_dir)?;
+    let source = source_backend.open(cache_dir.clone(), None)?;
+    let target = command.to.open(cache_dir.clone(), None)?;
+    let migrated = cache::migrate(&*source, &*target)?;
+    println!("Migrated {} entries", migrated);
+    if command.keep {
+        return Ok(());
+    }
+    drop(source);
+    match source_backend {
+        Backend::File => {
+            let source = FileRepository::new(cache_dir, None);
+            for entry in source.entries() {
+                source.remove_entry(&entry)?;
+            }
+        }
+        Backend::Sqlite => {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = database.clone().into_os_string();
+                path.push(suffix);
+                match fs::remove_file(path) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(err.into())
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,593 @@
+// Bounds-checked parsing of fatbins from untrusted input, e.g. files on disk.
+// Unlike FatbinModuleHandle, which trusts the sizes it finds in headers, it
+// never reads outside of the input and reports malformed data together with
+// its offset in the input
+use crate::{
+    AnyUInt, DecompressionFailure, FatbinCompression, FatbinFile, FatbinFileHeader,
+    FatbinFileHeaderFlags, FatbinFileKind, FatbinHeader, FatbincWrapper, UnexpectedFieldError,
+    AR_MAGIC, FATBINC_MAGIC, FATBINC_VERSION_V1, FATBINC_VERSION_V2, FATBIN_MAGIC, FATBIN_VERSION,
+    HOST_FATBIN_SECTIONS,
+};
+use std::{borrow::Cow, convert::TryInto, fmt::Display, mem, ptr};
+
+// Section with FatbincWrapper structures of a host object, one for every
+// fatbin registered with __cudaRegisterFatBinary
+pub const HOST_FATBINC_WRAPPER_SECTION: &str = ".nvFatBinSegment";
+
+// Optional part of the file header that follows `uncompressed_payload`
+const PTXAS_ARGS_OFFSET: usize = mem::size_of::<FatbinFileHeader>();
+const PTXAS_ARGS_HEADER_SIZE: usize = PTXAS_ARGS_OFFSET + 8;
+
+#[derive(Debug)]
+pub enum InspectErrorKind {
+    UnexpectedField(UnexpectedFieldError),
+    // Structure (or a region described by a header) does not fit in the input
+    OutOfBounds {
+        name: &'static str,
+        size: u64,
+        available: u64,
+    },
+    MalformedHostObject,
+    // ELF for the GPU (cubin), it has no fatbins
+    DeviceElf,
+    UnknownFormat,
+}
+
+#[derive(Debug)]
+pub struct InspectError {
+    pub offset: usize,
+    pub kind: InspectErrorKind,
+}
+
+impl InspectError {
+    fn unexpected_field(offset: usize, error: UnexpectedFieldError) -> Self {
+        Self {
+            offset,
+            kind: InspectErrorKind::UnexpectedField(error),
+        }
+    }
+
+    fn out_of_bounds(offset: usize, name: &'static str, size: u64, available: u64) -> Self {
+        Self {
+            offset,
+            kind: InspectErrorKind::OutOfBounds {
+                name,
+                size,
+                available,
+            },
+        }
+    }
+}
+
+impl Display for InspectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}: ", self.offset)?;
+        match &self.kind {
+            InspectErrorKind::UnexpectedField(error) => {
+                write!(f, "unexpected {} {}, expected ", error.name, error.observed)?;
+                for (index, expected) in error.expected.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " or ")?;
+                    }
+                    write!(f, "{}", expected)?;
+                }
+                Ok(())
+            }
+            InspectErrorKind::OutOfBounds {
+                name,
+                size,
+                available,
+            } => write!(
+                f,
+                "{} of {} bytes does not fit in the remaining {} bytes",
+                name, size, available
+            ),
+            InspectErrorKind::MalformedHostObject => write!(f, "malformed host object"),
+            InspectErrorKind::DeviceElf => {
+                write!(f, "device ELF (cubin), it contains no fatbins")
+            }
+            InspectErrorKind::UnknownFormat => {
+                write!(f, "not a fatbin, ELF host object or archive")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InspectError {}
+
+// Everything found in a single input file. All offsets are relative to the
+// start of the input
+pub struct InspectedInput {
+    pub fatbins: Vec<InspectedFatbin>,
+    pub wrappers: Vec<Result<InspectedWrapper, InspectError>>,
+    // Errors that prevented inspection of a part of the input, e.g. a
+    // malformed member of an archive
+    pub errors: Vec<InspectError>,
+}
+
+pub struct InspectedWrapper {
+    pub offset: usize,
+    pub archive_member: Option<String>,
+    pub version: u32,
+}
+
+pub struct InspectedFatbin {
+    pub offset: usize,
+    pub archive_member: Option<String>,
+    // None for a standalone fatbin file
+    pub section: Option<&'static str>,
+    pub header_size: u16,
+    pub files_size: u64,
+    // Files are chained by their sizes, so the first error ends the list
+    pub files: Vec<Result<InspectedFile, InspectError>>,
+}
+
+pub struct InspectedFile {
+    pub offset: usize,
+    pub kind: FatbinFileKind,
+    pub compression: FatbinCompression,
+    pub sm_version: u32,
+    // (major * 0x10000) + minor, only meaningful for PTX
+    pub ptx_version: u32,
+    pub flags: FatbinFileHeaderFlags,
+    pub header_size: u32,
+    pub padded_payload_size: u32,
+    pub payload_size: u32,
+    pub uncompressed_size: u64,
+    pub file_name: Option<String>,
+    pub ptxas_args: Option<String>,
+    // File header followed by the payload, 8-byte aligned copy of the input
+    data: Vec<u64>,
+}
+
+impl InspectedFile {
+    pub fn payload(&self) -> Result<Cow<'_, [u8]>, DecompressionFailure> {
+        // Header was validated and bounds of the payload checked in
+        // `inspect_file(...)`, decompressed data is owned and borrowed one
+        // points into `self.data`
+        unsafe {
+            let file = FatbinFile::try_new(&*self.data.as_ptr().cast::<FatbinFileHeader>())
+                .map_err(|_| DecompressionFailure)?;
+            file.get_or_decompress()
+        }
+    }
+}
+
+pub fn inspect(input: &[u8]) -> Result<InspectedInput, InspectError> {
+    let mut result = InspectedInput {
+        fatbins: Vec::new(),
+        wrappers: Vec::new(),
+        errors: Vec::new(),
+    };
+    if read_u32(input, 0) == Some(FATBIN_MAGIC) {
+        inspect_section(&mut result, input, 0, input.len(), None, None)?;
+    } else if input.starts_with(&goblin::elf::header::ELFMAG[..]) {
+        inspect_host_object(&mut result, input, 0, input.len(), None)?;
+    } else if input.starts_with(&AR_MAGIC) {
+        inspect_archive(&mut result, input)?;
+    } else {
+        return Err(InspectError {
+            offset: 0,
+            kind: InspectErrorKind::UnknownFormat,
+        });
+    }
+    Ok(result)
+}
+
+fn inspect_archive(result: &mut InspectedInput, input: &[u8]) -> Result<(), InspectError> {
+    let malformed = |offset| InspectError {
+        offset,
+        kind: InspectErrorKind::MalformedHostObject,
+    };
+    let parsed = goblin::archive::Archive::parse(input).map_err(|_| malformed(0))?;
+    for index in 0..parsed.len() {
+        let member = parsed.get_at(index).ok_or_else(|| malformed(0))?;
+        let start = member.offset as usize;
+        let end = start
+            .checked_add(member.size())
+            .filter(|end| *end <= input.len())
+            .ok_or_else(|| malformed(start))?;
+        // Archives might contain other things, e.g. symbol tables
+        if !input[start..end].starts_with(&goblin::elf::header::ELFMAG[..]) {
+            continue;
+        }
+        if let Err(error) =
+            inspect_host_object(result, input, start, end, Some(member.extended_name()))
+        {
+            result.errors.push(error);
+        }
+    }
+    Ok(())
+}
+
+fn inspect_host_object(
+    result: &mut InspectedInput,
+    input: &[u8],
+    start: usize,
+    end: usize,
+    archive_member: Option<&str>,
+) -> Result<(), InspectError> {
+    let elf = &input[start..end];
+    let parsed = goblin::elf::Elf::parse(elf).map_err(|_| InspectError {
+        offset: start,
+        kind: InspectErrorKind::MalformedHostObject,
+    })?;
+    if parsed.header.e_machine == goblin::elf::header::EM_CUDA {
+        return Err(InspectError {
+            offset: start,
+            kind: InspectErrorKind::DeviceElf,
+        });
+    }
+    for section in parsed.section_headers.iter() {
+        if section.sh_type == goblin::elf::section_header::SHT_NOBITS {
+            continue;
+        }
+        let name = match parsed.shdr_strtab.get_at(section.sh_name) {
+            Some(name) => name,
+            None => continue,
+        };
+        let section_start = start.saturating_add(section.sh_offset as usize);
+        let section_end = section_start.saturating_add(section.sh_size as usize);
+        if section_end > end {
+            result.errors.push(InspectError::out_of_bounds(
+                section_start,
+                "section",
+                section.sh_size,
+                end.saturating_sub(section_start) as u64,
+            ));
+            continue;
+        }
+        if name == HOST_FATBINC_WRAPPER_SECTION {
+            inspect_wrappers(result, input, section_start, section_end, archive_member);
+        } else if let Some(section_name) = HOST_FATBIN_SECTIONS.iter().find(|s| **s == name) {
+            if let Err(error) = inspect_section(
+                result,
+                input,
+                section_start,
+                section_end,
+                Some(*section_name),
+                archive_member,
+            ) {
+                result.errors.push(error);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Pointers of the wrappers are relocated at load time, only their magic
+// and version are meaningful in a file
+fn inspect_wrappers(
+    result: &mut InspectedInput,
+    input: &[u8],
+    start: usize,
+    end: usize,
+    archive_member: Option<&str>,
+) {
+    const WRAPPER_SIZE: usize = mem::size_of::<FatbincWrapper>();
+    let mut offset = start;
+    while offset + WRAPPER_SIZE <= end {
+        let magic = read_u32(input, offset).unwrap();
+        let version = read_u32(input, offset + 4).unwrap();
+        let wrapper = if magic != FATBINC_MAGIC {
+            Err(InspectError::unexpected_field(
+                offset,
+                UnexpectedFieldError {
+                    name: "FATBINC_MAGIC",
+                    expected: vec![AnyUInt::U32(FATBINC_MAGIC)],
+                    observed: AnyUInt::U32(magic),
+                },
+            ))
+        } else if version != FATBINC_VERSION_V1 && version != FATBINC_VERSION_V2 {
+            Err(InspectError::unexpected_field(
+                offset + 4,
+                UnexpectedFieldError {
+                    name: "FATBINC_VERSION",
+                    expected: vec![
+                        AnyUInt::U32(FATBINC_VERSION_V1),
+                        AnyUInt::U32(FATBINC_VERSION_V2),
+                    ],
+                    observed: AnyUInt::U32(version),
+                },
+            ))
+        } else {
+            Ok(InspectedWrapper {
+                offset,
+                archive_member: archive_member.map(ToString::to_string),
+                version,
+            })
+        };
+        result.wrappers.push(wrapper);
+        offset += WRAPPER_SIZE;
+    }
+}
+
+// Single section (or a fatbin file) can contain multiple fatbins laid out
+// one after another, every one of them starts at 8-byte boundary
+fn inspect_section(
+    result: &mut InspectedInput,
+    input: &[u8],
+    start: usize,
+    end: usize,
+    section: Option<&'static str>,
+    archive_member: Option<&str>,
+) -> Result<(), InspectError> {
+    const HEADER_SIZE: usize = mem::size_of::<FatbinHeader>();
+    let mut offset = start;
+    while offset < end {
+        // Sections are padded with zeros
+        if input[offset..end].iter().all(|byte| *byte == 0) {
+            break;
+        }
+        if end - offset < HEADER_SIZE {
+            return Err(InspectError::out_of_bounds(
+                offset,
+                "fatbin header",
+                HEADER_SIZE as u64,
+                (end - offset) as u64,
+            ));
+        }
+        let magic = read_u32(input, offset).unwrap();
+        if magic != FATBIN_MAGIC {
+            return Err(InspectError::unexpected_field(
+                offset,
+                UnexpectedFieldError {
+                    name: "FATBIN_MAGIC",
+                    expected: vec![AnyUInt::U32(FATBIN_MAGIC)],
+                    observed: AnyUInt::U32(magic),
+                },
+            ));
+        }
+        let version = read_u16(input, offset + 4).unwrap();
+        if version != FATBIN_VERSION {
+            return Err(InspectError::unexpected_field(
+                offset + 4,
+                UnexpectedFieldError {
+                    name: "FATBIN_VERSION",
+                    expected: vec![AnyUInt::U16(FATBIN_VERSION)],
+                    observed: AnyUInt::U16(version),
+                },
+            ));
+        }
+        let header_size = read_u16(input, offset + 6).unwrap();
+        let files_size = read_u64(input, offset + 8).unwrap();
+        let available = (end - offset) as u64;
+        // Size that overflows does not fit either
+        let fatbin_size = (header_size as u64)
+            .checked_add(files_size)
+            .unwrap_or(u64::MAX);
+        if (header_size as usize) < HEADER_SIZE || fatbin_size > available {
+            return Err(InspectError::out_of_bounds(
+                offset,
+                "fatbin",
+                fatbin_size,
+                available,
+            ));
+        }
+        let files_start = offset + header_size as usize;
+        let files_end = offset + fatbin_size as usize;
+        result.fatbins.push(InspectedFatbin {
+            offset,
+            archive_member: archive_member.map(ToString::to_string),
+            section,
+            header_size,
+            files_size,
+            files: inspect_files(input, files_start, files_end),
+        });
+        offset = start + ((files_end - start + 7) & !7);
+    }
+    Ok(())
+}
+
+fn inspect_files(
+    input: &[u8],
+    start: usize,
+    end: usize,
+) -> Vec<Result<InspectedFile, InspectError>> {
+    let mut files = Vec::new();
+    let mut offset = start;
+    while offset < end {
+        match inspect_file(input, offset, end) {
+            Ok((file, size)) => {
+                files.push(Ok(file));
+                offset += size;
+            }
+            Err(error) => {
+                files.push(Err(error));
+                break;
+            }
+        }
+    }
+    files
+}
+
+fn inspect_file(
+    input: &[u8],
+    offset: usize,
+    end: usize,
+) -> Result<(InspectedFile, usize), InspectError> {
+    const HEADER_SIZE: usize = mem::size_of::<FatbinFileHeader>();
+    let available = (end - offset) as u64;
+    if available < HEADER_SIZE as u64 {
+        return Err(InspectError::out_of_bounds(
+            offset,
+            "fatbin file header",
+            HEADER_SIZE as u64,
+            available,
+        ));
+    }
+    // Only sizes are read here, everything else is read from the aligned copy
+    let header_size = read_u32(input, offset + 4).unwrap();
+    let padded_payload_size = read_u32(input, offset + 8).unwrap();
+    if (header_size as usize) < HEADER_SIZE {
+        return Err(InspectError::out_of_bounds(
+            offset,
+            "fatbin file header",
+            HEADER_SIZE as u64,
+            header_size as u64,
+        ));
+    }
+    let file_size = header_size as u64 + padded_payload_size as u64;
+    if file_size > available {
+        return Err(InspectError::out_of_bounds(
+            offset,
+            "fatbin file",
+            file_size,
+            available,
+        ));
+    }
+    let file_size = file_size as usize;
+    let mut data = vec![0u64; (file_size + 7) / 8];
+    unsafe {
+        ptr::copy_nonoverlapping(
+            input[offset..].as_ptr(),
+            data.as_mut_ptr().cast::<u8>(),
+            file_size,
+        )
+    };
+    let header = unsafe { &*data.as_ptr().cast::<FatbinFileHeader>() };
+    let file = unsafe { FatbinFile::try_new(header) }
+        .map_err(|error| InspectError::unexpected_field(offset, error))?;
+    // Compressed payloads are read up to `payload_size`
+    if file.compression != FatbinCompression::None && header.payload_size > padded_payload_size {
+        return Err(InspectError::out_of_bounds(
+            offset + header_size as usize,
+            "compressed payload",
+            header.payload_size as u64,
+            padded_payload_size as u64,
+        ));
+    }
+    let bytes = &input[offset..offset + file_size];
+    let file_name = read_string(bytes, header.file_name_offset, header.file_name_len);
+    let ptxas_args = if header_size as usize >= PTXAS_ARGS_HEADER_SIZE {
+        read_string(
+            bytes,
+            read_u32(bytes, PTXAS_ARGS_OFFSET).unwrap(),
+            read_u32(bytes, PTXAS_ARGS_OFFSET + 4).unwrap(),
+        )
+    } else {
+        None
+    };
+    let inspected = InspectedFile {
+        offset,
+        kind: file.kind,
+        compression: file.compression,
+        sm_version: header.sm_version,
+        ptx_version: header.ptx_version,
+        flags: FatbinFileHeaderFlags::from_bits_retain(header.flags.bits()),
+        header_size,
+        padded_payload_size,
+        payload_size: header.payload_size,
+        uncompressed_size: header.uncompressed_payload,
+        file_name,
+        ptxas_args,
+        data,
+    };
+    Ok((inspected, file_size))
+}
+
+// Strings are stored inside the file (usually in its header) at an offset
+// relative to the start of the file header
+fn read_string(file: &[u8], offset: u32, len: u32) -> Option<String> {
+    if len == 0 {
+        return None;
+    }
+    let start = offset as usize;
+    let bytes = file.get(start..start.checked_add(len as usize)?)?;
+    let bytes = match memchr::memchr(0, bytes) {
+        Some(nul) => &bytes[..nul],
+        None => bytes,
+    };
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn read_u16(input: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        input.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(input: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        input.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(input: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        input.get(offset..offset.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, InspectErrorKind};
+    use crate::{FatbinCompression, FatbinFileKind};
+
+    #[test]
+    fn inspect_compressed_fatbin() {
+        let fatbin = include_bytes!("test/compressed.fatbin");
+        let expected_ptx = include_bytes!("test/add.ptx");
+        let input = inspect(fatbin).unwrap();
+        assert_eq!(input.fatbins.len(), 1);
+        let files = input.fatbins[0]
+            .files
+            .iter()
+            .map(|file| file.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| (file.kind, file.compression, file.sm_version))
+                .collect::<Vec<_>>(),
+            vec![
+                (FatbinFileKind::Ptx, FatbinCompression::Zlib, 52),
+                (FatbinFileKind::Ptx, FatbinCompression::Lz4, 61),
+                (FatbinFileKind::Ptx, FatbinCompression::Zstd, 75)
+            ]
+        );
+        for file in files {
+            assert_eq!(&*file.payload().unwrap(), &expected_ptx[..]);
+        }
+    }
+
+    #[test]
+    fn inspect_truncated_fatbin() {
+        let fatbin = include_bytes!("test/compressed.fatbin");
+        let error = inspect(&fatbin[..fatbin.len() / 2]).err().unwrap();
+        assert_eq!(error.offset, 0);
+        assert!(matches!(
+            error.kind,
+            InspectErrorKind::OutOfBounds { name: "fatbin", .. }
+        ));
+    }
+
+    #[test]
+    fn inspect_overflowing_fatbin_size() {
+        let mut fatbin = include_bytes!("test/compressed.fatbin").to_vec();
+        fatbin[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = inspect(&fatbin).err().unwrap();
+        assert_eq!(error.offset, 0);
+        assert!(matches!(
+            error.kind,
+            InspectErrorKind::OutOfBounds {
+                name: "fatbin",
+                size: u64::MAX,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn inspect_corrupted_file_header() {
+        let mut fatbin = include_bytes!("test/compressed.fatbin").to_vec();
+        // Kind of the first file
+        fatbin[0x10] = 0x7;
+        let input = inspect(&fatbin).unwrap();
+        let files = &input.fatbins[0].files;
+        assert_eq!(files.len(), 1);
+        let error = files[0].as_ref().err().unwrap();
+        assert_eq!(error.offset, 0x10);
+        assert!(matches!(error.kind, InspectErrorKind::UnexpectedField(_)));
+    }
+}
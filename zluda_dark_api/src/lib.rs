@@ -7,6 +7,7 @@ use hip_common::zluda_ext::CudaResult;
 use paste::paste;
 use std::{
     borrow::Cow,
+    convert::TryInto,
     ffi::c_void,
     fmt::Display,
     mem,
@@ -14,6 +15,8 @@ use std::{
     ptr, slice,
 };
 
+pub mod inspect;
+
 macro_rules! dark_api_fn_decl {
     (SIZE_OF) => { };
     (NULL) => { };
@@ -476,6 +479,8 @@ bitflags! {
         const OptMask = 0x0000000000000f00;
         const CompressedOld = 0x0000000000001000;
         const CompressedNew = 0x0000000000002000;
+        // Default compression mode since CUDA 12.4
+        const CompressedZstd = 0x0000000000008000;
 
         const _ = !0;
     }
@@ -679,6 +684,100 @@ impl Iterator for FatbinModuleFiles {
     }
 }
 
+// PTX of a fatbin module sorted by descending sm version, skips files that
+// fail to decompress or are not valid UTF-8
+pub fn extract_ptx(files: FatbinModuleFiles) -> Vec<(Cow<'static, str>, u32)> {
+    let mut ptx_files = files
+        .filter_map(|file| {
+            file.ok()
+                .map(|file| {
+                    if file.kind == FatbinFileKind::Ptx {
+                        unsafe { file.get_or_decompress() }
+                            .ok()
+                            .map(|f| {
+                                let text = match f {
+                                    Cow::Borrowed(slice) => {
+                                        Cow::Borrowed(std::str::from_utf8(slice).ok()?)
+                                    }
+                                    Cow::Owned(vec) => Cow::Owned(String::from_utf8(vec).ok()?),
+                                };
+                                Some((text, file.sm_version))
+                            })
+                            .flatten()
+                    } else {
+                        None
+                    }
+                })
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+    ptx_files.sort_unstable_by_key(|(_, sm_version)| std::cmp::Reverse(*sm_version));
+    ptx_files
+}
+
+// Which of the PTX modules embedded in a multi-arch fatbin gets compiled,
+// set with ZLUDA_PTX_SELECTION. Newer PTX is not always better: it might use
+// instructions we don't support yet, while older PTX of the same kernel does not
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PtxSelection {
+    // Only the PTX with the highest sm version
+    Highest,
+    // Only the PTX with the lowest sm version
+    Lowest,
+    // Only the PTX for the given sm version. If the fatbin does not have one,
+    // candidates are tried like with FirstThatCompiles
+    Exact(u32),
+    // Tries candidates starting with the highest sm version, the choice is
+    // saved in the kernel cache, so later runs start with the working one
+    FirstThatCompiles,
+}
+
+impl PtxSelection {
+    pub fn from_env() -> Self {
+        match std::env::var("ZLUDA_PTX_SELECTION") {
+            Ok(value) => Self::parse(&value).unwrap_or(Self::FirstThatCompiles),
+            Err(_) => Self::FirstThatCompiles,
+        }
+    }
+
+    // Accepts "highest", "lowest", "first" and sm version as "sm_61" or "61"
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        Some(match &*value {
+            "highest" => Self::Highest,
+            "lowest" => Self::Lowest,
+            "first" => Self::FirstThatCompiles,
+            sm_version => Self::Exact(
+                sm_version
+                    .strip_prefix("sm_")
+                    .unwrap_or(sm_version)
+                    .parse()
+                    .ok()?,
+            ),
+        })
+    }
+
+    // Candidates in the order they should be tried, `ptx_files` must be
+    // sorted by descending sm version, like returned by `extract_ptx`
+    pub fn select<T>(self, mut ptx_files: Vec<(T, u32)>) -> Vec<(T, u32)> {
+        match self {
+            PtxSelection::Highest => ptx_files.truncate(1),
+            PtxSelection::Lowest => {
+                if let Some(lowest) = ptx_files.pop() {
+                    ptx_files = vec![lowest];
+                }
+            }
+            PtxSelection::Exact(sm_version) => {
+                if let Some(index) = ptx_files.iter().position(|(_, sm)| *sm == sm_version) {
+                    ptx_files = vec![ptx_files.swap_remove(index)];
+                }
+            }
+            PtxSelection::FirstThatCompiles => {}
+        }
+        ptx_files
+    }
+}
+
 pub enum FatbinModule {
     Elf(*const u8),
     Files(FatbinModuleFiles),
@@ -694,10 +793,12 @@ pub struct FatbinFile {
     uncompressed_payload: usize,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum FatbinCompression {
     None,
     Zlib,
     Lz4,
+    Zstd,
 }
 
 impl FatbinFile {
@@ -735,6 +836,11 @@ impl FatbinFile {
             .contains(FatbinFileHeaderFlags::CompressedNew)
         {
             FatbinCompression::Lz4
+        } else if fatbin_file
+            .flags
+            .contains(FatbinFileHeaderFlags::CompressedZstd)
+        {
+            FatbinCompression::Zstd
         } else {
             FatbinCompression::None
         };
@@ -769,6 +875,15 @@ impl FatbinFile {
                     None => Err(DecompressionFailure),
                 }
             }
+            FatbinCompression::Zstd => {
+                let mut decompressed = self
+                    .decompress_kernel_module_zstd()
+                    .ok_or(DecompressionFailure)?;
+                if self.kind == FatbinFileKind::Ptx {
+                    decompressed.pop(); // remove trailing zero
+                }
+                Ok(Cow::Owned(decompressed))
+            }
             FatbinCompression::Zlib => {
                 let compressed =
                     std::slice::from_raw_parts(self.data.cast(), self.padded_payload_size);
@@ -809,11 +924,168 @@ impl FatbinFile {
             }
         }
     }
+
+    unsafe fn decompress_kernel_module_zstd(&self) -> Option<Vec<u8>> {
+        let compressed = slice::from_raw_parts(self.data, self.payload_size);
+        // zstd frames written by nvcc carry their content size, header value is only a fallback
+        let decompressed_size = match zstd_safe::get_frame_content_size(compressed) {
+            Ok(Some(size)) => size as usize,
+            _ => self.uncompressed_payload,
+        };
+        if decompressed_size > Self::MAX_MODULE_DECOMPRESSION_BOUND {
+            return None;
+        }
+        let mut decompressed_vec = Vec::with_capacity(decompressed_size);
+        zstd_safe::decompress(&mut decompressed_vec, compressed).ok()?;
+        Some(decompressed_vec)
+    }
 }
 
 #[derive(Debug)]
 pub struct DecompressionFailure;
 
+pub const AR_MAGIC: [u8; 8] = *b"!<arch>\n";
+// Sections of host objects where nvcc places fatbins. __nv_relfatbin is used
+// for relocatable device code (-rdc=true)
+pub const HOST_FATBIN_SECTIONS: [&'static str; 2] = [".nv_fatbin", "__nv_relfatbin"];
+
+#[derive(Debug)]
+pub struct MalformedHostObject;
+
+// Fatbins embedded in host objects, either standalone or as members of a static
+// library archive (what CUDA receives with CU_JIT_INPUT_LIBRARY).
+// Section data inside an archive is generally not aligned, so every fatbin is
+// copied into an owned, 8-byte aligned buffer
+pub struct HostObjectFatbins {
+    fatbins: Vec<Vec<u64>>,
+    // Fatbins from __nv_relfatbin, device code of relocatable objects that
+    // only takes part in linking
+    relocatable: Vec<bool>,
+}
+
+impl HostObjectFatbins {
+    pub fn from_archive(archive: &[u8]) -> Result<Self, MalformedHostObject> {
+        let parsed = goblin::archive::Archive::parse(archive).map_err(|_| MalformedHostObject)?;
+        let mut result = Self {
+            fatbins: Vec::new(),
+            relocatable: Vec::new(),
+        };
+        for index in 0..parsed.len() {
+            let member = parsed.get_at(index).ok_or(MalformedHostObject)?;
+            let start = member.offset as usize;
+            let end = start
+                .checked_add(member.size())
+                .ok_or(MalformedHostObject)?;
+            let member_data = archive.get(start..end).ok_or(MalformedHostObject)?;
+            // Archives might contain other things, e.g. symbol tables or objects
+            // for other architectures, we are only interested in ELF host objects
+            if member_data.get(..4) != Some(&goblin::elf::header::ELFMAG[..]) {
+                continue;
+            }
+            result.push_host_object(member_data)?;
+        }
+        Ok(result)
+    }
+
+    pub fn from_host_object(elf: &[u8]) -> Result<Self, MalformedHostObject> {
+        let mut result = Self {
+            fatbins: Vec::new(),
+            relocatable: Vec::new(),
+        };
+        result.push_host_object(elf)?;
+        Ok(result)
+    }
+
+    fn push_host_object(&mut self, elf: &[u8]) -> Result<(), MalformedHostObject> {
+        let parsed = goblin::elf::Elf::parse(elf).map_err(|_| MalformedHostObject)?;
+        // Device ELF (cubin) contains only SASS, nothing we can use
+        if parsed.header.e_machine == goblin::elf::header::EM_CUDA {
+            return Ok(());
+        }
+        for section in parsed.section_headers.iter() {
+            if section.sh_type == goblin::elf::section_header::SHT_NOBITS {
+                continue;
+            }
+            let relocatable = match parsed.shdr_strtab.get_at(section.sh_name) {
+                Some(name) if HOST_FATBIN_SECTIONS.contains(&name) => {
+                    name == HOST_FATBIN_SECTIONS[1]
+                }
+                _ => continue,
+            };
+            let start = section.sh_offset as usize;
+            let end = start
+                .checked_add(section.sh_size as usize)
+                .ok_or(MalformedHostObject)?;
+            let section_data = elf.get(start..end).ok_or(MalformedHostObject)?;
+            self.push_section(section_data, relocatable);
+        }
+        Ok(())
+    }
+
+    // Single section can contain multiple fatbins laid out one after another.
+    // Parsing stops at the first fatbin with a nonsensical size: fatbins are
+    // later walked using their declared size, so it must fit in the section
+    fn push_section(&mut self, mut section: &[u8], relocatable: bool) {
+        const HEADER_SIZE: usize = mem::size_of::<FatbinHeader>();
+        while section.len() >= HEADER_SIZE {
+            let magic = u32::from_le_bytes(section[0..4].try_into().unwrap());
+            if magic != FATBIN_MAGIC {
+                break;
+            }
+            let header_size = u16::from_le_bytes(section[6..8].try_into().unwrap()) as usize;
+            let files_size = u64::from_le_bytes(section[8..16].try_into().unwrap()) as usize;
+            let fatbin_size = match header_size.checked_add(files_size) {
+                Some(size) if size >= HEADER_SIZE && size <= section.len() => size,
+                _ => break,
+            };
+            let mut aligned = vec![0u64; (fatbin_size + 7) / 8];
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    section.as_ptr(),
+                    aligned.as_mut_ptr().cast::<u8>(),
+                    fatbin_size,
+                )
+            };
+            self.fatbins.push(aligned);
+            self.relocatable.push(relocatable);
+            let next = (fatbin_size + 7) & !7;
+            section = section.get(next..).unwrap_or(&[]);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fatbins.is_empty()
+    }
+
+    // Returned handles (and files decompressed from them) point into this object
+    // and must not outlive it
+    pub fn modules(&self) -> impl Iterator<Item = FatbinModuleHandle> + '_ {
+        self.fatbins
+            .iter()
+            .map(|fatbin| FatbinModuleHandle(fatbin.as_ptr().cast()))
+    }
+
+    // Fatbins an executable or a shared library registers at startup with
+    // __cudaRegisterFatBinary. Same lifetime rules as for `modules()`
+    pub fn loadable_fatbins(&self) -> impl Iterator<Item = CudaFatbin> + '_ {
+        self.fatbins
+            .iter()
+            .zip(self.relocatable.iter())
+            .filter(|(_, relocatable)| !**relocatable)
+            .map(|(fatbin, _)| unsafe { CudaFatbin::from_header(fatbin.as_ptr().cast()) })
+    }
+
+    // Device code from __nv_relfatbin, what a fatbin registered by a device-linked
+    // (-rdc=true) program lists as its pre-link modules
+    pub fn relocatable_modules(&self) -> impl Iterator<Item = FatbinModuleHandle> + '_ {
+        self.fatbins
+            .iter()
+            .zip(self.relocatable.iter())
+            .filter(|(_, relocatable)| **relocatable)
+            .map(|(fatbin, _)| FatbinModuleHandle(fatbin.as_ptr().cast()))
+    }
+}
+
 pub fn anti_zluda_hash<F: FnMut(u32) -> AntiZludaHashInputDevice>(
     return_known_value: bool,
     input: AntiZludaHashInput,
@@ -1122,10 +1394,55 @@ mod tests {
     use cuda_types::CUuuid;
 
     use crate::{
-        anti_zluda_hash_impl, anti_zluda_hash_round_v1, AntiZludaHashInput,
-        AntiZludaHashInputDevice,
+        anti_zluda_hash_impl, anti_zluda_hash_round_v1, extract_ptx, AntiZludaHashInput,
+        AntiZludaHashInputDevice, CudaFatbin, FatbinCompression, FatbinFileKind, FatbinModule,
+        HostObjectFatbins, PtxSelection, FATBIN_FILE_HEADER_KIND_PTX,
+        FATBIN_FILE_HEADER_VERSION_CURRENT, FATBIN_MAGIC,
     };
 
+    fn fatbin_header(header_size: u16, files_size: u64) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&FATBIN_MAGIC.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&header_size.to_le_bytes());
+        header.extend_from_slice(&files_size.to_le_bytes());
+        header
+    }
+
+    fn push_section(section: &[u8]) -> usize {
+        let mut fatbins = HostObjectFatbins {
+            fatbins: Vec::new(),
+            relocatable: Vec::new(),
+        };
+        fatbins.push_section(section, false);
+        fatbins.fatbins.len()
+    }
+
+    #[test]
+    fn push_section_accepts_consecutive_fatbins() {
+        let mut section = fatbin_header(16, 8);
+        section.extend_from_slice(&[0; 8]);
+        section.extend(fatbin_header(16, 0));
+        assert_eq!(push_section(&section), 2);
+    }
+
+    #[test]
+    fn push_section_rejects_zero_size_header() {
+        assert_eq!(push_section(&fatbin_header(0, 0)), 0);
+    }
+
+    #[test]
+    fn push_section_rejects_oversized_header() {
+        assert_eq!(push_section(&fatbin_header(16, u64::MAX)), 0);
+    }
+
+    #[test]
+    fn push_section_rejects_truncated_fatbin() {
+        let mut section = fatbin_header(16, 8);
+        section.extend_from_slice(&[0; 4]);
+        assert_eq!(push_section(&section), 0);
+    }
+
     #[test]
     fn anti_zluda_hash_round_sample() {
         let mut input: [u8; 66] = [
@@ -1173,4 +1490,122 @@ mod tests {
         let result = anti_zluda_hash_impl(false, input, dev_getter, process_id, thread_id);
         assert_eq!(result, 0xEAF1313342BFCD84A7C34628F214707A);
     }
+
+    #[test]
+    fn decompress_all_fatbin_compressions() {
+        let fatbin = include_bytes!("test/compressed.fatbin");
+        let expected_ptx = include_bytes!("test/add.ptx");
+        // Fatbin headers must be 8-byte aligned
+        let mut aligned = vec![0u64; (fatbin.len() + 7) / 8];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                fatbin.as_ptr(),
+                aligned.as_mut_ptr().cast::<u8>(),
+                fatbin.len(),
+            )
+        };
+        let module = match unsafe { CudaFatbin::from_header(aligned.as_ptr().cast()) } {
+            CudaFatbin::Version1(module) => unsafe { module.get() }.unwrap(),
+            CudaFatbin::Version2 { .. } => panic!(),
+        };
+        let files = match module {
+            FatbinModule::Files(files) => files,
+            FatbinModule::Elf(_) => panic!(),
+        };
+        let mut compressions = Vec::new();
+        for file in files {
+            let file = file.unwrap();
+            assert_eq!(file.kind, FatbinFileKind::Ptx);
+            let ptx = unsafe { file.get_or_decompress() }.unwrap();
+            assert_eq!(&*ptx, &expected_ptx[..]);
+            compressions.push((file.compression, file.sm_version));
+        }
+        assert_eq!(
+            compressions,
+            vec![
+                (FatbinCompression::Zlib, 52),
+                (FatbinCompression::Lz4, 61),
+                (FatbinCompression::Zstd, 75)
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_ptx_keeps_envreg() {
+        // Grid sync from cooperative groups reads the barrier workspace address
+        // from %envreg1 and %envreg2
+        let ptx = b".version 6.5\n.target sm_30\n.address_size 64\n\n.visible .entry grid_sync()\n{\n    .reg .u32 ws;\n    mov.u32 ws, %envreg1;\n    ret;\n}\n";
+        let mut file = Vec::new();
+        file.extend_from_slice(&FATBIN_FILE_HEADER_KIND_PTX.to_le_bytes());
+        file.extend_from_slice(&FATBIN_FILE_HEADER_VERSION_CURRENT.to_le_bytes());
+        // header_size, padded_payload_size, unknown0, payload_size, unknown1
+        for field in [64, ptx.len() as u32, 0, ptx.len() as u32, 0] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+        // ptx_version, sm_version
+        file.extend_from_slice(&0x10005u32.to_le_bytes());
+        file.extend_from_slice(&30u32.to_le_bytes());
+        file.resize(64, 0);
+        file.extend_from_slice(ptx);
+        let mut fatbin = fatbin_header(16, file.len() as u64);
+        fatbin.extend(file);
+        let mut aligned = vec![0u64; (fatbin.len() + 7) / 8];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                fatbin.as_ptr(),
+                aligned.as_mut_ptr().cast::<u8>(),
+                fatbin.len(),
+            )
+        };
+        let module = match unsafe { CudaFatbin::from_header(aligned.as_ptr().cast()) } {
+            CudaFatbin::Version1(module) => unsafe { module.get() }.unwrap(),
+            CudaFatbin::Version2 { .. } => panic!(),
+        };
+        let files = match module {
+            FatbinModule::Files(files) => files,
+            FatbinModule::Elf(_) => panic!(),
+        };
+        let extracted = extract_ptx(files);
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].0.as_bytes(), &ptx[..]);
+        assert_eq!(extracted[0].1, 30);
+    }
+
+    #[test]
+    fn ptx_selection_parse() {
+        assert_eq!(PtxSelection::parse("highest"), Some(PtxSelection::Highest));
+        assert_eq!(PtxSelection::parse(" Lowest "), Some(PtxSelection::Lowest));
+        assert_eq!(
+            PtxSelection::parse("first"),
+            Some(PtxSelection::FirstThatCompiles)
+        );
+        assert_eq!(PtxSelection::parse("sm_61"), Some(PtxSelection::Exact(61)));
+        assert_eq!(PtxSelection::parse("75"), Some(PtxSelection::Exact(75)));
+        assert_eq!(PtxSelection::parse("sm_xx"), None);
+    }
+
+    #[test]
+    fn ptx_selection_order() {
+        let candidates = vec![("c", 80), ("b", 61), ("a", 52)];
+        assert_eq!(
+            PtxSelection::Highest.select(candidates.clone()),
+            vec![("c", 80)]
+        );
+        assert_eq!(
+            PtxSelection::Lowest.select(candidates.clone()),
+            vec![("a", 52)]
+        );
+        assert_eq!(
+            PtxSelection::Exact(61).select(candidates.clone()),
+            vec![("b", 61)]
+        );
+        assert_eq!(
+            PtxSelection::Exact(70).select(candidates.clone()),
+            candidates
+        );
+        assert_eq!(
+            PtxSelection::FirstThatCompiles.select(candidates.clone()),
+            candidates
+        );
+    }
 }
@@ -0,0 +1,266 @@
+use argh::FromArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zluda_dark_api::inspect::{self, InspectedFatbin, InspectedFile, InspectedInput};
+use zluda_dark_api::{FatbinCompression, FatbinFileHeaderFlags, FatbinFileKind};
+
+type DynError = Box<dyn std::error::Error>;
+
+#[derive(FromArgs)]
+/// Inspect CUDA fatbins, either standalone or embedded in executables, shared
+/// libraries, object files and static libraries
+struct Arguments {
+    #[argh(subcommand)]
+    command: Subcommand,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Subcommand {
+    List(ListCommand),
+    Extract(ExtractCommand),
+}
+
+#[derive(FromArgs)]
+/// List fatbins and files inside them
+#[argh(subcommand, name = "list")]
+struct ListCommand {
+    /// fatbin or host ELF file
+    #[argh(positional)]
+    input: PathBuf,
+}
+
+#[derive(FromArgs)]
+/// Write (decompressed) PTX and ELF files from fatbins
+#[argh(subcommand, name = "extract")]
+struct ExtractCommand {
+    /// fatbin or host ELF file
+    #[argh(positional)]
+    input: PathBuf,
+    /// output directory, defaults to the current directory
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+    /// only extract files of this kind: ptx or elf
+    #[argh(option, from_str_fn(parse_kind))]
+    kind: Option<FatbinFileKind>,
+    /// only extract files for this SM version (e.g. 86)
+    #[argh(option)]
+    sm: Option<u32>,
+}
+
+fn main() {
+    let args: Arguments = argh::from_env();
+    let result = match args.command {
+        Subcommand::List(command) => list(command),
+        Subcommand::Extract(command) => extract(command),
+    };
+    match result {
+        Ok(true) => {}
+        // Errors were already reported
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_kind(value: &str) -> Result<FatbinFileKind, String> {
+    match &*value.to_ascii_lowercase() {
+        "ptx" => Ok(FatbinFileKind::Ptx),
+        "elf" => Ok(FatbinFileKind::Elf),
+        _ => Err(format!("invalid kind: {}", value)),
+    }
+}
+
+fn read_input(path: &Path) -> Result<InspectedInput, DynError> {
+    let input = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    inspect::inspect(&input).map_err(|err| format!("{}: {}", path.display(), err).into())
+}
+
+// Reports errors found in the input, returns true if there were none
+fn report_errors(path: &Path, input: &InspectedInput) -> bool {
+    let mut success = true;
+    let wrapper_errors = input
+        .wrappers
+        .iter()
+        .filter_map(|wrapper| wrapper.as_ref().err());
+    let file_errors = input
+        .fatbins
+        .iter()
+        .flat_map(|fatbin| fatbin.files.iter())
+        .filter_map(|file| file.as_ref().err());
+    for err in input.errors.iter().chain(wrapper_errors).chain(file_errors) {
+        eprintln!("{}: {}", path.display(), err);
+        success = false;
+    }
+    success
+}
+
+fn list(command: ListCommand) -> Result<bool, DynError> {
+    let input = read_input(&command.input)?;
+    for wrapper in input
+        .wrappers
+        .iter()
+        .filter_map(|wrapper| wrapper.as_ref().ok())
+    {
+        println!(
+            "wrapper at {:#x}{}: version {}",
+            wrapper.offset,
+            describe_member(&wrapper.archive_member),
+            wrapper.version
+        );
+    }
+    for (index, fatbin) in input.fatbins.iter().enumerate() {
+        println!("{}", describe_fatbin(index, fatbin));
+        println!(
+            "  {:>10} {:<4} {:>6} {:>7} {:<11} {:>10} {:>12} {:<10} {}",
+            "OFFSET", "KIND", "ARCH", "PTX", "COMPRESSION", "SIZE", "UNCOMPRESSED", "FLAGS", "NAME"
+        );
+        for file in fatbin.files.iter().filter_map(|file| file.as_ref().ok()) {
+            println!(
+                "  {:>#10x} {:<4} {:>6} {:>7} {:<11} {:>10} {:>12} {:<10} {}",
+                file.offset,
+                file.kind.file_extension(),
+                format!("sm_{}", file.sm_version),
+                describe_ptx_version(file),
+                describe_compression(file.compression),
+                file.payload_size,
+                file.uncompressed_size,
+                describe_flags(&file.flags),
+                file.file_name.as_deref().unwrap_or("")
+            );
+            if let Some(ref ptxas_args) = file.ptxas_args {
+                println!("  {:>10} ptxas arguments: {}", "", ptxas_args);
+            }
+        }
+    }
+    if input.fatbins.is_empty() {
+        println!("{}: no fatbins found", command.input.display());
+    }
+    Ok(report_errors(&command.input, &input))
+}
+
+fn describe_fatbin(index: usize, fatbin: &InspectedFatbin) -> String {
+    let section = match fatbin.section {
+        Some(section) => format!(" in {}", section),
+        None => String::new(),
+    };
+    format!(
+        "fatbin #{} at {:#x}{}{}: header {} bytes, files {} bytes",
+        index,
+        fatbin.offset,
+        section,
+        describe_member(&fatbin.archive_member),
+        fatbin.header_size,
+        fatbin.files_size
+    )
+}
+
+fn describe_member(archive_member: &Option<String>) -> String {
+    match archive_member {
+        Some(member) => format!(" ({})", member),
+        None => String::new(),
+    }
+}
+
+fn describe_ptx_version(file: &InspectedFile) -> String {
+    match file.kind {
+        FatbinFileKind::Ptx => format!("{}.{}", file.ptx_version >> 16, file.ptx_version & 0xffff),
+        FatbinFileKind::Elf | FatbinFileKind::Archive => "-".to_string(),
+    }
+}
+
+fn describe_compression(compression: FatbinCompression) -> &'static str {
+    match compression {
+        FatbinCompression::None => "none",
+        FatbinCompression::Zlib => "zlib",
+        FatbinCompression::Lz4 => "lz4",
+        FatbinCompression::Zstd => "zstd",
+    }
+}
+
+fn describe_flags(flags: &FatbinFileHeaderFlags) -> String {
+    let mut result = Vec::new();
+    if flags.contains(FatbinFileHeaderFlags::Is64Bit) {
+        result.push("64");
+    }
+    if flags.contains(FatbinFileHeaderFlags::Debug) {
+        result.push("debug");
+    }
+    if flags.contains(FatbinFileHeaderFlags::Linux) {
+        result.push("linux");
+    }
+    if flags.contains(FatbinFileHeaderFlags::Mac) {
+        result.push("mac");
+    }
+    if flags.contains(FatbinFileHeaderFlags::Windows) {
+        result.push("windows");
+    }
+    if result.is_empty() {
+        "-".to_string()
+    } else {
+        result.join(",")
+    }
+}
+
+fn extract(command: ExtractCommand) -> Result<bool, DynError> {
+    let input = read_input(&command.input)?;
+    let output = command.output.unwrap_or_else(|| PathBuf::from("."));
+    fs::create_dir_all(&output)?;
+    let stem = command
+        .input
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut success = report_errors(&command.input, &input);
+    for (fatbin_index, fatbin) in input.fatbins.iter().enumerate() {
+        for (file_index, file) in fatbin.files.iter().enumerate() {
+            let file = match file {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            if command.kind.map_or(false, |kind| kind != file.kind)
+                || command.sm.map_or(false, |sm| sm != file.sm_version)
+            {
+                continue;
+            }
+            let mut path = output.clone();
+            path.push(format!(
+                "{}.{}.{}.sm_{}.{}",
+                stem,
+                fatbin_index,
+                file_index,
+                file.sm_version,
+                file.kind.file_extension()
+            ));
+            let payload = match file.payload() {
+                Ok(payload) => payload,
+                Err(_) => {
+                    eprintln!(
+                        "{}: {:#x}: could not decompress {} payload",
+                        command.input.display(),
+                        file.offset,
+                        describe_compression(file.compression)
+                    );
+                    success = false;
+                    continue;
+                }
+            };
+            // Uncompressed PTX is padded with zeros
+            let payload = match file.kind {
+                FatbinFileKind::Ptx => {
+                    let len = payload
+                        .iter()
+                        .rposition(|byte| *byte != 0)
+                        .map_or(0, |i| i + 1);
+                    &payload[..len]
+                }
+                FatbinFileKind::Elf | FatbinFileKind::Archive => &payload[..],
+            };
+            fs::write(&path, payload).map_err(|err| format!("{}: {}", path.display(), err))?;
+            println!("{}", path.display());
+        }
+    }
+    Ok(success)
+}
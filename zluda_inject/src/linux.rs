@@ -0,0 +1,340 @@
+use std::env;
+use std::ffi::OsString;
+use std::os::unix::fs::symlink;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::{error::Error, fs, io};
+
+use argh::FromArgs;
+use tempfile::TempDir;
+
+static ZLUDA_DUMP_SO: &str = "libzluda_dump.so";
+static REDIRECT_SO: &str = "libzluda_redirect.so";
+
+// ZLUDA build of a library and the names applications load it by. Library
+// is used if given on the command line or if it is present in the directory
+// of this executable, libraries marked as required must be found
+struct Library {
+    option: &'static str,
+    file_name: &'static str,
+    linux_names: &'static [&'static str],
+    required: bool,
+}
+
+static NVCUDA: Library = Library {
+    option: "--nvcuda",
+    file_name: "libnvcuda.so",
+    linux_names: &["libcuda.so", "libcuda.so.1"],
+    required: true,
+};
+static NVML: Library = Library {
+    option: "--nvml",
+    file_name: "libnvml.so",
+    linux_names: &["libnvidia-ml.so", "libnvidia-ml.so.1"],
+    required: true,
+};
+static NCCL: Library = Library {
+    option: "--nccl",
+    file_name: "libnccl.so",
+    linux_names: &["libnccl.so", "libnccl.so.2"],
+    required: false,
+};
+static NVRTC: Library = Library {
+    option: "--nvrtc",
+    file_name: "libnvrtc.so",
+    linux_names: &["libnvrtc.so", "libnvrtc.so.10", "libnvrtc.so.11"],
+    required: false,
+};
+static NVOPTIX: Library = Library {
+    option: "--nvoptix",
+    file_name: "libnvoptix.so",
+    linux_names: &["liboptix.so.6.5.0", "liboptix.so.6.6.0"],
+    required: false,
+};
+static CUBLAS: Library = Library {
+    option: "--cublas",
+    file_name: "libcublas.so",
+    linux_names: &["libcublas.so", "libcublas.so.10", "libcublas.so.11"],
+    required: false,
+};
+static CUBLASLT: Library = Library {
+    option: "--cublaslt",
+    file_name: "libcublasLt.so",
+    linux_names: &["libcublasLt.so", "libcublasLt.so.11"],
+    required: false,
+};
+static CUSPARSE: Library = Library {
+    option: "--cusparse",
+    file_name: "libcusparse.so",
+    linux_names: &["libcusparse.so", "libcusparse.so.11"],
+    required: false,
+};
+static CUDNN: Library = Library {
+    option: "--cudnn",
+    file_name: "libcudnn.so",
+    linux_names: &["libcudnn.so", "libcudnn.so.7", "libcudnn.so.8"],
+    required: false,
+};
+static CUFFT: Library = Library {
+    option: "--cufft",
+    file_name: "libcufft.so",
+    linux_names: &["libcufft.so", "libcufft.so.10"],
+    required: false,
+};
+
+#[derive(FromArgs)]
+/// Launch application with custom CUDA libraries
+struct ProgramArguments {
+    /// library to be used instead of system libnccl.so. If not provided, will use libnccl.so from its own directory, if present
+    #[argh(option)]
+    nccl: Option<PathBuf>,
+
+    /// library to be used instead of system libnvrtc.so. If not provided, will use libnvrtc.so from its own directory, if present
+    #[argh(option)]
+    nvrtc: Option<PathBuf>,
+
+    /// library to be used instead of system libcuda.so. If not provided, will use libnvcuda.so from its own directory
+    #[argh(option)]
+    nvcuda: Option<PathBuf>,
+
+    /// library to be used instead of system libnvidia-ml.so. If not provided, will use libnvml.so from its own directory
+    #[argh(option)]
+    nvml: Option<PathBuf>,
+
+    /// library to be used instead of system liboptix.so. If not provided, will use libnvoptix.so from its own directory, if present
+    #[argh(option)]
+    nvoptix: Option<PathBuf>,
+
+    /// library to be used instead of system libcublas.so. If not provided, will use libcublas.so from its own directory, if present
+    #[argh(option)]
+    cublas: Option<PathBuf>,
+
+    /// library to be used instead of system libcublasLt.so. If not provided, will use libcublasLt.so from its own directory, if present
+    #[argh(option)]
+    cublaslt: Option<PathBuf>,
+
+    /// library to be used instead of system libcusparse.so. If not provided, will use libcusparse.so from its own directory, if present
+    #[argh(option)]
+    cusparse: Option<PathBuf>,
+
+    /// library to be used instead of system libcudnn.so. If not provided, will use libcudnn.so from its own directory, if present
+    #[argh(option)]
+    cudnn: Option<PathBuf>,
+
+    /// library to be used instead of system libcufft.so. If not provided, will use libcufft.so from its own directory, if present
+    #[argh(option)]
+    cufft: Option<PathBuf>,
+
+    /// log CUDA calls with ZLUDA dumper, which forwards them to the library selected with --nvcuda
+    #[argh(switch)]
+    dump: bool,
+
+    /// executable to be launched with custom CUDA libraries
+    #[argh(positional)]
+    exe: String,
+
+    /// arguments to the executable
+    #[argh(positional)]
+    args: Vec<String>,
+}
+
+pub fn main_impl() -> Result<(), Box<dyn Error>> {
+    let raw_args = argh::from_env::<ProgramArguments>();
+    let normalized_args = NormalizedArguments::new(raw_args)?;
+    let environment = Environment::setup(&normalized_args.libraries)?;
+    let mut command = Command::new(&normalized_args.exe);
+    command
+        .args(&normalized_args.args)
+        .env("LD_LIBRARY_PATH", environment.library_path());
+    if let Some(ref cuda_lib) = normalized_args.zluda_cuda_lib {
+        command.env("ZLUDA_CUDA_LIB", cuda_lib);
+    }
+    // Catches applications that load CUDA by absolute path
+    if let Some(ref redirect_path) = normalized_args.redirect_path {
+        command
+            .env("LD_PRELOAD", preload(redirect_path))
+            .env("ZLUDA_REDIRECT_DIR", environment.temp_dir.path());
+    }
+    unsafe { command.pre_exec(kill_child_on_process_exit) };
+    let status = command
+        .status()
+        .map_err(|err| format!("{}: {}", normalized_args.exe, err))?;
+    // Remove the temporary directory before exiting, process::exit(...)
+    // does not run destructors
+    drop(environment);
+    let exit_code = match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        // Same convention as shells
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    };
+    process::exit(exit_code)
+}
+
+struct NormalizedArguments {
+    // (library, absolute path to its ZLUDA build)
+    libraries: Vec<(&'static Library, PathBuf)>,
+    // Set with --dump, unless already present in the environment
+    zluda_cuda_lib: Option<PathBuf>,
+    // Only if present in the directory of this executable
+    redirect_path: Option<PathBuf>,
+    exe: String,
+    args: Vec<String>,
+}
+
+impl NormalizedArguments {
+    fn new(prog_args: ProgramArguments) -> Result<Self, Box<dyn Error>> {
+        let current_exe = env::current_exe()?;
+        let zluda_dir = current_exe.parent().unwrap();
+        let mut libraries = Vec::new();
+        for (library, path) in [
+            (&NCCL, prog_args.nccl),
+            (&NVRTC, prog_args.nvrtc),
+            (&NVCUDA, prog_args.nvcuda),
+            (&NVML, prog_args.nvml),
+            (&NVOPTIX, prog_args.nvoptix),
+            (&CUBLAS, prog_args.cublas),
+            (&CUBLASLT, prog_args.cublaslt),
+            (&CUSPARSE, prog_args.cusparse),
+            (&CUDNN, prog_args.cudnn),
+            (&CUFFT, prog_args.cufft),
+        ] {
+            if let Some(path) = Self::get_absolute_path_or_default(zluda_dir, library, path)? {
+                libraries.push((library, path));
+            }
+        }
+        let mut zluda_cuda_lib = None;
+        if prog_args.dump {
+            let nvcuda = libraries
+                .iter_mut()
+                .find(|(library, _)| library.file_name == NVCUDA.file_name)
+                .unwrap();
+            let dump_path = Self::get_absolute_path(zluda_dir.join(ZLUDA_DUMP_SO))?;
+            let cuda_lib = std::mem::replace(&mut nvcuda.1, dump_path);
+            if env::var_os("ZLUDA_CUDA_LIB").is_none() {
+                zluda_cuda_lib = Some(cuda_lib);
+            }
+        }
+        let redirect_path = zluda_dir.join(REDIRECT_SO);
+        let redirect_path = if redirect_path.exists() {
+            Some(Self::get_absolute_path(redirect_path)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            libraries,
+            zluda_cuda_lib,
+            redirect_path,
+            exe: prog_args.exe,
+            args: prog_args.args,
+        })
+    }
+
+    fn get_absolute_path_or_default(
+        zluda_dir: &Path,
+        library: &Library,
+        path: Option<PathBuf>,
+    ) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        if let Some(path) = path {
+            return Self::get_absolute_path(path).map(Some);
+        }
+        let default = zluda_dir.join(library.file_name);
+        if default.exists() {
+            Self::get_absolute_path(default).map(Some)
+        } else if library.required {
+            Err(format!(
+                "{}: not found, use {} to select the library",
+                default.display(),
+                library.option
+            )
+            .into())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_absolute_path(path: PathBuf) -> Result<PathBuf, Box<dyn Error>> {
+        fs::canonicalize(&path).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+}
+
+// Temporary directory with libraries symlinked under the names applications
+// load them by. It is prepended to LD_LIBRARY_PATH of the child process,
+// which is inherited by all its children
+struct Environment {
+    temp_dir: TempDir,
+}
+
+impl Environment {
+    fn setup(libraries: &[(&'static Library, PathBuf)]) -> io::Result<Self> {
+        let temp_dir = TempDir::new()?;
+        for (library, path) in libraries {
+            for name in library.linux_names {
+                symlink(path, temp_dir.path().join(name))?;
+            }
+        }
+        Ok(Self { temp_dir })
+    }
+
+    fn library_path(&self) -> OsString {
+        let mut result = self.temp_dir.path().as_os_str().to_owned();
+        if let Some(old_path) = env::var_os("LD_LIBRARY_PATH") {
+            if !old_path.is_empty() {
+                result.push(":");
+                result.push(old_path);
+            }
+        }
+        result
+    }
+}
+
+fn preload(redirect_path: &Path) -> OsString {
+    let mut result = redirect_path.as_os_str().to_owned();
+    if let Some(old_preload) = env::var_os("LD_PRELOAD") {
+        if !old_preload.is_empty() {
+            result.push(":");
+            result.push(old_preload);
+        }
+    }
+    result
+}
+
+// Runs in the child between fork and exec
+fn kill_child_on_process_exit() -> io::Result<()> {
+    if unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Environment, CUBLAS, NVCUDA};
+    use std::fs;
+
+    #[test]
+    fn symlinks_every_linux_name() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let nvcuda = dir.path().join("libnvcuda.so");
+        let cublas = dir.path().join("libcublas.so");
+        fs::write(&nvcuda, "").unwrap();
+        fs::write(&cublas, "").unwrap();
+        let environment =
+            Environment::setup(&[(&NVCUDA, nvcuda.clone()), (&CUBLAS, cublas.clone())]).unwrap();
+        let temp_dir = environment.temp_dir.path();
+        assert_eq!(
+            fs::read_link(temp_dir.join("libcuda.so.1")).unwrap(),
+            nvcuda
+        );
+        assert_eq!(fs::read_link(temp_dir.join("libcuda.so")).unwrap(), nvcuda);
+        assert_eq!(
+            fs::read_link(temp_dir.join("libcublas.so.11")).unwrap(),
+            cublas
+        );
+        assert!(environment
+            .library_path()
+            .to_string_lossy()
+            .starts_with(&*temp_dir.to_string_lossy()));
+    }
+}
@@ -3,11 +3,18 @@
 mod win;
 #[cfg(target_os = "windows")]
 mod bin;
+#[cfg(target_os = "linux")]
+mod linux;
 
 #[cfg(target_os = "windows")]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     bin::main_impl()
 }
 
-#[cfg(not(target_os = "windows"))]
+#[cfg(target_os = "linux")]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    linux::main_impl()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
 fn main() {}
@@ -1,3 +1,6 @@
+// Helpers are built only on Windows, see build.rs
+#![cfg(windows)]
+
 use std::{
     env, io,
     path::PathBuf,
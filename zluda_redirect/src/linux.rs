@@ -0,0 +1,175 @@
+// Loaded with LD_PRELOAD. Applications and libraries that dlopen(...) NVIDIA
+// libraries by absolute path or find them through their own RPATH bypass
+// LD_LIBRARY_PATH, here we redirect such loads to ZLUDA builds
+use std::ffi::{CStr, CString, OsStr};
+use std::io::Write;
+use std::os::raw::{c_char, c_int, c_long, c_void};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, mem};
+
+// Names of libraries implemented by ZLUDA, as they appear in the ZLUDA
+// directory (see linux_names and dump_names in Cargo.toml of every project)
+const LIBRARY_NAMES: &[&str] = &[
+    "libcuda.so",
+    "libcuda.so.1",
+    "libnvidia-ml.so",
+    "libnvidia-ml.so.1",
+    "libcublas.so",
+    "libcublas.so.10",
+    "libcublas.so.11",
+    "libcublasLt.so",
+    "libcublasLt.so.11",
+    "libcusparse.so",
+    "libcusparse.so.11",
+    "libcudnn.so",
+    "libcudnn.so.7",
+    "libcudnn.so.8",
+    "libcufft.so",
+    "libcufft.so.10",
+    "libnvrtc.so",
+    "libnvrtc.so.10",
+    "libnvrtc.so.11",
+    "libnccl.so",
+    "libnccl.so.2",
+    "liboptix.so.6.5.0",
+    "liboptix.so.6.6.0",
+];
+
+// Directory with libraries to redirect to, defaults to the directory of this
+// library
+const REDIRECT_DIR_ENV: &str = "ZLUDA_REDIRECT_DIR";
+// Every redirection is logged to this file or to stderr if it is not set
+const REDIRECT_LOG_ENV: &str = "ZLUDA_REDIRECT_LOG";
+
+#[no_mangle]
+pub unsafe extern "C" fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void {
+    let real_dlopen =
+        match real_fn::<unsafe extern "C" fn(*const c_char, c_int) -> *mut c_void>(b"dlopen\0") {
+            Some(real_dlopen) => real_dlopen,
+            None => return std::ptr::null_mut(),
+        };
+    match redirect(filename) {
+        Some(new_filename) => real_dlopen(new_filename.as_ptr(), flags),
+        None => real_dlopen(filename, flags),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dlmopen(
+    lmid: c_long,
+    filename: *const c_char,
+    flags: c_int,
+) -> *mut c_void {
+    let real_dlmopen = match real_fn::<
+        unsafe extern "C" fn(c_long, *const c_char, c_int) -> *mut c_void,
+    >(b"dlmopen\0")
+    {
+        Some(real_dlmopen) => real_dlmopen,
+        None => return std::ptr::null_mut(),
+    };
+    match redirect(filename) {
+        Some(new_filename) => real_dlmopen(lmid, new_filename.as_ptr(), flags),
+        None => real_dlmopen(lmid, filename, flags),
+    }
+}
+
+unsafe fn real_fn<T: Copy>(name: &[u8]) -> Option<T> {
+    let symbol = libc::dlsym(libc::RTLD_NEXT, name.as_ptr() as _);
+    if symbol.is_null() {
+        None
+    } else {
+        Some(mem::transmute_copy(&symbol))
+    }
+}
+
+unsafe fn redirect(filename: *const c_char) -> Option<CString> {
+    if filename.is_null() {
+        return None;
+    }
+    let filename = OsStr::from_bytes(CStr::from_ptr(filename).to_bytes());
+    let redirect_dir = match env::var_os(REDIRECT_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => current_library_dir()?,
+    };
+    let new_filename = redirect_target(Path::new(filename), &redirect_dir)?;
+    if !new_filename.exists() {
+        return None;
+    }
+    log(format_args!(
+        "ZLUDA_REDIRECT: {} -> {}",
+        Path::new(filename).display(),
+        new_filename.display()
+    ));
+    CString::new(new_filename.into_os_string().into_vec()).ok()
+}
+
+// Returns path of the ZLUDA library that should be loaded instead of
+// `filename`, if `filename` is one of the libraries ZLUDA implements. Loads
+// by bare name are redirected too, RPATH takes precedence over
+// LD_LIBRARY_PATH
+fn redirect_target(filename: &Path, redirect_dir: &Path) -> Option<PathBuf> {
+    let name = filename.file_name()?;
+    if !LIBRARY_NAMES
+        .iter()
+        .any(|library| OsStr::new(library) == name)
+    {
+        return None;
+    }
+    let new_filename = redirect_dir.join(name);
+    // Already a ZLUDA library
+    if filename == new_filename {
+        return None;
+    }
+    Some(new_filename)
+}
+
+unsafe fn current_library_dir() -> Option<PathBuf> {
+    let mut info = mem::zeroed::<libc::Dl_info>();
+    if libc::dladdr(current_library_dir as *const c_void, &mut info) == 0
+        || info.dli_fname.is_null()
+    {
+        return None;
+    }
+    let path = Path::new(OsStr::from_bytes(CStr::from_ptr(info.dli_fname).to_bytes()));
+    Some(path.parent()?.to_path_buf())
+}
+
+fn log(message: std::fmt::Arguments) {
+    // Nowhere to report failures
+    let _ = match env::var_os(REDIRECT_LOG_ENV) {
+        Some(path) => fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", message)),
+        None => writeln!(io::stderr(), "{}", message),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redirect_target;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn redirects_only_known_libraries() {
+        let dir = Path::new("/opt/zluda");
+        assert_eq!(
+            redirect_target(Path::new("/usr/lib/x86_64-linux-gnu/libcuda.so.1"), dir),
+            Some(PathBuf::from("/opt/zluda/libcuda.so.1"))
+        );
+        assert_eq!(
+            redirect_target(Path::new("libcublasLt.so.11"), dir),
+            Some(PathBuf::from("/opt/zluda/libcublasLt.so.11"))
+        );
+        assert_eq!(
+            redirect_target(Path::new("/usr/lib/libcudart.so.11.0"), dir),
+            None
+        );
+        assert_eq!(
+            redirect_target(Path::new("/opt/zluda/libcuda.so"), dir),
+            None
+        );
+    }
+}